@@ -0,0 +1,23 @@
+// Génère l'en-tête C dans `OUT_DIR` ; `include/license_agent.h`, celui du dépôt,
+// est tenu à jour par `cbindgen` et vérifié par les tests
+
+fn main() {
+    let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    println!("cargo:rerun-if-changed=src/lib.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+
+    // `guard` ne peut rien contre une panique compilée en `abort`
+    if std::env::var("CARGO_CFG_PANIC").as_deref() == Ok("abort") {
+        println!("cargo:warning=panic = \"abort\": a panic aborts the host process, build with --profile release-ffi");
+    }
+
+    let config = cbindgen::Config::from_file(format!("{}/cbindgen.toml", crate_dir)).expect("invalid cbindgen.toml");
+    let out_dir = std::env::var("OUT_DIR").unwrap();
+    match cbindgen::generate_with_config(&crate_dir, config) {
+        Ok(bindings) => {
+            bindings.write_to_file(format!("{}/license_agent.h", out_dir));
+        }
+        // Sans en-tête régénéré, celui du dépôt reste utilisable
+        Err(e) => println!("cargo:warning=Failed to generate C header: {}", e),
+    }
+}
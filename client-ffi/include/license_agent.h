@@ -0,0 +1,140 @@
+#ifndef LICENSE_AGENT_H
+#define LICENSE_AGENT_H
+
+/* Généré par cbindgen depuis client-ffi/src/lib.rs : ne pas modifier. */
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+/**
+ * Code de retour des fonctions `lsa_*`
+ */
+typedef enum LsaStatus {
+  LSA_STATUS_OK = 0,
+  /**
+   * Pointeur nul ou chaîne non UTF-8
+   */
+  LSA_STATUS_INVALID_ARGUMENT = 1,
+  /**
+   * Agent injoignable
+   */
+  LSA_STATUS_CONNECT = 2,
+  LSA_STATUS_IO = 3,
+  LSA_STATUS_TIMEOUT = 4,
+  LSA_STATUS_PROTOCOL = 5,
+  /**
+   * Erreur renvoyée par l'agent (limite de débit, accès refusé...)
+   */
+  LSA_STATUS_AGENT = 6,
+  /**
+   * Réponse non authentifiée par la clé de l'application
+   */
+  LSA_STATUS_NOT_AUTHENTIC = 7,
+  LSA_STATUS_CONFIG = 8,
+  LSA_STATUS_INTERNAL = 9,
+} LsaStatus;
+
+/**
+ * Connexion à l'agent (opaque)
+ */
+typedef struct LsaClient LsaClient;
+
+/**
+ * Résultat de validation, libéré par [`lsa_free_result`]
+ */
+typedef struct LsaResult {
+  bool valid;
+  /**
+   * Expiration (secondes Unix), 0 si la licence n'expire pas
+   */
+  int64_t expires_at;
+  /**
+   * Fonctionnalités incluses (chaînes UTF-8 terminées par NUL)
+   */
+  char **features;
+  size_t features_len;
+  /**
+   * Motif du refus, NULL si la licence est valide
+   */
+  char *error;
+} LsaResult;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Ouvre une connexion à l'agent
+ *
+ * `socket_path` NULL : `LICENSE_AGENT_SOCKET` ou le socket par défaut. La clé
+ * d'application est lue dans `LICENSE_AGENT_APP_ID` / `LICENSE_AGENT_APP_KEY`.
+ * `timeout_ms` à 0 conserve le délai par défaut. En cas de succès, `*out`
+ * reçoit le client, à libérer avec [`lsa_disconnect`].
+ *
+ * # Safety
+ *
+ * `socket_path` est NULL ou une chaîne terminée par NUL ; `out` est un pointeur valide.
+ */
+enum LsaStatus lsa_connect(const char *socket_path, uint32_t timeout_ms, struct LsaClient **out);
+
+/**
+ * Ferme la connexion et libère le client
+ *
+ * # Safety
+ *
+ * `client` est NULL ou a été obtenu par [`lsa_connect`], et n'est plus utilisé ensuite.
+ */
+void lsa_disconnect(struct LsaClient *client);
+
+/**
+ * Valide une licence
+ *
+ * Une licence refusée retourne `LSA_STATUS_OK` avec `valid == false`. En cas de
+ * succès, `*out` reçoit le résultat, à libérer avec [`lsa_free_result`].
+ *
+ * # Safety
+ *
+ * `client` provient de [`lsa_connect`], `token` pointe sur `token_len` octets
+ * et `out` est un pointeur valide.
+ */
+enum LsaStatus lsa_validate(const struct LsaClient *client,
+                            const uint8_t *token,
+                            size_t token_len,
+                            struct LsaResult **out);
+
+/**
+ * Indique si la licence est valide et inclut la fonctionnalité `feature`
+ *
+ * # Safety
+ *
+ * `client` provient de [`lsa_connect`], `token` pointe sur `token_len` octets,
+ * `feature` est une chaîne terminée par NUL et `out` est un pointeur valide.
+ */
+enum LsaStatus lsa_has_feature(const struct LsaClient *client,
+                               const uint8_t *token,
+                               size_t token_len,
+                               const char *feature,
+                               bool *out);
+
+/**
+ * Libère un résultat de [`lsa_validate`]
+ *
+ * # Safety
+ *
+ * `result` est NULL ou a été obtenu par [`lsa_validate`], et n'est plus utilisé ensuite.
+ */
+void lsa_free_result(struct LsaResult *result);
+
+/**
+ * Message de la dernière erreur du thread appelant, NULL s'il n'y en a pas
+ *
+ * La chaîne reste valide jusqu'au prochain appel `lsa_*` sur ce thread.
+ */
+const char *lsa_last_error(void);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* LICENSE_AGENT_H */
@@ -0,0 +1,267 @@
+//! API C de License Secret Agent
+//!
+//! Enveloppe le client bloquant de `license-agent-client` pour les applications
+//! C, C++ ou Python (ctypes/cffi). L'en-tête `include/license_agent.h` est généré
+//! par cbindgen à la compilation.
+//!
+//! Les fonctions retournent un [`LsaStatus`] ; en cas d'erreur, [`lsa_last_error`]
+//! donne le message associé pour le thread appelant.
+
+use license_agent_client::{ClientConfig, ClientError, LicenseClient, ValidationResult};
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::time::Duration;
+
+/// Code de retour des fonctions `lsa_*`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LsaStatus {
+    Ok = 0,
+    /// Pointeur nul ou chaîne non UTF-8
+    InvalidArgument = 1,
+    /// Agent injoignable
+    Connect = 2,
+    Io = 3,
+    Timeout = 4,
+    Protocol = 5,
+    /// Erreur renvoyée par l'agent (limite de débit, accès refusé...)
+    Agent = 6,
+    /// Réponse non authentifiée par la clé de l'application
+    NotAuthentic = 7,
+    Config = 8,
+    Internal = 9,
+}
+
+/// Connexion à l'agent (opaque)
+pub struct LsaClient {
+    client: LicenseClient,
+}
+
+/// Résultat de validation, libéré par [`lsa_free_result`]
+#[repr(C)]
+pub struct LsaResult {
+    pub valid: bool,
+    /// Expiration (secondes Unix), 0 si la licence n'expire pas
+    pub expires_at: i64,
+    /// Fonctionnalités incluses (chaînes UTF-8 terminées par NUL)
+    pub features: *mut *mut c_char,
+    pub features_len: usize,
+    /// Motif du refus, NULL si la licence est valide
+    pub error: *mut c_char,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: impl Into<String>) {
+    let message = CString::new(message.into().replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+fn fail(status: LsaStatus, message: impl Into<String>) -> LsaStatus {
+    set_last_error(message);
+    status
+}
+
+fn status_for(error: &ClientError) -> LsaStatus {
+    match error {
+        ClientError::Connect { .. } => LsaStatus::Connect,
+        ClientError::Io(_) => LsaStatus::Io,
+        ClientError::Timeout(_) => LsaStatus::Timeout,
+        ClientError::Protocol(_) => LsaStatus::Protocol,
+        ClientError::Agent { .. } => LsaStatus::Agent,
+        ClientError::NotAuthentic(_) => LsaStatus::NotAuthentic,
+        ClientError::Config(_) => LsaStatus::Config,
+    }
+}
+
+/// Exécute `f` sans laisser une panique traverser la frontière C
+///
+/// Nécessite `panic = "unwind"` : la bibliothèque est livrée avec le profil
+/// `release-ffi`, le profil `release` de l'espace de travail s'arrêtant sur panique.
+fn guard(f: impl FnOnce() -> Result<(), LsaStatus>) -> LsaStatus {
+    match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => LsaStatus::Ok,
+        Ok(Err(status)) => status,
+        Err(_) => fail(LsaStatus::Internal, "Internal error (panic)"),
+    }
+}
+
+unsafe fn client_ref<'a>(client: *const LsaClient) -> Result<&'a LicenseClient, LsaStatus> {
+    client
+        .as_ref()
+        .map(|client| &client.client)
+        .ok_or_else(|| fail(LsaStatus::InvalidArgument, "client is NULL"))
+}
+
+unsafe fn token_slice<'a>(token: *const u8, token_len: usize) -> Result<&'a [u8], LsaStatus> {
+    if token.is_null() {
+        return Err(fail(LsaStatus::InvalidArgument, "license token is NULL"));
+    }
+    Ok(std::slice::from_raw_parts(token, token_len))
+}
+
+unsafe fn str_arg<'a>(value: *const c_char, name: &str) -> Result<&'a str, LsaStatus> {
+    if value.is_null() {
+        return Err(fail(LsaStatus::InvalidArgument, format!("{} is NULL", name)));
+    }
+    CStr::from_ptr(value)
+        .to_str()
+        .map_err(|_| fail(LsaStatus::InvalidArgument, format!("{} is not valid UTF-8", name)))
+}
+
+fn c_string(value: &str) -> *mut c_char {
+    CString::new(value.replace('\0', " ")).unwrap_or_default().into_raw()
+}
+
+/// Ouvre une connexion à l'agent
+///
+/// `socket_path` NULL : `LICENSE_AGENT_SOCKET` ou le socket par défaut. La clé
+/// d'application est lue dans `LICENSE_AGENT_APP_ID` / `LICENSE_AGENT_APP_KEY`.
+/// `timeout_ms` à 0 conserve le délai par défaut. En cas de succès, `*out`
+/// reçoit le client, à libérer avec [`lsa_disconnect`].
+///
+/// # Safety
+///
+/// `socket_path` est NULL ou une chaîne terminée par NUL ; `out` est un pointeur valide.
+#[no_mangle]
+pub unsafe extern "C" fn lsa_connect(socket_path: *const c_char, timeout_ms: u32, out: *mut *mut LsaClient) -> LsaStatus {
+    guard(|| {
+        if out.is_null() {
+            return Err(fail(LsaStatus::InvalidArgument, "out is NULL"));
+        }
+        *out = std::ptr::null_mut();
+
+        let mut config = ClientConfig::from_env().map_err(|e| fail(status_for(&e), e.to_string()))?;
+        if !socket_path.is_null() {
+            config.socket_path = str_arg(socket_path, "socket_path")?.into();
+        }
+        if timeout_ms > 0 {
+            config = config
+                .with_request_timeout(Duration::from_millis(timeout_ms.into()))
+                .with_connect_timeout(Duration::from_millis(timeout_ms.into()));
+        }
+
+        let client = LicenseClient::new(config);
+        client.connect().map_err(|e| fail(status_for(&e), e.to_string()))?;
+        *out = Box::into_raw(Box::new(LsaClient { client }));
+        Ok(())
+    })
+}
+
+/// Ferme la connexion et libère le client
+///
+/// # Safety
+///
+/// `client` est NULL ou a été obtenu par [`lsa_connect`], et n'est plus utilisé ensuite.
+#[no_mangle]
+pub unsafe extern "C" fn lsa_disconnect(client: *mut LsaClient) {
+    if !client.is_null() {
+        drop(Box::from_raw(client));
+    }
+}
+
+/// Valide une licence
+///
+/// Une licence refusée retourne `LSA_STATUS_OK` avec `valid == false`. En cas de
+/// succès, `*out` reçoit le résultat, à libérer avec [`lsa_free_result`].
+///
+/// # Safety
+///
+/// `client` provient de [`lsa_connect`], `token` pointe sur `token_len` octets
+/// et `out` est un pointeur valide.
+#[no_mangle]
+pub unsafe extern "C" fn lsa_validate(
+    client: *const LsaClient,
+    token: *const u8,
+    token_len: usize,
+    out: *mut *mut LsaResult,
+) -> LsaStatus {
+    guard(|| {
+        if out.is_null() {
+            return Err(fail(LsaStatus::InvalidArgument, "out is NULL"));
+        }
+        *out = std::ptr::null_mut();
+
+        let result = client_ref(client)?
+            .validate(token_slice(token, token_len)?)
+            .map_err(|e| fail(status_for(&e), e.to_string()))?;
+        *out = Box::into_raw(Box::new(to_c_result(result)));
+        Ok(())
+    })
+}
+
+/// Indique si la licence est valide et inclut la fonctionnalité `feature`
+///
+/// # Safety
+///
+/// `client` provient de [`lsa_connect`], `token` pointe sur `token_len` octets,
+/// `feature` est une chaîne terminée par NUL et `out` est un pointeur valide.
+#[no_mangle]
+pub unsafe extern "C" fn lsa_has_feature(
+    client: *const LsaClient,
+    token: *const u8,
+    token_len: usize,
+    feature: *const c_char,
+    out: *mut bool,
+) -> LsaStatus {
+    guard(|| {
+        if out.is_null() {
+            return Err(fail(LsaStatus::InvalidArgument, "out is NULL"));
+        }
+        *out = false;
+
+        let feature = str_arg(feature, "feature")?;
+        *out = client_ref(client)?
+            .has_feature(token_slice(token, token_len)?, feature)
+            .map_err(|e| fail(status_for(&e), e.to_string()))?;
+        Ok(())
+    })
+}
+
+/// Libère un résultat de [`lsa_validate`]
+///
+/// # Safety
+///
+/// `result` est NULL ou a été obtenu par [`lsa_validate`], et n'est plus utilisé ensuite.
+#[no_mangle]
+pub unsafe extern "C" fn lsa_free_result(result: *mut LsaResult) {
+    if result.is_null() {
+        return;
+    }
+    let result = Box::from_raw(result);
+    if !result.features.is_null() {
+        let features = Vec::from_raw_parts(result.features, result.features_len, result.features_len);
+        for feature in features {
+            drop(CString::from_raw(feature));
+        }
+    }
+    if !result.error.is_null() {
+        drop(CString::from_raw(result.error));
+    }
+}
+
+/// Message de la dernière erreur du thread appelant, NULL s'il n'y en a pas
+///
+/// La chaîne reste valide jusqu'au prochain appel `lsa_*` sur ce thread.
+#[no_mangle]
+pub extern "C" fn lsa_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(std::ptr::null(), |message| message.as_ptr()))
+}
+
+fn to_c_result(result: ValidationResult) -> LsaResult {
+    let features: Box<[*mut c_char]> = result.features.iter().map(|feature| c_string(feature)).collect();
+    let features_len = features.len();
+    // Tranche boxée : capacité égale à la longueur, requis par `lsa_free_result`
+    let features = Box::into_raw(features) as *mut *mut c_char;
+
+    LsaResult {
+        valid: result.valid,
+        expires_at: result.expires_at.map_or(0, |at| at.timestamp()),
+        features,
+        features_len,
+        error: result.error.as_deref().map_or(std::ptr::null_mut(), c_string),
+    }
+}
@@ -0,0 +1,106 @@
+// Tests de l'API C, appelée par FFI contre un agent en processus
+
+#[cfg(test)]
+mod tests {
+    use base64::{engine::general_purpose, Engine as _};
+    use chrono::{Duration, Utc};
+    use license_agent::{
+        lsa_connect, lsa_disconnect, lsa_free_result, lsa_has_feature, lsa_last_error, lsa_validate, LsaClient,
+        LsaResult, LsaStatus,
+    };
+    use license_agent_testing::{bind_current_uid, TestAgent, APP_ID};
+    use license_secret_agent::roles::Role;
+    use std::ffi::{CStr, CString};
+    use std::ptr;
+
+    fn last_error() -> String {
+        let message = lsa_last_error();
+        assert!(!message.is_null());
+        unsafe { CStr::from_ptr(message) }.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn test_c_api_against_agent() {
+        let agent = TestAgent::start_blocking(|management| bind_current_uid(management, Role::App));
+        let socket = CString::new(agent.socket().to_str().unwrap()).unwrap();
+        let valid = agent.license(&["premium", "export"], Utc::now() + Duration::days(30));
+        let expired = agent.license(&["premium"], Utc::now() - Duration::days(1));
+
+        // Seul test du binaire à modifier l'environnement
+        std::env::set_var("LICENSE_AGENT_APP_ID", APP_ID);
+        std::env::set_var("LICENSE_AGENT_APP_KEY", general_purpose::STANDARD.encode(agent.app_key()));
+
+        unsafe {
+            let mut client: *mut LsaClient = ptr::null_mut();
+            assert_eq!(lsa_connect(socket.as_ptr(), 1000, &mut client), LsaStatus::Ok);
+            assert!(!client.is_null());
+
+            // Licence valide : fonctionnalités copiées dans le résultat
+            let mut result: *mut LsaResult = ptr::null_mut();
+            assert_eq!(lsa_validate(client, valid.as_ptr(), valid.len(), &mut result), LsaStatus::Ok);
+            let license = &*result;
+            assert!(license.valid);
+            assert!(license.expires_at > Utc::now().timestamp());
+            assert!(license.error.is_null());
+            let features: Vec<String> = std::slice::from_raw_parts(license.features, license.features_len)
+                .iter()
+                .map(|feature| CStr::from_ptr(*feature).to_string_lossy().into_owned())
+                .collect();
+            assert_eq!(features, ["premium", "export"]);
+            lsa_free_result(result);
+
+            // Licence refusée : pas une erreur d'appel
+            assert_eq!(lsa_validate(client, expired.as_ptr(), expired.len(), &mut result), LsaStatus::Ok);
+            assert!(!(*result).valid);
+            assert!(CStr::from_ptr((*result).error).to_str().unwrap().contains("expired"));
+            lsa_free_result(result);
+
+            let premium = CString::new("premium").unwrap();
+            let enterprise = CString::new("enterprise").unwrap();
+            let mut has = false;
+            assert_eq!(lsa_has_feature(client, valid.as_ptr(), valid.len(), premium.as_ptr(), &mut has), LsaStatus::Ok);
+            assert!(has);
+            assert_eq!(lsa_has_feature(client, valid.as_ptr(), valid.len(), enterprise.as_ptr(), &mut has), LsaStatus::Ok);
+            assert!(!has);
+
+            // Arguments invalides
+            assert_eq!(lsa_validate(client, ptr::null(), 0, &mut result), LsaStatus::InvalidArgument);
+            assert!(result.is_null());
+            assert_eq!(lsa_has_feature(ptr::null(), valid.as_ptr(), valid.len(), premium.as_ptr(), &mut has), LsaStatus::InvalidArgument);
+            assert_eq!(last_error(), "client is NULL");
+
+            lsa_disconnect(client);
+            lsa_free_result(ptr::null_mut());
+
+            // Réponses signées avec une autre clé
+            std::env::set_var("LICENSE_AGENT_APP_KEY", general_purpose::STANDARD.encode([1u8; 32]));
+            assert_eq!(lsa_connect(socket.as_ptr(), 1000, &mut client), LsaStatus::Ok);
+            assert_eq!(lsa_validate(client, valid.as_ptr(), valid.len(), &mut result), LsaStatus::NotAuthentic);
+            lsa_disconnect(client);
+
+            // Erreur de l'agent : application non enregistrée
+            std::env::set_var("LICENSE_AGENT_APP_ID", "unknown");
+            assert_eq!(lsa_connect(socket.as_ptr(), 1000, &mut client), LsaStatus::Ok);
+            assert_eq!(lsa_validate(client, valid.as_ptr(), valid.len(), &mut result), LsaStatus::Agent);
+            assert!(result.is_null());
+            assert!(last_error().contains("Unknown application"));
+            lsa_disconnect(client);
+
+            // Agent absent
+            let missing = CString::new(agent.dir().join("missing.sock").to_str().unwrap()).unwrap();
+            assert_eq!(lsa_connect(missing.as_ptr(), 100, &mut client), LsaStatus::Connect);
+            assert!(client.is_null());
+        }
+    }
+
+    #[test]
+    fn test_committed_header_is_up_to_date() {
+        let generated = std::fs::read_to_string(concat!(env!("OUT_DIR"), "/license_agent.h"))
+            .expect("C header not generated by build.rs");
+        let committed = include_str!("../include/license_agent.h");
+        assert!(
+            generated == committed,
+            "include/license_agent.h is stale: run `cbindgen --config cbindgen.toml --output include/license_agent.h` in client-ffi/"
+        );
+    }
+}
@@ -0,0 +1,250 @@
+//! Module Python de License Secret Agent
+//!
+//! Expose le client bloquant de `license-agent-client` à Python (PyO3). Compilé
+//! par maturin (`pyproject.toml`), il s'importe sous le nom `license_agent` :
+//!
+//! ```python
+//! import license_agent
+//!
+//! client = license_agent.Client("/run/license-agent/license-agent.sock", timeout=2.0)
+//! if client.has_feature(token, "premium"):
+//!     ...
+//! ```
+//!
+//! Les erreurs de l'agent lèvent une sous-classe de `license_agent.AgentError`
+//! correspondant au code d'erreur renvoyé (attribut `code`).
+
+use chrono::{DateTime, Utc};
+use license_agent_client::{ClientConfig, ClientError, ErrorCode, LicenseClient, ValidationResult};
+use pyo3::create_exception;
+use pyo3::exceptions::PyException;
+use pyo3::prelude::*;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+create_exception!(license_agent, AgentError, PyException, "Erreur de License Secret Agent");
+create_exception!(license_agent, AgentUnavailableError, AgentError, "Agent injoignable ou connexion interrompue");
+create_exception!(license_agent, AgentTimeoutError, AgentError, "Délai de requête dépassé");
+create_exception!(license_agent, ProtocolError, AgentError, "Réponse invalide de l'agent");
+create_exception!(license_agent, NotAuthenticError, AgentError, "Réponse non authentifiée par la clé de l'application");
+create_exception!(license_agent, ConfigError, AgentError, "Configuration invalide");
+create_exception!(license_agent, InvalidRequestError, AgentError, "Requête refusée par l'agent");
+create_exception!(license_agent, UnauthorizedError, AgentError, "Authentification administrateur requise");
+create_exception!(license_agent, ForbiddenError, AgentError, "Rôle insuffisant pour la commande");
+create_exception!(license_agent, RateLimitedError, AgentError, "Limite de débit atteinte");
+create_exception!(license_agent, ReplayDetectedError, AgentError, "Nonce déjà utilisé");
+create_exception!(license_agent, SecretNotFoundError, AgentError, "Secret introuvable");
+create_exception!(license_agent, SecretExpiredError, AgentError, "Secret expiré");
+create_exception!(license_agent, SecretInvalidError, AgentError, "Secret invalide");
+create_exception!(license_agent, LicenseValidationError, AgentError, "Échec de validation de la licence");
+create_exception!(license_agent, TpmError, AgentError, "Erreur TPM");
+create_exception!(license_agent, NetworkError, AgentError, "Erreur réseau de l'agent");
+create_exception!(license_agent, IpcError, AgentError, "Erreur IPC de l'agent");
+create_exception!(license_agent, RotationError, AgentError, "Échec de rotation");
+create_exception!(license_agent, CryptoError, AgentError, "Erreur cryptographique");
+create_exception!(license_agent, SignatureVerificationError, AgentError, "Signature invalide");
+create_exception!(license_agent, InternalError, AgentError, "Erreur interne de l'agent");
+
+/// Exception correspondant à une erreur du client
+fn to_py_err(py: Python<'_>, error: ClientError) -> PyErr {
+    let (err, code) = match error {
+        ClientError::Agent { code, message } => (agent_error(code, message), Some(code)),
+        ClientError::Connect { .. } | ClientError::Io(_) => (AgentUnavailableError::new_err(error.to_string()), None),
+        ClientError::Timeout(_) => (AgentTimeoutError::new_err(error.to_string()), None),
+        ClientError::Protocol(_) => (ProtocolError::new_err(error.to_string()), None),
+        ClientError::NotAuthentic(_) => (NotAuthenticError::new_err(error.to_string()), None),
+        ClientError::Config(_) => (ConfigError::new_err(error.to_string()), None),
+    };
+
+    // Code de l'agent tel qu'il apparaît dans le protocole (ex: `RATE_LIMITED`)
+    let code = code.and_then(|code| serde_json::to_value(code).ok()).and_then(|value| value.as_str().map(str::to_string));
+    let _ = err.value(py).setattr("code", code);
+    err
+}
+
+fn agent_error(code: ErrorCode, message: String) -> PyErr {
+    match code {
+        ErrorCode::InvalidRequest | ErrorCode::UnsupportedVersion => InvalidRequestError::new_err(message),
+        ErrorCode::Unauthorized => UnauthorizedError::new_err(message),
+        ErrorCode::Forbidden => ForbiddenError::new_err(message),
+        ErrorCode::RateLimited => RateLimitedError::new_err(message),
+        ErrorCode::ReplayDetected => ReplayDetectedError::new_err(message),
+        ErrorCode::SecretNotFound => SecretNotFoundError::new_err(message),
+        ErrorCode::SecretExpired => SecretExpiredError::new_err(message),
+        ErrorCode::SecretInvalid => SecretInvalidError::new_err(message),
+        ErrorCode::LicenseValidationFailed => LicenseValidationError::new_err(message),
+        ErrorCode::TpmError => TpmError::new_err(message),
+        ErrorCode::NetworkError => NetworkError::new_err(message),
+        ErrorCode::ConfigError => ConfigError::new_err(message),
+        ErrorCode::IpcError => IpcError::new_err(message),
+        ErrorCode::RotationFailed => RotationError::new_err(message),
+        ErrorCode::CryptoError => CryptoError::new_err(message),
+        ErrorCode::SignatureVerificationFailed => SignatureVerificationError::new_err(message),
+        ErrorCode::InternalError => InternalError::new_err(message),
+    }
+}
+
+/// Résultat de validation d'une licence
+#[pyclass(name = "ValidationResult", module = "license_agent", frozen, get_all)]
+pub struct PyValidationResult {
+    pub valid: bool,
+    /// `datetime` UTC, `None` si la licence n'expire pas
+    pub expires_at: Option<DateTime<Utc>>,
+    pub features: Vec<String>,
+    pub metadata: HashMap<String, String>,
+    /// Motif du refus
+    pub error: Option<String>,
+}
+
+#[pymethods]
+impl PyValidationResult {
+    /// Licence valide et incluant la fonctionnalité `feature`
+    fn has_feature(&self, feature: &str) -> bool {
+        self.valid && self.features.iter().any(|f| f == feature)
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "ValidationResult(valid={}, expires_at={:?}, features={:?}, error={:?})",
+            self.valid,
+            self.expires_at.map(|at| at.to_rfc3339()),
+            self.features,
+            self.error
+        )
+    }
+}
+
+impl From<ValidationResult> for PyValidationResult {
+    fn from(result: ValidationResult) -> Self {
+        Self {
+            valid: result.valid,
+            expires_at: result.expires_at,
+            features: result.features,
+            metadata: result.metadata,
+            error: result.error,
+        }
+    }
+}
+
+/// Connexion à l'agent, conservée entre les requêtes
+///
+/// Les paramètres omis sont lus dans l'environnement (`LICENSE_AGENT_SOCKET`,
+/// `LICENSE_AGENT_APP_ID`, `LICENSE_AGENT_APP_KEY`).
+#[pyclass(name = "Client", module = "license_agent", frozen)]
+pub struct PyClient {
+    client: LicenseClient,
+}
+
+#[pymethods]
+impl PyClient {
+    #[new]
+    #[pyo3(signature = (socket_path = None, timeout = None, retries = None, app_id = None, app_key = None))]
+    fn new(
+        py: Python<'_>,
+        socket_path: Option<PathBuf>,
+        timeout: Option<f64>,
+        retries: Option<u32>,
+        app_id: Option<String>,
+        app_key: Option<Vec<u8>>,
+    ) -> PyResult<Self> {
+        let mut config = ClientConfig::from_env().map_err(|e| to_py_err(py, e))?;
+        if let Some(path) = socket_path {
+            config.socket_path = path;
+        }
+        if let Some(timeout) = timeout {
+            let timeout = Duration::try_from_secs_f64(timeout)
+                .map_err(|_| ConfigError::new_err(format!("Invalid timeout: {}", timeout)))?;
+            config = config.with_request_timeout(timeout).with_connect_timeout(timeout);
+        }
+        if let Some(retries) = retries {
+            let backoff = config.retry_backoff;
+            config = config.with_retries(retries, backoff);
+        }
+        match (app_id, app_key) {
+            (Some(app_id), Some(key)) => config = config.with_app_key(app_id, key),
+            (None, None) => {}
+            _ => return Err(ConfigError::new_err("app_id and app_key must be given together")),
+        }
+
+        Ok(Self {
+            client: LicenseClient::new(config),
+        })
+    }
+
+    /// Valide une licence ; une licence refusée retourne `valid == False`
+    fn validate(&self, py: Python<'_>, token: &[u8]) -> PyResult<PyValidationResult> {
+        py.allow_threads(|| self.client.validate(token))
+            .map(PyValidationResult::from)
+            .map_err(|e| to_py_err(py, e))
+    }
+
+    /// Licence valide et incluant la fonctionnalité `feature`
+    fn has_feature(&self, py: Python<'_>, token: &[u8], feature: &str) -> PyResult<bool> {
+        py.allow_threads(|| self.client.has_feature(token, feature))
+            .map_err(|e| to_py_err(py, e))
+    }
+
+    /// État de l'agent sous forme de `dict` (rôle opérateur requis)
+    fn status(&self, py: Python<'_>) -> PyResult<PyObject> {
+        let status = py.allow_threads(|| self.client.status()).map_err(|e| to_py_err(py, e))?;
+        let json = serde_json::to_string(&status).map_err(|e| ProtocolError::new_err(e.to_string()))?;
+        Ok(py.import("json")?.call_method1("loads", (json,))?.unbind())
+    }
+}
+
+/// Valide une licence avec un client configuré par l'environnement
+#[pyfunction]
+#[pyo3(signature = (token, socket_path = None))]
+fn validate(py: Python<'_>, token: &[u8], socket_path: Option<PathBuf>) -> PyResult<PyValidationResult> {
+    PyClient::new(py, socket_path, None, None, None, None)?.validate(py, token)
+}
+
+/// Licence valide et incluant la fonctionnalité `feature`
+#[pyfunction]
+#[pyo3(signature = (token, feature, socket_path = None))]
+fn has_feature(py: Python<'_>, token: &[u8], feature: &str, socket_path: Option<PathBuf>) -> PyResult<bool> {
+    PyClient::new(py, socket_path, None, None, None, None)?.has_feature(py, token, feature)
+}
+
+/// État de l'agent (rôle opérateur requis)
+#[pyfunction]
+#[pyo3(signature = (socket_path = None))]
+fn status(py: Python<'_>, socket_path: Option<PathBuf>) -> PyResult<PyObject> {
+    PyClient::new(py, socket_path, None, None, None, None)?.status(py)
+}
+
+#[pymodule]
+#[pyo3(name = "license_agent")]
+pub fn license_agent(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    let py = m.py();
+    m.add_class::<PyClient>()?;
+    m.add_class::<PyValidationResult>()?;
+    m.add_function(wrap_pyfunction!(validate, m)?)?;
+    m.add_function(wrap_pyfunction!(has_feature, m)?)?;
+    m.add_function(wrap_pyfunction!(status, m)?)?;
+
+    m.add("AgentError", py.get_type::<AgentError>())?;
+    m.add("AgentUnavailableError", py.get_type::<AgentUnavailableError>())?;
+    m.add("AgentTimeoutError", py.get_type::<AgentTimeoutError>())?;
+    m.add("ProtocolError", py.get_type::<ProtocolError>())?;
+    m.add("NotAuthenticError", py.get_type::<NotAuthenticError>())?;
+    m.add("ConfigError", py.get_type::<ConfigError>())?;
+    m.add("InvalidRequestError", py.get_type::<InvalidRequestError>())?;
+    m.add("UnauthorizedError", py.get_type::<UnauthorizedError>())?;
+    m.add("ForbiddenError", py.get_type::<ForbiddenError>())?;
+    m.add("RateLimitedError", py.get_type::<RateLimitedError>())?;
+    m.add("ReplayDetectedError", py.get_type::<ReplayDetectedError>())?;
+    m.add("SecretNotFoundError", py.get_type::<SecretNotFoundError>())?;
+    m.add("SecretExpiredError", py.get_type::<SecretExpiredError>())?;
+    m.add("SecretInvalidError", py.get_type::<SecretInvalidError>())?;
+    m.add("LicenseValidationError", py.get_type::<LicenseValidationError>())?;
+    m.add("TpmError", py.get_type::<TpmError>())?;
+    m.add("NetworkError", py.get_type::<NetworkError>())?;
+    m.add("IpcError", py.get_type::<IpcError>())?;
+    m.add("RotationError", py.get_type::<RotationError>())?;
+    m.add("CryptoError", py.get_type::<CryptoError>())?;
+    m.add("SignatureVerificationError", py.get_type::<SignatureVerificationError>())?;
+    m.add("InternalError", py.get_type::<InternalError>())?;
+    Ok(())
+}
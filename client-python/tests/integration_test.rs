@@ -0,0 +1,88 @@
+// Tests du module Python, importé dans un interpréteur embarqué, contre un agent en processus
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone, Utc};
+    use license_agent_py::license_agent;
+    use license_agent_testing::{bind_current_uid, TestAgent, APP_ID};
+    use license_secret_agent::roles::Role;
+    use pyo3::prelude::*;
+    use pyo3::types::{PyBytes, PyDict};
+    use std::ffi::CString;
+
+    const SCRIPT: &str = r#"
+import license_agent as la
+from datetime import datetime, timezone
+
+client = la.Client(socket, timeout=1.0, retries=0, app_id=app_id, app_key=app_key)
+
+result = client.validate(valid)
+assert result.valid
+assert result.expires_at == datetime(2030, 1, 1, tzinfo=timezone.utc)
+assert result.features == ["premium"]
+assert result.metadata == {"customer": "ACME"}
+assert result.has_feature("premium") and not result.has_feature("enterprise")
+
+result = client.validate(expired)
+assert not result.valid and "expired" in result.error and result.expires_at is None
+assert client.has_feature(valid, "premium")
+assert not client.has_feature(expired, "premium")
+
+def raises(exception, call):
+    try:
+        call()
+    except exception as e:
+        assert isinstance(e, la.AgentError)
+        return e
+    raise AssertionError(f"{exception.__name__} not raised")
+
+# Rôle `app` : l'état est réservé aux opérateurs
+e = raises(la.ForbiddenError, client.status)
+assert e.code == "FORBIDDEN"
+raises(la.ForbiddenError, lambda: la.status(socket_path=socket))
+
+limited_client = la.Client(limited, retries=0)
+assert limited_client.validate(valid).valid
+e = raises(la.RateLimitedError, lambda: limited_client.validate(valid))
+assert e.code == "RATE_LIMITED"
+
+wrong_key = la.Client(socket, retries=0, app_id=app_id, app_key=b"\x01" * 32)
+raises(la.NotAuthenticError, lambda: wrong_key.validate(valid))
+e = raises(la.AgentUnavailableError, lambda: la.Client(missing, retries=0).validate(valid))
+assert e.code is None
+raises(la.ConfigError, lambda: la.Client(socket, app_id=app_id))
+
+assert la.validate(valid, socket_path=socket).valid
+assert la.has_feature(valid, "premium", socket_path=socket)
+"#;
+
+    #[test]
+    fn test_python_module_against_agent() {
+        let agent = TestAgent::start_blocking(|management| bind_current_uid(management, Role::App));
+        let limited = TestAgent::start_blocking(|management| {
+            bind_current_uid(management, Role::App);
+            management.rate_limit_requests_per_minute = Some(1);
+        });
+        let valid = agent.license(&["premium"], Utc.with_ymd_and_hms(2030, 1, 1, 0, 0, 0).unwrap());
+        let expired = agent.license(&["premium"], Utc::now() - Duration::days(1));
+
+        // Seul test du binaire : l'interpréteur est initialisé ici
+        pyo3::append_to_inittab!(license_agent);
+        Python::with_gil(|py| {
+            let globals = PyDict::new(py);
+            globals.set_item("socket", agent.socket().to_str().unwrap()).unwrap();
+            globals.set_item("limited", limited.socket().to_str().unwrap()).unwrap();
+            globals.set_item("missing", agent.dir().join("missing.sock").to_str().unwrap()).unwrap();
+            globals.set_item("app_id", APP_ID).unwrap();
+            globals.set_item("app_key", PyBytes::new(py, agent.app_key())).unwrap();
+            globals.set_item("valid", PyBytes::new(py, &valid)).unwrap();
+            globals.set_item("expired", PyBytes::new(py, &expired)).unwrap();
+
+            let script = CString::new(SCRIPT).unwrap();
+            if let Err(e) = py.run(&script, Some(&globals), None) {
+                e.display(py);
+                panic!("Python script failed: {}", e);
+            }
+        });
+    }
+}
@@ -0,0 +1,112 @@
+use crate::{
+    encode_request, frame_error, parse_status, parse_validate, prepare_validate, ClientConfig, ClientError, Result,
+    SystemStatus, ValidationResult,
+};
+use license_secret_agent::protocol::{read_frame, write_frame, IpcRequest};
+use std::future::Future;
+use tokio::net::UnixStream;
+use tokio::sync::Mutex;
+
+/// Client asynchrone (tokio)
+///
+/// Les requêtes d'un même client partagent une connexion et sont envoyées
+/// l'une après l'autre ; créer plusieurs clients pour paralléliser.
+pub struct AsyncLicenseClient {
+    config: ClientConfig,
+    connection: Mutex<Option<UnixStream>>,
+}
+
+impl AsyncLicenseClient {
+    /// La connexion est ouverte à la première requête
+    pub fn new(config: ClientConfig) -> Self {
+        Self {
+            config,
+            connection: Mutex::new(None),
+        }
+    }
+
+    pub fn config(&self) -> &ClientConfig {
+        &self.config
+    }
+
+    /// Valide une licence
+    ///
+    /// Une licence refusée n'est pas une erreur : le résultat porte `valid: false`.
+    pub async fn validate(&self, license_token: &[u8]) -> Result<ValidationResult> {
+        // Nouveau nonce à chaque tentative : l'agent rejette les rejeux
+        self.with_retries(|| async {
+            let prepared = prepare_validate(&self.config, license_token)?;
+            let frame = self.exchange(&prepared.frame).await?;
+            parse_validate(&self.config, &prepared, license_token, &frame)
+        })
+        .await
+    }
+
+    /// Licence valide et incluant la fonctionnalité `feature`
+    pub async fn has_feature(&self, license_token: &[u8], feature: &str) -> Result<bool> {
+        Ok(self.validate(license_token).await?.has_feature(feature))
+    }
+
+    /// État de l'agent (rôle opérateur requis)
+    pub async fn status(&self) -> Result<SystemStatus> {
+        let request = encode_request(IpcRequest::Status {})?;
+        self.with_retries(|| async { parse_status(&self.exchange(&request).await?) })
+            .await
+    }
+
+    async fn with_retries<T, F, Fut>(&self, mut request: F) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let mut attempt = 0;
+        loop {
+            match request().await {
+                Err(e) if e.is_retryable() && attempt < self.config.max_retries => {
+                    attempt += 1;
+                    tokio::time::sleep(self.config.backoff(attempt)).await;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Envoie une trame et lit la réponse sur la connexion partagée
+    async fn exchange(&self, frame: &[u8]) -> Result<Vec<u8>> {
+        let mut connection = self.connection.lock().await;
+
+        let exchange = async {
+            if connection.is_none() {
+                *connection = Some(self.connect().await?);
+            }
+            let stream = connection.as_mut().expect("connection just opened");
+            write_frame(stream, frame).await.map_err(frame_error)?;
+            read_frame(stream).await.map_err(frame_error)
+        };
+
+        match tokio::time::timeout(self.config.request_timeout, exchange).await {
+            Ok(Ok(frame)) => Ok(frame),
+            Ok(Err(e)) => {
+                // Connexion fermée par l'agent (inactivité) ou dans un état inconnu
+                *connection = None;
+                Err(e)
+            }
+            Err(_) => {
+                *connection = None;
+                Err(ClientError::Timeout(self.config.request_timeout))
+            }
+        }
+    }
+
+    async fn connect(&self) -> Result<UnixStream> {
+        let path = &self.config.socket_path;
+        match tokio::time::timeout(self.config.connect_timeout, UnixStream::connect(path)).await {
+            Ok(Ok(stream)) => Ok(stream),
+            Ok(Err(source)) => Err(ClientError::Connect {
+                path: path.clone(),
+                source,
+            }),
+            Err(_) => Err(ClientError::Timeout(self.config.connect_timeout)),
+        }
+    }
+}
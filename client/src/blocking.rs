@@ -0,0 +1,142 @@
+use crate::{
+    encode_request, parse_status, parse_validate, prepare_validate, ClientConfig, ClientError, Result, SystemStatus,
+    ValidationResult,
+};
+use license_secret_agent::protocol::IpcRequest;
+use license_secret_agent::protocol::MAX_FRAME_SIZE;
+use std::io::{Read, Write};
+use std::os::unix::net::UnixStream;
+use std::sync::Mutex;
+
+/// Client bloquant, sans runtime asynchrone
+///
+/// Les délais s'appliquent à chaque lecture et écriture sur le socket.
+pub struct LicenseClient {
+    config: ClientConfig,
+    connection: Mutex<Option<UnixStream>>,
+}
+
+impl LicenseClient {
+    /// La connexion est ouverte à la première requête
+    pub fn new(config: ClientConfig) -> Self {
+        Self {
+            config,
+            connection: Mutex::new(None),
+        }
+    }
+
+    pub fn config(&self) -> &ClientConfig {
+        &self.config
+    }
+
+    /// Ouvre la connexion à l'agent sans attendre la première requête
+    pub fn connect(&self) -> Result<()> {
+        let mut connection = self.connection.lock().unwrap_or_else(|e| e.into_inner());
+        if connection.is_none() {
+            *connection = Some(self.open()?);
+        }
+        Ok(())
+    }
+
+    /// Valide une licence
+    ///
+    /// Une licence refusée n'est pas une erreur : le résultat porte `valid: false`.
+    pub fn validate(&self, license_token: &[u8]) -> Result<ValidationResult> {
+        // Nouveau nonce à chaque tentative : l'agent rejette les rejeux
+        self.with_retries(|| {
+            let prepared = prepare_validate(&self.config, license_token)?;
+            let frame = self.exchange(&prepared.frame)?;
+            parse_validate(&self.config, &prepared, license_token, &frame)
+        })
+    }
+
+    /// Licence valide et incluant la fonctionnalité `feature`
+    pub fn has_feature(&self, license_token: &[u8], feature: &str) -> Result<bool> {
+        Ok(self.validate(license_token)?.has_feature(feature))
+    }
+
+    /// État de l'agent (rôle opérateur requis)
+    pub fn status(&self) -> Result<SystemStatus> {
+        let request = encode_request(IpcRequest::Status {})?;
+        self.with_retries(|| parse_status(&self.exchange(&request)?))
+    }
+
+    fn with_retries<T>(&self, mut request: impl FnMut() -> Result<T>) -> Result<T> {
+        let mut attempt = 0;
+        loop {
+            match request() {
+                Err(e) if e.is_retryable() && attempt < self.config.max_retries => {
+                    attempt += 1;
+                    std::thread::sleep(self.config.backoff(attempt));
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Envoie une trame et lit la réponse sur la connexion partagée
+    fn exchange(&self, frame: &[u8]) -> Result<Vec<u8>> {
+        let mut connection = self.connection.lock().unwrap_or_else(|e| e.into_inner());
+
+        let exchange = |connection: &mut Option<UnixStream>| -> Result<Vec<u8>> {
+            if connection.is_none() {
+                *connection = Some(self.open()?);
+            }
+            let stream = connection.as_mut().expect("connection just opened");
+            write_frame(stream, frame)?;
+            read_frame(stream)
+        };
+
+        exchange(&mut connection).map_err(|e| {
+            // Connexion fermée par l'agent (inactivité) ou dans un état inconnu
+            *connection = None;
+            self.timeout_error(e)
+        })
+    }
+
+    fn open(&self) -> Result<UnixStream> {
+        let path = &self.config.socket_path;
+        let stream = UnixStream::connect(path).map_err(|source| ClientError::Connect {
+            path: path.clone(),
+            source,
+        })?;
+        stream.set_read_timeout(Some(self.config.request_timeout))?;
+        stream.set_write_timeout(Some(self.config.request_timeout))?;
+        Ok(stream)
+    }
+
+    /// Un délai de socket expiré se traduit par `WouldBlock` ou `TimedOut`
+    fn timeout_error(&self, error: ClientError) -> ClientError {
+        match error {
+            ClientError::Io(e)
+                if matches!(e.kind(), std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut) =>
+            {
+                ClientError::Timeout(self.config.request_timeout)
+            }
+            other => other,
+        }
+    }
+}
+
+fn write_frame(stream: &mut UnixStream, data: &[u8]) -> Result<()> {
+    if data.len() > MAX_FRAME_SIZE {
+        return Err(ClientError::Protocol(format!("Frame too large: {} bytes", data.len())));
+    }
+    stream.write_all(&(data.len() as u32).to_be_bytes())?;
+    stream.write_all(data)?;
+    stream.flush()?;
+    Ok(())
+}
+
+fn read_frame(stream: &mut UnixStream) -> Result<Vec<u8>> {
+    let mut len_bytes = [0u8; 4];
+    stream.read_exact(&mut len_bytes)?;
+    let len = u32::from_be_bytes(len_bytes) as usize;
+    if len > MAX_FRAME_SIZE {
+        return Err(ClientError::Protocol(format!("Frame too large: {} bytes", len)));
+    }
+
+    let mut data = vec![0u8; len];
+    stream.read_exact(&mut data)?;
+    Ok(data)
+}
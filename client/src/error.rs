@@ -0,0 +1,49 @@
+use license_secret_agent::protocol::ErrorCode;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Erreurs du client
+#[derive(Debug, thiserror::Error)]
+pub enum ClientError {
+    #[error("Failed to connect to agent at {path}: {source}")]
+    Connect {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Request timed out after {0:?}")]
+    Timeout(Duration),
+
+    #[error("Protocol error: {0}")]
+    Protocol(String),
+
+    /// Erreur renvoyée par l'agent
+    #[error("Agent error ({code:?}): {message}")]
+    Agent { code: ErrorCode, message: String },
+
+    /// Réponse non authentifiée par la clé de l'application (faux agent ou réponse rejouée)
+    #[error("Response authentication failed: {0}")]
+    NotAuthentic(String),
+
+    #[error("Invalid client configuration: {0}")]
+    Config(String),
+}
+
+impl ClientError {
+    /// Erreur transitoire : la requête peut être renvoyée sur une nouvelle connexion
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            ClientError::Connect { .. }
+                | ClientError::Io(_)
+                | ClientError::Timeout(_)
+                | ClientError::Agent { code: ErrorCode::RateLimited, .. }
+        )
+    }
+}
+
+pub type Result<T> = std::result::Result<T, ClientError>;
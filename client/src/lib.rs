@@ -0,0 +1,205 @@
+//! Client des applications pour License Secret Agent
+//!
+//! Valide des licences auprès de l'agent local (socket Unix), avec une API
+//! asynchrone ([`AsyncLicenseClient`], tokio) et une API bloquante
+//! ([`LicenseClient`]). La connexion est conservée entre les requêtes et
+//! rouverte si l'agent l'a fermée ; les erreurs transitoires sont réessayées.
+//!
+//! ```no_run
+//! use license_agent_client::{ClientConfig, LicenseClient};
+//!
+//! # fn main() -> license_agent_client::Result<()> {
+//! let client = LicenseClient::new(ClientConfig::from_env()?);
+//! let token = std::fs::read("license.bin")?;
+//! if client.has_feature(&token, "premium")? {
+//!     // ...
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+mod async_client;
+mod blocking;
+mod error;
+
+pub use async_client::AsyncLicenseClient;
+pub use blocking::LicenseClient;
+pub use error::{ClientError, Result};
+pub use license_secret_agent::protocol::ErrorCode;
+pub use license_secret_agent::types::{SystemStatus, ValidationResult};
+
+use base64::{engine::general_purpose, Engine as _};
+use license_secret_agent::app_auth::verify_validation_response;
+use license_secret_agent::protocol::{IpcRequest, IpcResponse, RequestEnvelope, ResponseEnvelope, PROTOCOL_VERSION};
+use license_secret_agent::types::ValidateLicenseRequest;
+use rand::RngCore;
+use std::path::PathBuf;
+use std::time::Duration;
+use zeroize::Zeroizing;
+
+/// Socket de l'agent par défaut (`RuntimeDirectory=license-agent` de l'unité systemd)
+pub const DEFAULT_SOCKET_PATH: &str = license_secret_agent::config::DEFAULT_SOCKET_PATH;
+
+/// Paramètres du client
+#[derive(Clone)]
+pub struct ClientConfig {
+    pub socket_path: PathBuf,
+    /// Délai de connexion au socket (client asynchrone)
+    pub connect_timeout: Duration,
+    /// Délai maximal d'une requête (envoi et réponse)
+    pub request_timeout: Duration,
+    /// Nouvelles tentatives après une erreur transitoire
+    pub max_retries: u32,
+    /// Attente avant la première nouvelle tentative, doublée à chaque essai
+    pub retry_backoff: Duration,
+    app_key: Option<AppKey>,
+}
+
+/// Identité de l'application enregistrée (`license-agent-cli register-app`)
+#[derive(Clone)]
+struct AppKey {
+    app_id: String,
+    key: Zeroizing<Vec<u8>>,
+}
+
+impl ClientConfig {
+    pub fn new(socket_path: impl Into<PathBuf>) -> Self {
+        Self {
+            socket_path: socket_path.into(),
+            connect_timeout: Duration::from_secs(2),
+            request_timeout: Duration::from_secs(5),
+            max_retries: 2,
+            retry_backoff: Duration::from_millis(100),
+            app_key: None,
+        }
+    }
+
+    /// Configuration depuis l'environnement : `LICENSE_AGENT_SOCKET`, et
+    /// `LICENSE_AGENT_APP_ID` / `LICENSE_AGENT_APP_KEY` (Base64) pour vérifier les réponses
+    pub fn from_env() -> Result<Self> {
+        let socket_path = std::env::var("LICENSE_AGENT_SOCKET").unwrap_or_else(|_| DEFAULT_SOCKET_PATH.to_string());
+        let mut config = Self::new(socket_path);
+
+        if let (Ok(app_id), Ok(key)) = (std::env::var("LICENSE_AGENT_APP_ID"), std::env::var("LICENSE_AGENT_APP_KEY")) {
+            let key = general_purpose::STANDARD
+                .decode(key.trim())
+                .map_err(|_| ClientError::Config("LICENSE_AGENT_APP_KEY must be Base64".to_string()))?;
+            config = config.with_app_key(app_id, key);
+        }
+
+        Ok(config)
+    }
+
+    pub fn with_request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = timeout;
+        self
+    }
+
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    pub fn with_retries(mut self, max_retries: u32, backoff: Duration) -> Self {
+        self.max_retries = max_retries;
+        self.retry_backoff = backoff;
+        self
+    }
+
+    /// Authentifie les réponses avec la clé de l'application
+    pub fn with_app_key(mut self, app_id: impl Into<String>, key: Vec<u8>) -> Self {
+        self.app_key = Some(AppKey {
+            app_id: app_id.into(),
+            key: Zeroizing::new(key),
+        });
+        self
+    }
+
+    /// Attente avant la tentative `attempt` (à partir de 1)
+    fn backoff(&self, attempt: u32) -> Duration {
+        self.retry_backoff.saturating_mul(1 << (attempt - 1).min(16))
+    }
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        Self::new(DEFAULT_SOCKET_PATH)
+    }
+}
+
+/// Requête de validation encodée, avec son nonce pour vérifier la réponse
+struct PreparedRequest {
+    nonce: [u8; 16],
+    frame: Vec<u8>,
+}
+
+/// Encode une requête `validate` avec un nouveau nonce
+fn prepare_validate(config: &ClientConfig, license_token: &[u8]) -> Result<PreparedRequest> {
+    let mut nonce = [0u8; 16];
+    rand::thread_rng().fill_bytes(&mut nonce);
+
+    let frame = encode_request(IpcRequest::Validate(ValidateLicenseRequest {
+        license_token: license_token.to_vec(),
+        nonce,
+        app_id: config.app_key.as_ref().map(|app| app.app_id.clone()),
+    }))?;
+
+    Ok(PreparedRequest { nonce, frame })
+}
+
+/// Encode une requête sans authentification administrateur
+fn encode_request(request: IpcRequest) -> Result<Vec<u8>> {
+    let envelope = RequestEnvelope {
+        version: PROTOCOL_VERSION,
+        id: None,
+        request,
+        auth: None,
+    };
+    serde_json::to_vec(&envelope).map_err(|e| ClientError::Protocol(e.to_string()))
+}
+
+/// Décode une réponse ; une erreur renvoyée par l'agent devient [`ClientError::Agent`]
+fn decode_response(frame: &[u8]) -> Result<IpcResponse> {
+    let envelope: ResponseEnvelope =
+        serde_json::from_slice(frame).map_err(|e| ClientError::Protocol(format!("Invalid response: {}", e)))?;
+    envelope.into_result().map_err(|error| ClientError::Agent {
+        code: error.code,
+        message: error.message,
+    })
+}
+
+/// Décode la réponse et vérifie son MAC si une clé d'application est configurée
+fn parse_validate(
+    config: &ClientConfig,
+    prepared: &PreparedRequest,
+    license_token: &[u8],
+    frame: &[u8],
+) -> Result<ValidationResult> {
+    let response = match decode_response(frame)? {
+        IpcResponse::Validate(response) => response,
+        other => return Err(ClientError::Protocol(format!("Unexpected response: {:?}", other))),
+    };
+
+    if let Some(app) = &config.app_key {
+        verify_validation_response(&app.key, &prepared.nonce, license_token, &response)
+            .map_err(|e| ClientError::NotAuthentic(e.to_string()))?;
+    }
+
+    Ok(response.result)
+}
+
+/// Décode la réponse à une requête `status`
+fn parse_status(frame: &[u8]) -> Result<SystemStatus> {
+    match decode_response(frame)? {
+        IpcResponse::Status(status) => Ok(*status),
+        other => Err(ClientError::Protocol(format!("Unexpected response: {:?}", other))),
+    }
+}
+
+/// Convertit une erreur de trame du protocole
+fn frame_error(error: anyhow::Error) -> ClientError {
+    match error.downcast::<std::io::Error>() {
+        Ok(e) => ClientError::Io(e),
+        Err(e) => ClientError::Protocol(e.to_string()),
+    }
+}
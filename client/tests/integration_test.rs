@@ -0,0 +1,135 @@
+// Tests du client contre un agent en processus
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration as ChronoDuration, Utc};
+    use license_agent_client::{AsyncLicenseClient, ClientConfig, ClientError, ErrorCode, LicenseClient};
+    use license_agent_testing::{bind_current_uid, TestAgent, APP_ID};
+    use license_secret_agent::roles::Role;
+    use std::time::Duration;
+
+    fn config(agent: &TestAgent) -> ClientConfig {
+        ClientConfig::new(agent.socket())
+            .with_request_timeout(Duration::from_millis(500))
+            .with_retries(2, Duration::from_millis(10))
+    }
+
+    /// Licences valide (fonctionnalité `premium`) et expirée
+    fn licenses(agent: &TestAgent) -> (Vec<u8>, Vec<u8>) {
+        (
+            agent.license(&["premium"], Utc::now() + ChronoDuration::days(30)),
+            agent.license(&["premium"], Utc::now() - ChronoDuration::days(1)),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_async_client_against_agent() {
+        // Connexion fermée par l'agent toutes les deux requêtes
+        let agent = TestAgent::start(|management| {
+            bind_current_uid(management, Role::App);
+            management.ipc_max_requests_per_connection = Some(2);
+        })
+        .await;
+        let (valid, expired) = licenses(&agent);
+        let client = AsyncLicenseClient::new(config(&agent).with_app_key(APP_ID, agent.app_key().to_vec()));
+
+        // Réponses authentifiées ; au-delà de la limite, nouvelle tentative sur une nouvelle connexion
+        let result = client.validate(&valid).await.unwrap();
+        assert!(result.valid);
+        assert_eq!(result.features, ["premium"]);
+        assert!(client.has_feature(&valid, "premium").await.unwrap());
+        assert!(!client.has_feature(&valid, "enterprise").await.unwrap());
+        let result = client.validate(&expired).await.unwrap();
+        assert!(!result.valid);
+        assert!(result.error.unwrap().contains("expired"));
+        assert!(!client.has_feature(&expired, "premium").await.unwrap());
+
+        // Commande réservée aux opérateurs : erreur de l'agent, sans nouvelle tentative
+        let error = client.status().await.unwrap_err();
+        assert!(matches!(error, ClientError::Agent { code: ErrorCode::Forbidden, .. }));
+        assert!(!error.is_retryable());
+
+        // Application inconnue de l'agent
+        let unknown = AsyncLicenseClient::new(config(&agent).with_app_key("unknown", vec![1u8; 32]));
+        assert!(matches!(
+            unknown.validate(&valid).await,
+            Err(ClientError::Agent { code: ErrorCode::ConfigError, .. })
+        ));
+
+        // Réponse signée avec la clé de l'agent, pas celle du client
+        let wrong_key = AsyncLicenseClient::new(config(&agent).with_app_key(APP_ID, vec![1u8; 32]));
+        assert!(matches!(wrong_key.validate(&valid).await, Err(ClientError::NotAuthentic(_))));
+
+        // Agent absent
+        let missing = AsyncLicenseClient::new(
+            ClientConfig::new(agent.dir().join("missing.sock")).with_retries(1, Duration::ZERO),
+        );
+        let error = missing.validate(&valid).await.unwrap_err();
+        assert!(matches!(error, ClientError::Connect { .. }));
+        assert!(error.is_retryable());
+
+        // Agent bloqué : connexion acceptée, aucune réponse
+        let stalled_socket = agent.dir().join("stalled.sock");
+        let listener = tokio::net::UnixListener::bind(&stalled_socket).unwrap();
+        let stalled = tokio::spawn(async move {
+            let mut connections = Vec::new();
+            while let Ok((stream, _)) = listener.accept().await {
+                connections.push(stream);
+            }
+        });
+        let no_retry = AsyncLicenseClient::new(
+            ClientConfig::new(&stalled_socket)
+                .with_request_timeout(Duration::from_millis(200))
+                .with_retries(0, Duration::ZERO),
+        );
+        assert!(matches!(no_retry.validate(&valid).await, Err(ClientError::Timeout(_))));
+        stalled.abort();
+    }
+
+    #[tokio::test]
+    async fn test_client_rate_limited_by_agent() {
+        let agent = TestAgent::start(|management| {
+            bind_current_uid(management, Role::App);
+            management.rate_limit_requests_per_minute = Some(1);
+        })
+        .await;
+        let (valid, _) = licenses(&agent);
+        let client = AsyncLicenseClient::new(config(&agent).with_retries(0, Duration::ZERO));
+
+        assert!(client.validate(&valid).await.unwrap().valid);
+        let error = client.validate(&valid).await.unwrap_err();
+        assert!(matches!(error, ClientError::Agent { code: ErrorCode::RateLimited, .. }));
+        assert!(error.is_retryable());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_blocking_client_against_agent() {
+        let agent = TestAgent::start(|management| {
+            bind_current_uid(management, Role::App);
+            management.ipc_max_requests_per_connection = Some(2);
+        })
+        .await;
+        let (valid, expired) = licenses(&agent);
+        let config = config(&agent).with_app_key(APP_ID, agent.app_key().to_vec());
+
+        tokio::task::spawn_blocking(move || {
+            let client = LicenseClient::new(config);
+            client.connect().unwrap();
+            assert!(client.validate(&valid).unwrap().valid);
+            assert!(client.has_feature(&valid, "premium").unwrap());
+
+            let result = client.validate(&expired).unwrap();
+            assert!(!result.valid);
+            assert!(result.error.unwrap().contains("expired"));
+
+            // Connexion fermée par l'agent : reprise transparente
+            assert!(client.has_feature(&valid, "premium").unwrap());
+            assert!(matches!(
+                client.status(),
+                Err(ClientError::Agent { code: ErrorCode::Forbidden, .. })
+            ));
+        })
+        .await
+        .unwrap();
+    }
+}
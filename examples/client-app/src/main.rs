@@ -1,120 +1,16 @@
 use anyhow::{Context, Result};
-use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::UnixStream;
-
-/// Application cliente exemple qui utilise l'agent pour valider des licences
-#[derive(Debug)]
-struct LicenseClient {
-    socket_path: PathBuf,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-struct ValidateRequest {
-    license_token: Vec<u8>,
-    nonce: [u8; 16],
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-struct ValidateResponse {
-    result: ValidationResult,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-struct ValidationResult {
-    valid: bool,
-    expires_at: Option<String>,
-    features: Vec<String>,
-    metadata: std::collections::HashMap<String, String>,
-    error: Option<String>,
-}
-
-impl LicenseClient {
-    fn new(socket_path: impl Into<PathBuf>) -> Self {
-        Self {
-            socket_path: socket_path.into(),
-        }
-    }
-
-    /// Valide une licence via l'agent
-    async fn validate_license(&self, license_token: Vec<u8>) -> Result<ValidationResult> {
-        // Générer un nonce
-        let nonce = self.generate_nonce();
-
-        // Créer la requête
-        let request = ValidateRequest {
-            license_token,
-            nonce,
-        };
-
-        // Envoyer à l'agent via IPC
-        let response: ValidateResponse = self.send_ipc_request("validate", &request).await?;
-
-        Ok(response.result)
-    }
-
-    /// Envoie une requête IPC à l'agent
-    async fn send_ipc_request<T: Serialize, R: for<'de> Deserialize<'de>>(
-        &self,
-        command: &str,
-        data: &T,
-    ) -> Result<R> {
-        let mut stream = UnixStream::connect(&self.socket_path)
-            .await
-            .context("Failed to connect to agent socket")?;
-
-        let request = serde_json::json!({
-            "command": command,
-            "data": data
-        });
-
-        let request_bytes = serde_json::to_vec(&request)?;
-
-        // Envoyer la longueur puis les données
-        stream.write_all(&(request_bytes.len() as u32).to_be_bytes()).await?;
-        stream.write_all(&request_bytes).await?;
-
-        // Lire la réponse
-        let mut len_bytes = [0u8; 4];
-        stream.read_exact(&mut len_bytes).await?;
-        let len = u32::from_be_bytes(len_bytes) as usize;
-
-        let mut response_bytes = vec![0u8; len];
-        stream.read_exact(&mut response_bytes).await?;
-
-        let response: serde_json::Value = serde_json::from_slice(&response_bytes)?;
-
-        if let Some(error) = response.get("error") {
-            anyhow::bail!("Agent error: {}", error);
-        }
-
-        let result: R = serde_json::from_value(
-            response.get("data").cloned()
-                .ok_or_else(|| anyhow::anyhow!("Missing data in response"))?
-        )?;
-
-        Ok(result)
-    }
-
-    fn generate_nonce(&self) -> [u8; 16] {
-        use rand::Rng;
-        let mut nonce = [0u8; 16];
-        rand::thread_rng().fill(&mut nonce);
-        nonce
-    }
-}
+use license_agent_client::{AsyncLicenseClient, ClientConfig};
 
 /// Application exemple
 struct ExampleApp {
-    license_client: LicenseClient,
+    license_client: AsyncLicenseClient,
     license_token: Option<Vec<u8>>,
 }
 
 impl ExampleApp {
-    fn new(socket_path: impl Into<PathBuf>) -> Self {
+    fn new(license_client: AsyncLicenseClient) -> Self {
         Self {
-            license_client: LicenseClient::new(socket_path),
+            license_client,
             license_token: None,
         }
     }
@@ -140,7 +36,7 @@ impl ExampleApp {
             .ok_or_else(|| anyhow::anyhow!("Aucune licence chargée"))?;
 
         println!("Validation de la licence...");
-        let result = self.license_client.validate_license(license_token.clone()).await?;
+        let result = self.license_client.validate(license_token).await?;
 
         if result.valid {
             println!("✓ Licence VALIDE");
@@ -173,13 +69,13 @@ impl ExampleApp {
             .ok_or_else(|| anyhow::anyhow!("Aucune licence chargée"))?;
 
         // Valider la licence avant d'utiliser la fonctionnalité
-        let result = self.license_client.validate_license(license_token.clone()).await?;
+        let result = self.license_client.validate(license_token).await?;
 
         if !result.valid {
             anyhow::bail!("Licence invalide, fonctionnalité '{}' non disponible", feature);
         }
 
-        if !result.features.contains(&feature.to_string()) {
+        if !result.has_feature(feature) {
             anyhow::bail!("Fonctionnalité '{}' non incluse dans la licence", feature);
         }
 
@@ -210,10 +106,9 @@ async fn main() -> Result<()> {
         return Ok(());
     }
 
-    let socket_path = std::env::var("LICENSE_AGENT_SOCKET")
-        .unwrap_or_else(|_| "/var/run/license-agent.sock".to_string());
-    
-    let mut app = ExampleApp::new(&socket_path);
+    // Socket et clé d'application : LICENSE_AGENT_SOCKET, LICENSE_AGENT_APP_ID, LICENSE_AGENT_APP_KEY
+    let license_client = AsyncLicenseClient::new(ClientConfig::from_env()?);
+    let mut app = ExampleApp::new(license_client);
 
     match args[1].as_str() {
         "load" => {
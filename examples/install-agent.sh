@@ -39,6 +39,12 @@ if [ ! -f /etc/license-agent/config.toml ] && [ -f "$PROJECT_ROOT/deploy/config.
     chown root:license-agent /etc/license-agent/config.toml
 fi
 
+if [ ! -f /etc/license-agent/kek.seed ]; then
+    head -c 32 /dev/urandom > /etc/license-agent/kek.seed
+    chmod 600 /etc/license-agent/kek.seed
+    chown license-agent:license-agent /etc/license-agent/kek.seed
+fi
+
 if [ -f "$PROJECT_ROOT/deploy/license-agent.service" ]; then
     cp "$PROJECT_ROOT/deploy/license-agent.service" /etc/systemd/system/license-agent.service
     systemctl daemon-reload
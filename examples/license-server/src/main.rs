@@ -8,6 +8,8 @@ use axum::{
 use chrono::{DateTime, Utc};
 use clap::Parser;
 use license_secret_agent::crypto::{self, CryptoManager};
+use license_secret_agent::license::encrypt_license_v2;
+use license_secret_agent::rotation::RotateSecretResponse;
 use rsa::{Oaep, RsaPrivateKey, RsaPublicKey};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -75,16 +77,6 @@ struct RotateSecretRequest {
     agent_public_key: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct RotateSecretResponse {
-    new_secret_encrypted: String,
-    version: u64,
-    valid_from: DateTime<Utc>,
-    valid_until: DateTime<Utc>,
-    grace_until: DateTime<Utc>,
-    signature: String,
-}
-
 struct LicenseServer {
     crypto: Arc<CryptoManager>,
     current_secret: Vec<u8>,
@@ -121,7 +113,6 @@ impl LicenseServer {
         features: Vec<String>,
         metadata: Option<HashMap<String, String>>,
     ) -> Result<LicenseResponse> {
-        use aes_gcm::{Aes256Gcm, Key, KeyInit, Nonce};
         use base64::{engine::general_purpose, Engine as _};
 
         let license_id = license_id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
@@ -137,21 +128,8 @@ impl LicenseServer {
 
         let license_json = serde_json::to_vec(&license_data)?;
 
-        let key = Key::<Aes256Gcm>::from_slice(&self.current_secret);
-        let cipher = Aes256Gcm::new(key);
-
-        let nonce_bytes = crypto::generate_nonce(12);
-        let nonce = Nonce::from_slice(&nonce_bytes);
-
-        use aead::Aead;
-        let ciphertext = cipher
-            .encrypt(nonce, license_json.as_ref())
-            .map_err(|e| anyhow::anyhow!("Encryption failed: {}", e))?;
-
-        let mut token = Vec::new();
-        token.extend_from_slice(&self.secret_version.to_be_bytes());
-        token.extend_from_slice(&nonce_bytes);
-        token.extend_from_slice(&ciphertext);
+        // Token v2 : en-tête (magic, format, version, key ID) authentifié en AAD
+        let token = encrypt_license_v2(&self.current_secret, self.secret_version, &license_json)?;
 
         let license_token = general_purpose::STANDARD.encode(&token);
         let expires_at = Utc::now() + chrono::Duration::days(self.license_duration_days as i64);
@@ -182,7 +160,7 @@ impl LicenseServer {
         Ok(general_purpose::STANDARD.encode(&encrypted))
     }
 
-    fn handle_rotation(&mut self, agent_public_key_pem: &str) -> Result<RotateSecretResponse> {
+    fn handle_rotation(&mut self, request: &RotateSecretRequest, agent_public_key_pem: &str) -> Result<RotateSecretResponse> {
         let new_secret = self.generate_new_secret();
         let encrypted_secret = self.encrypt_secret_for_agent(&new_secret, agent_public_key_pem)?;
 
@@ -190,27 +168,23 @@ impl LicenseServer {
         let valid_until = valid_from + chrono::Duration::days(self.secret_validity_days as i64);
         let grace_until = valid_until + chrono::Duration::days(self.grace_period_days as i64);
 
-        let response_data = serde_json::json!({
-            "new_secret_encrypted": encrypted_secret,
-            "version": self.secret_version,
-            "valid_from": valid_from.to_rfc3339(),
-            "valid_until": valid_until.to_rfc3339(),
-            "grace_until": grace_until.to_rfc3339(),
-        });
-
-        let response_json = serde_json::to_vec(&response_data)?;
-        let signature = self.crypto.sign_pss(&response_json)?;
-        use base64::{engine::general_purpose, Engine as _};
-        let signature_b64 = general_purpose::STANDARD.encode(&signature);
-
-        Ok(RotateSecretResponse {
+        let mut response = RotateSecretResponse {
+            agent_id: request.agent_id.clone(),
+            nonce: request.nonce.clone(),
             new_secret_encrypted: encrypted_secret,
             version: self.secret_version,
             valid_from,
             valid_until,
             grace_until,
-            signature: signature_b64,
-        })
+            signature: String::new(),
+        };
+
+        // Signer la forme canonique partagée avec l'agent
+        let signature = self.crypto.sign_pss(&response.canonical_bytes())?;
+        use base64::{engine::general_purpose, Engine as _};
+        response.signature = general_purpose::STANDARD.encode(&signature);
+
+        Ok(response)
     }
 }
 
@@ -290,12 +264,13 @@ async fn rotate_secret(
 ) -> Result<Json<RotateSecretResponse>, (StatusCode, String)> {
     let agent_public_key = request
         .agent_public_key
+        .clone()
         .or_else(|| state.agent_public_key.clone())
         .ok_or_else(|| (StatusCode::BAD_REQUEST, "agent_public_key manquant".to_string()))?;
 
     let mut server = state.server.lock().await;
     let response = server
-        .handle_rotation(&agent_public_key)
+        .handle_rotation(&request, &agent_public_key)
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
     Ok(Json(response))
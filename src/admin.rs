@@ -0,0 +1,145 @@
+use crate::config::ManagementConfig;
+use crate::crypto::{constant_time_compare, generate_nonce, sha256, verify_pss_with_key};
+use crate::protocol::{admin_challenge_message, AdminCredential};
+use anyhow::Context;
+use base64::{engine::general_purpose, Engine as _};
+use rsa::RsaPublicKey;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// Durée de validité d'un challenge d'administration
+pub const CHALLENGE_TTL: Duration = Duration::from_secs(30);
+
+/// Challenge émis sur une connexion IPC, à usage unique
+pub struct Challenge {
+    nonce: Vec<u8>,
+    issued_at: Instant,
+}
+
+impl Challenge {
+    pub fn new() -> Self {
+        Self {
+            nonce: generate_nonce(32),
+            issued_at: Instant::now(),
+        }
+    }
+
+    pub fn nonce(&self) -> &[u8] {
+        &self.nonce
+    }
+
+    fn is_expired(&self) -> bool {
+        self.issued_at.elapsed() > CHALLENGE_TTL
+    }
+}
+
+impl Default for Challenge {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Certificat d'administrateur
+struct AdminCertificate {
+    /// SHA-256 (hexadécimal) du certificat DER
+    fingerprint: String,
+    public_key: RsaPublicKey,
+}
+
+/// Authentification des commandes d'administration IPC
+///
+/// Une commande d'administration doit porter un jeton connu ou une signature du
+/// challenge de la connexion ; le rôle du client est vérifié par le serveur IPC.
+pub struct AdminAuthenticator {
+    token_hashes: Vec<[u8; 32]>,
+    certificates: Vec<AdminCertificate>,
+}
+
+impl AdminAuthenticator {
+    pub fn from_config(config: &ManagementConfig) -> anyhow::Result<Self> {
+        let token_hashes = config
+            .admin_token_sha256
+            .iter()
+            .map(|hash| {
+                let bytes = hex::decode(hash)?;
+                <[u8; 32]>::try_from(bytes.as_slice())
+                    .map_err(|_| anyhow::anyhow!("Admin token hash must be 32 bytes"))
+            })
+            .collect::<anyhow::Result<_>>()?;
+
+        let certificates = config
+            .admin_certificates
+            .iter()
+            .map(|path| load_admin_certificate(path))
+            .collect::<anyhow::Result<_>>()?;
+
+        Ok(Self {
+            token_hashes,
+            certificates,
+        })
+    }
+
+    /// Vérifie les identifiants d'une commande d'administration
+    ///
+    /// Retourne l'identité de l'administrateur (`token:...` ou `cert:...`),
+    /// ou la raison du refus.
+    pub fn authorize(
+        &self,
+        credential: Option<&AdminCredential>,
+        challenge: Option<&Challenge>,
+        command: &str,
+    ) -> Result<String, String> {
+        match credential {
+            None => Err("Admin credential required".to_string()),
+            Some(AdminCredential::Token { token }) => {
+                let hash = sha256(token.as_bytes());
+                self.token_hashes
+                    .iter()
+                    .find(|expected| constant_time_compare(expected.as_slice(), &hash))
+                    .map(|expected| format!("token:{}", hex::encode(&expected[..4])))
+                    .ok_or_else(|| "Invalid admin token".to_string())
+            }
+            Some(AdminCredential::Signature { certificate_sha256, challenge: presented, signature }) => {
+                let challenge = challenge
+                    .filter(|challenge| !challenge.is_expired())
+                    .ok_or_else(|| "No valid challenge on this connection".to_string())?;
+                let presented = hex::decode(presented).map_err(|_| "Invalid challenge encoding".to_string())?;
+                if !constant_time_compare(&presented, challenge.nonce()) {
+                    return Err("Challenge mismatch".to_string());
+                }
+
+                let certificate = self
+                    .certificates
+                    .iter()
+                    .find(|cert| cert.fingerprint.eq_ignore_ascii_case(certificate_sha256))
+                    .ok_or_else(|| "Unknown admin certificate".to_string())?;
+                let signature = general_purpose::STANDARD
+                    .decode(signature)
+                    .map_err(|_| "Invalid signature encoding".to_string())?;
+
+                let message = admin_challenge_message(challenge.nonce(), command);
+                match verify_pss_with_key(&certificate.public_key, &message, &signature) {
+                    Ok(true) => Ok(format!("cert:{}", &certificate.fingerprint[..16])),
+                    _ => Err("Invalid admin signature".to_string()),
+                }
+            }
+        }
+    }
+}
+
+fn load_admin_certificate(path: &Path) -> anyhow::Result<AdminCertificate> {
+    use rsa::pkcs8::DecodePublicKey;
+    use x509_cert::der::{Decode, Encode};
+
+    let der = crate::tls::load_certificates(path)?.remove(0).0;
+    let cert = x509_cert::Certificate::from_der(&der)
+        .with_context(|| format!("Invalid admin certificate {}", path.display()))?;
+    let spki = cert.tbs_certificate.subject_public_key_info.to_der()?;
+    let public_key = RsaPublicKey::from_public_key_der(&spki)
+        .map_err(|e| anyhow::anyhow!("Admin certificate {} must hold an RSA key: {}", path.display(), e))?;
+
+    Ok(AdminCertificate {
+        fingerprint: hex::encode(sha256(&der)),
+        public_key,
+    })
+}
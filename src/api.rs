@@ -0,0 +1,538 @@
+use crate::config::{Config, ManagementConfig};
+use crate::core::CoreEngine;
+use crate::crypto::{constant_time_compare, sha256};
+use crate::ipc::IpcServer;
+use crate::protocol::{ErrorCode, ErrorPayload, IpcRequest, IpcResponse, LogsRequest};
+use crate::roles::Role;
+use crate::types::AgentResult;
+use axum::extract::{OriginalUri, Path, Query, Request, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Extension, Json, Router};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Semaphore;
+use tokio_rustls::TlsAcceptor;
+use tracing::{debug, error, info, warn};
+
+/// Type MIME du format texte Prometheus
+const PROMETHEUS_CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Délai de traitement par défaut d'une requête de gestion
+const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Délai par défaut de la poignée de main TLS et de la lecture des en-têtes
+const DEFAULT_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Nombre maximal par défaut de connexions HTTP simultanées
+const DEFAULT_MAX_CONNECTIONS: usize = 64;
+
+/// Serveur HTTP de l'agent
+///
+/// `/metrics` et `/healthz` sont publics. Les routes de gestion (`/api/v1/...`)
+/// reprennent les commandes du CLI et exigent un jeton bearer ou un certificat
+/// client vérifié (mTLS), dont le rôle doit suffire pour la commande.
+///
+/// Les connexions simultanées sont plafonnées ; la poignée de main TLS et la
+/// lecture des en-têtes de chaque requête sont bornées dans le temps.
+pub struct ApiServer {
+    listener: TcpListener,
+    tls: Option<TlsAcceptor>,
+    router: Router,
+    handshake_timeout: Duration,
+    connections: Arc<Semaphore>,
+}
+
+/// Identité TLS du client, propre à chaque connexion
+#[derive(Debug, Clone, Default)]
+pub struct ClientIdentity {
+    /// SHA-256 (hexadécimal) du certificat client vérifié
+    pub certificate_sha256: Option<String>,
+}
+
+/// Appelant authentifié de l'API de gestion
+#[derive(Debug, Clone)]
+pub struct ApiPrincipal {
+    /// `token:<début de l'empreinte>` ou `cert:<empreinte>`
+    pub name: String,
+    pub role: Role,
+}
+
+/// Authentification des routes de gestion
+///
+/// Les jetons de `api_token_sha256` ont le rôle `operator`. Un certificat
+/// client n'authentifie que s'il est listé dans `api_roles` : signé par
+/// `api_client_ca` sans y figurer, il n'obtient aucun rôle.
+#[derive(Debug, Clone)]
+pub struct ApiAuth {
+    tokens: Vec<([u8; 32], Role)>,
+    /// Rôle par empreinte de certificat (hexadécimal, minuscules)
+    certificates: HashMap<String, Role>,
+    accept_client_certificates: bool,
+}
+
+impl ApiAuth {
+    pub fn from_config(config: &ManagementConfig) -> anyhow::Result<Self> {
+        let roles = config.api_roles.clone().unwrap_or_default();
+
+        let mut tokens = Vec::new();
+        for (hashes, role) in [
+            (&config.api_token_sha256, Role::Operator),
+            (&roles.operator.token_sha256, Role::Operator),
+            (&roles.admin.token_sha256, Role::Admin),
+        ] {
+            for hash in hashes {
+                let bytes = hex::decode(hash)?;
+                let hash = <[u8; 32]>::try_from(bytes.as_slice())
+                    .map_err(|_| anyhow::anyhow!("API token hash must be 32 bytes"))?;
+                tokens.push((hash, role));
+            }
+        }
+
+        let mut certificates = HashMap::new();
+        for (fingerprints, role) in [
+            (&roles.operator.certificate_sha256, Role::Operator),
+            (&roles.admin.certificate_sha256, Role::Admin),
+        ] {
+            for fingerprint in fingerprints {
+                let entry = certificates.entry(fingerprint.to_ascii_lowercase()).or_insert(role);
+                *entry = (*entry).max(role);
+            }
+        }
+
+        let accept_client_certificates = config.api_client_ca.is_some();
+        if accept_client_certificates && certificates.is_empty() {
+            warn!("management.api_client_ca is set but no certificate is listed in management.api_roles: client certificates are not accepted");
+        }
+
+        Ok(Self {
+            tokens,
+            certificates,
+            accept_client_certificates,
+        })
+    }
+
+    /// Aucune méthode d'authentification configurée : routes de gestion désactivées
+    pub fn is_enabled(&self) -> bool {
+        !self.tokens.is_empty() || (self.accept_client_certificates && !self.certificates.is_empty())
+    }
+
+    /// Retourne l'appelant authentifié et son rôle
+    pub fn authenticate(&self, headers: &HeaderMap, client: &ClientIdentity) -> Option<ApiPrincipal> {
+        // Certificat non listé : pas de rôle, un jeton reste possible
+        if self.accept_client_certificates {
+            if let Some((fingerprint, role)) = client
+                .certificate_sha256
+                .as_ref()
+                .and_then(|fingerprint| self.certificates.get_key_value(fingerprint))
+            {
+                return Some(ApiPrincipal {
+                    name: format!("cert:{}", fingerprint),
+                    role: *role,
+                });
+            }
+        }
+
+        let token = headers
+            .get(header::AUTHORIZATION)?
+            .to_str()
+            .ok()?
+            .strip_prefix("Bearer ")?
+            .trim();
+        let hash = sha256(token.as_bytes());
+        // Un jeton listé sous plusieurs rôles obtient le plus élevé
+        self.tokens
+            .iter()
+            .filter(|(expected, _)| constant_time_compare(expected.as_slice(), &hash))
+            .max_by_key(|(_, role)| *role)
+            .map(|(expected, role)| ApiPrincipal {
+                name: format!("token:{}", hex::encode(&expected[..4])),
+                role: *role,
+            })
+    }
+}
+
+impl ApiServer {
+    pub async fn bind(config: &Config, addr: SocketAddr, core: Arc<CoreEngine>) -> anyhow::Result<Self> {
+        let management = &config.management;
+        let tls = match (&management.api_tls_cert, &management.api_tls_key) {
+            (Some(cert), Some(key)) => {
+                let server_config =
+                    crate::tls::build_api_server_config(cert, key, management.api_client_ca.as_deref())?;
+                Some(TlsAcceptor::from(Arc::new(server_config)))
+            }
+            _ => None,
+        };
+
+        let auth = ApiAuth::from_config(management)?;
+        let request_timeout = management
+            .api_request_timeout_seconds
+            .map_or(DEFAULT_REQUEST_TIMEOUT, Duration::from_secs);
+        let handshake_timeout = management
+            .api_handshake_timeout_seconds
+            .map_or(DEFAULT_HANDSHAKE_TIMEOUT, Duration::from_secs);
+        let max_connections = management.api_max_connections.unwrap_or(DEFAULT_MAX_CONNECTIONS).max(1);
+        if !auth.is_enabled() {
+            info!("No API token or client CA configured, management API disabled");
+        } else if tls.is_none() && !addr.ip().is_loopback() {
+            warn!("⚠️  Management API served over plain HTTP on {} - bearer tokens are sent in clear text", addr);
+        }
+
+        let listener = TcpListener::bind(addr).await?;
+        info!(
+            "HTTP API listening on {} ({})",
+            listener.local_addr()?,
+            if tls.is_some() { "https" } else { "http" }
+        );
+
+        Ok(Self {
+            listener,
+            tls,
+            router: router(core, auth, request_timeout),
+            handshake_timeout,
+            connections: Arc::new(Semaphore::new(max_connections)),
+        })
+    }
+
+    pub fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Sert les requêtes jusqu'à l'arrêt de l'agent
+    pub async fn run(self, shutdown: Arc<tokio::sync::Notify>) -> anyhow::Result<()> {
+        loop {
+            // Au-delà du plafond, les connexions attendent dans la file d'écoute du noyau
+            let permit = tokio::select! {
+                permit = Arc::clone(&self.connections).acquire_owned() => permit?,
+                _ = shutdown.notified() => break,
+            };
+            let (stream, peer_addr) = tokio::select! {
+                accepted = self.listener.accept() => match accepted {
+                    Ok(accepted) => accepted,
+                    Err(e) => {
+                        error!("Failed to accept HTTP connection: {}", e);
+                        tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+                        continue;
+                    }
+                },
+                _ = shutdown.notified() => break,
+            };
+
+            let tls = self.tls.clone();
+            let router = self.router.clone();
+            let handshake_timeout = self.handshake_timeout;
+            tokio::spawn(async move {
+                let _permit = permit;
+                if let Err(e) = Self::handle_connection(stream, tls, router, handshake_timeout).await {
+                    debug!("HTTP connection from {} failed: {}", peer_addr, e);
+                }
+            });
+        }
+
+        Ok(())
+    }
+
+    async fn handle_connection(
+        stream: TcpStream,
+        tls: Option<TlsAcceptor>,
+        router: Router,
+        handshake_timeout: Duration,
+    ) -> anyhow::Result<()> {
+        match tls {
+            Some(acceptor) => {
+                let stream = tokio::time::timeout(handshake_timeout, acceptor.accept(stream))
+                    .await
+                    .map_err(|_| anyhow::anyhow!("TLS handshake timed out"))??;
+                let identity = ClientIdentity {
+                    certificate_sha256: stream
+                        .get_ref()
+                        .1
+                        .peer_certificates()
+                        .and_then(|certs| certs.first())
+                        .map(|cert| hex::encode(sha256(&cert.0))),
+                };
+                Self::serve(stream, router.layer(Extension(identity)), handshake_timeout).await
+            }
+            None => {
+                Self::serve(stream, router.layer(Extension(ClientIdentity::default())), handshake_timeout).await
+            }
+        }
+    }
+
+    async fn serve<S>(io: S, router: Router, header_read_timeout: Duration) -> anyhow::Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        hyper::server::conn::http1::Builder::new()
+            .timer(hyper_util::rt::TokioTimer::new())
+            .header_read_timeout(header_read_timeout)
+            .serve_connection(
+                hyper_util::rt::TokioIo::new(io),
+                hyper_util::service::TowerToHyperService::new(router),
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+/// Routes de l'API
+///
+/// `request_timeout` borne chaque requête de gestion, lecture du corps comprise.
+pub fn router(core: Arc<CoreEngine>, auth: ApiAuth, request_timeout: Duration) -> Router {
+    let management = Router::new()
+        .route("/status", get(status))
+        .route("/rotate", post(rotate))
+        .route("/secrets/:version/invalidate", post(invalidate))
+        .route("/degraded-mode", get(degraded_mode_status).post(degraded_mode))
+        .route("/logs", get(logs))
+        .route("/tpm", get(tpm_status))
+        .route_layer(middleware::from_fn_with_state(request_timeout, limit_duration))
+        .route_layer(middleware::from_fn_with_state((Arc::new(auth), Arc::clone(&core)), require_auth));
+
+    Router::new()
+        .route("/metrics", get(metrics))
+        .route("/healthz", get(healthz))
+        .nest("/api/v1", management)
+        .with_state(core)
+}
+
+/// Authentifie les routes de gestion et audite chaque commande avec l'identité de l'appelant
+///
+/// Le rôle est vérifié par chaque route ([`execute`]), qui connaît la commande.
+async fn require_auth(
+    State((auth, core)): State<(Arc<ApiAuth>, Arc<CoreEngine>)>,
+    Extension(client): Extension<ClientIdentity>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    if !auth.is_enabled() {
+        return error_response(StatusCode::NOT_FOUND, ErrorCode::InvalidRequest, "Management API disabled");
+    }
+
+    // Chemin complet (les routes imbriquées voient le chemin sans le préfixe `/api/v1`)
+    let path = request
+        .extensions()
+        .get::<OriginalUri>()
+        .map_or_else(|| request.uri().path().to_string(), |uri| uri.path().to_string());
+    let command = format!("{} {}", request.method(), path);
+
+    match auth.authenticate(request.headers(), &client) {
+        Some(principal) => {
+            debug!("Management API {} by {} ({})", command, principal.name, principal.role);
+            let caller = serde_json::json!({
+                "transport": "http",
+                "principal": principal.name,
+                "role": principal.role.as_str(),
+            });
+            request.extensions_mut().insert(principal);
+            let response = next.run(request).await;
+
+            let status = response.status();
+            let error = (!status.is_success()).then(|| format!("HTTP {}", status.as_u16()));
+            core.audit_logger().admin_command(&command, caller, error.as_deref()).await;
+            response
+        }
+        None => {
+            warn!("Rejected unauthenticated management API request: {}", command);
+            let caller = serde_json::json!({ "transport": "http" });
+            core.audit_logger().admin_auth_failed(&command, caller, "Authentication required").await;
+            let mut response = error_response(StatusCode::UNAUTHORIZED, ErrorCode::Unauthorized, "Authentication required");
+            response
+                .headers_mut()
+                .insert(header::WWW_AUTHENTICATE, header::HeaderValue::from_static("Bearer"));
+            response
+        }
+    }
+}
+
+/// Répond `503` si la requête (corps compris) n'est pas traitée dans le délai
+///
+/// La commande elle-même s'exécute dans une tâche séparée ([`execute`]) : une
+/// rotation commencée se termine même si la réponse n'est plus attendue.
+async fn limit_duration(State(timeout): State<Duration>, request: Request, next: Next) -> Response {
+    let path = request.uri().path().to_string();
+    match tokio::time::timeout(timeout, next.run(request)).await {
+        Ok(response) => response,
+        Err(_) => {
+            warn!("Management API request {} timed out after {:?}", path, timeout);
+            error_response(StatusCode::SERVICE_UNAVAILABLE, ErrorCode::InternalError, "Request timed out")
+        }
+    }
+}
+
+async fn metrics(State(core): State<Arc<CoreEngine>>) -> Response {
+    match core.metrics_text().await {
+        Ok(text) => ([(header::CONTENT_TYPE, PROMETHEUS_CONTENT_TYPE)], text).into_response(),
+        Err(e) => {
+            error!("Failed to export metrics: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// 200 tant qu'un secret actif permet de valider des licences, 503 sinon
+async fn healthz(State(core): State<Arc<CoreEngine>>) -> Response {
+    let active_version = core.active_version();
+    let degraded_mode = core.degraded_mode_status().await.active;
+    let status = if active_version.is_some() {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    let body = serde_json::json!({
+        "status": if status == StatusCode::OK { "ok" } else { "unavailable" },
+        "active_version": active_version,
+        "degraded_mode": degraded_mode,
+    });
+    (status, Json(body)).into_response()
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct RotateBody {
+    #[serde(default)]
+    force: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct InvalidateBody {
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct DegradedModeBody {
+    #[serde(default)]
+    enable: bool,
+    #[serde(default)]
+    disable: bool,
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+async fn status(State(core): State<Arc<CoreEngine>>, Extension(caller): Extension<ApiPrincipal>) -> Response {
+    execute(&core, &caller, IpcRequest::Status {}).await
+}
+
+async fn rotate(
+    State(core): State<Arc<CoreEngine>>,
+    Extension(caller): Extension<ApiPrincipal>,
+    body: Option<Json<RotateBody>>,
+) -> Response {
+    let Json(body) = body.unwrap_or_default();
+    execute(&core, &caller, IpcRequest::Rotate { force: body.force }).await
+}
+
+async fn invalidate(
+    State(core): State<Arc<CoreEngine>>,
+    Extension(caller): Extension<ApiPrincipal>,
+    Path(version): Path<u64>,
+    body: Option<Json<InvalidateBody>>,
+) -> Response {
+    let Json(body) = body.unwrap_or_default();
+    execute(&core, &caller, IpcRequest::Invalidate { version, reason: body.reason }).await
+}
+
+async fn degraded_mode_status(
+    State(core): State<Arc<CoreEngine>>,
+    Extension(caller): Extension<ApiPrincipal>,
+) -> Response {
+    execute(&core, &caller, IpcRequest::DegradedMode { enable: false, disable: false, reason: None }).await
+}
+
+async fn degraded_mode(
+    State(core): State<Arc<CoreEngine>>,
+    Extension(caller): Extension<ApiPrincipal>,
+    Json(body): Json<DegradedModeBody>,
+) -> Response {
+    let request = IpcRequest::DegradedMode {
+        enable: body.enable,
+        disable: body.disable,
+        reason: body.reason,
+    };
+    execute(&core, &caller, request).await
+}
+
+async fn logs(
+    State(core): State<Arc<CoreEngine>>,
+    Extension(caller): Extension<ApiPrincipal>,
+    Query(request): Query<LogsRequest>,
+) -> Response {
+    execute(&core, &caller, IpcRequest::Logs(request)).await
+}
+
+async fn tpm_status(State(core): State<Arc<CoreEngine>>, Extension(caller): Extension<ApiPrincipal>) -> Response {
+    execute(&core, &caller, IpcRequest::TpmStatus {}).await
+}
+
+/// Exécute la commande comme l'IPC et renvoie son champ `data` en JSON
+///
+/// Le rôle de l'appelant doit suffire pour la commande, comme sur l'IPC.
+async fn execute(core: &Arc<CoreEngine>, caller: &ApiPrincipal, request: IpcRequest) -> Response {
+    let required_role = request.required_role();
+    if caller.role < required_role {
+        let command = request.command();
+        warn!("Denied {} to {} with role {} (requires {})", command, caller.name, caller.role, required_role);
+        let caller_json = serde_json::json!({ "transport": "http", "principal": caller.name });
+        core.audit_logger()
+            .access_denied(Some(command), caller_json, Some(caller.role.as_str()), Some(required_role.as_str()))
+            .await;
+        return error_response(
+            StatusCode::FORBIDDEN,
+            ErrorCode::Forbidden,
+            format!("Command {} requires role {}", command, required_role),
+        );
+    }
+
+    // Tâche séparée : le délai de la requête n'interrompt pas une commande en cours
+    let core = Arc::clone(core);
+    match tokio::spawn(async move { IpcServer::dispatch(&core, request).await }).await {
+        Ok(result) => json_response(result),
+        Err(e) => error_response(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            ErrorCode::InternalError,
+            format!("Command failed: {}", e),
+        ),
+    }
+}
+
+fn json_response(result: AgentResult<IpcResponse>) -> Response {
+    match result {
+        Ok(response) => match serde_json::to_value(response) {
+            Ok(mut value) => Json(value["data"].take()).into_response(),
+            Err(e) => error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                ErrorCode::InternalError,
+                format!("Failed to serialize response: {}", e),
+            ),
+        },
+        Err(e) => {
+            let payload = ErrorPayload::from(&e);
+            (status_for(payload.code), Json(payload)).into_response()
+        }
+    }
+}
+
+fn error_response(status: StatusCode, code: ErrorCode, message: impl Into<String>) -> Response {
+    (status, Json(ErrorPayload::new(code, message))).into_response()
+}
+
+fn status_for(code: ErrorCode) -> StatusCode {
+    match code {
+        ErrorCode::InvalidRequest | ErrorCode::IpcError | ErrorCode::UnsupportedVersion => StatusCode::BAD_REQUEST,
+        ErrorCode::Unauthorized => StatusCode::UNAUTHORIZED,
+        ErrorCode::Forbidden => StatusCode::FORBIDDEN,
+        ErrorCode::RateLimited => StatusCode::TOO_MANY_REQUESTS,
+        ErrorCode::ReplayDetected => StatusCode::CONFLICT,
+        ErrorCode::SecretNotFound => StatusCode::NOT_FOUND,
+        ErrorCode::NetworkError | ErrorCode::RotationFailed => StatusCode::BAD_GATEWAY,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
@@ -0,0 +1,291 @@
+use crate::crypto::{generate_nonce, sha256};
+use crate::types::{AgentError, AgentResult, ValidateLicenseResponse, ValidationResult};
+use base64::{engine::general_purpose, Engine as _};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+use zeroize::Zeroizing;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Durée pendant laquelle un nonce de validation est refusé s'il est réutilisé
+pub const NONCE_TTL: Duration = Duration::from_secs(300);
+
+/// Nombre maximal de nonces non expirés retenus par UID
+const MAX_NONCES_PER_UID: usize = 65536;
+
+/// Préfixe des messages authentifiés par le MAC de réponse
+const RESPONSE_MAC_CONTEXT: &[u8] = b"license-agent validate response v1\0";
+
+/// Taille des clés d'application (HMAC-SHA256)
+const APP_KEY_LEN: usize = 32;
+
+/// Message authentifié d'une réponse de validation
+///
+/// `contexte || nonce || SHA-256(token) || résultat JSON (clés triées)` : la
+/// réponse est liée à la requête, un client détecte une réponse rejouée ou
+/// émise par un faux agent.
+pub fn response_mac_message(
+    nonce: &[u8; 16],
+    license_token: &[u8],
+    result: &ValidationResult,
+) -> AgentResult<Vec<u8>> {
+    // `serde_json::Value` trie les clés des objets : encodage indépendant de l'ordre des métadonnées
+    let canonical = serde_json::to_value(result)
+        .and_then(|value| serde_json::to_vec(&value))
+        .map_err(|e| AgentError::InternalError(format!("Failed to encode validation result: {}", e)))?;
+
+    let mut message = Vec::with_capacity(RESPONSE_MAC_CONTEXT.len() + 16 + 32 + canonical.len());
+    message.extend_from_slice(RESPONSE_MAC_CONTEXT);
+    message.extend_from_slice(nonce);
+    message.extend_from_slice(&sha256(license_token));
+    message.extend_from_slice(&canonical);
+    Ok(message)
+}
+
+/// MAC (HMAC-SHA256, Base64) d'une réponse de validation
+pub fn compute_response_mac(
+    key: &[u8],
+    nonce: &[u8; 16],
+    license_token: &[u8],
+    result: &ValidationResult,
+) -> AgentResult<String> {
+    let mut mac = HmacSha256::new_from_slice(key)
+        .map_err(|e| AgentError::CryptoError(format!("Invalid application key: {}", e)))?;
+    mac.update(&response_mac_message(nonce, license_token, result)?);
+    Ok(general_purpose::STANDARD.encode(mac.finalize().into_bytes()))
+}
+
+/// Vérification côté client d'une réponse de validation
+///
+/// `key` est la clé de l'application fournie à son enregistrement, `nonce` et
+/// `license_token` ceux de la requête envoyée.
+pub fn verify_validation_response(
+    key: &[u8],
+    nonce: &[u8; 16],
+    license_token: &[u8],
+    response: &ValidateLicenseResponse,
+) -> AgentResult<()> {
+    let presented = response
+        .mac
+        .as_deref()
+        .ok_or_else(|| AgentError::SignatureVerificationFailed("Response is not authenticated".to_string()))?;
+    let presented = general_purpose::STANDARD
+        .decode(presented)
+        .map_err(|_| AgentError::SignatureVerificationFailed("Invalid response MAC encoding".to_string()))?;
+
+    let mut mac = HmacSha256::new_from_slice(key)
+        .map_err(|e| AgentError::CryptoError(format!("Invalid application key: {}", e)))?;
+    mac.update(&response_mac_message(nonce, license_token, &response.result)?);
+    mac.verify_slice(&presented)
+        .map_err(|_| AgentError::SignatureVerificationFailed("Response MAC mismatch".to_string()))
+}
+
+/// Refus d'un nonce de validation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NonceRejection {
+    /// Nonce déjà présenté pendant `ttl`
+    Replayed,
+    /// Trop de nonces non expirés pour cet UID : à réessayer plus tard
+    CacheFull,
+}
+
+/// Nonces de validation récemment vus, par UID client
+///
+/// Un nonce n'est oublié qu'à son expiration : au-delà de la limite par UID,
+/// les nouveaux nonces de cet UID sont refusés plutôt que d'oublier les plus
+/// anciens, ce qui permettrait de les rejouer. Les autres UID ne sont pas affectés.
+pub struct NonceCache {
+    ttl: Duration,
+    max_per_uid: usize,
+    entries: Mutex<NonceEntries>,
+}
+
+#[derive(Default)]
+struct NonceEntries {
+    by_uid: HashMap<u32, UidNonces>,
+    /// Dernière purge des UID sans nonce actif
+    swept_at: Option<Instant>,
+}
+
+#[derive(Default)]
+struct UidNonces {
+    seen: HashSet<[u8; 16]>,
+    /// Ordre d'arrivée, pour l'expiration
+    order: VecDeque<(Instant, [u8; 16])>,
+}
+
+impl UidNonces {
+    fn expire(&mut self, now: Instant, ttl: Duration) {
+        while let Some((at, nonce)) = self.order.front().copied() {
+            if now.duration_since(at) < ttl {
+                break;
+            }
+            self.order.pop_front();
+            self.seen.remove(&nonce);
+        }
+    }
+}
+
+impl NonceCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            max_per_uid: MAX_NONCES_PER_UID,
+            entries: Mutex::new(NonceEntries::default()),
+        }
+    }
+
+    pub fn with_max_per_uid(mut self, max_per_uid: usize) -> Self {
+        self.max_per_uid = max_per_uid;
+        self
+    }
+
+    /// Enregistre un nonce, ou indique pourquoi il est refusé
+    pub fn check_and_insert(&self, uid: u32, nonce: &[u8; 16]) -> Result<(), NonceRejection> {
+        let mut entries = self.entries.lock().unwrap();
+        let now = Instant::now();
+
+        if entries.swept_at.is_none_or(|at| now.duration_since(at) >= self.ttl) {
+            entries.by_uid.retain(|_, nonces| {
+                nonces.expire(now, self.ttl);
+                !nonces.order.is_empty()
+            });
+            entries.swept_at = Some(now);
+        }
+
+        let nonces = entries.by_uid.entry(uid).or_default();
+        nonces.expire(now, self.ttl);
+        if nonces.seen.contains(nonce) {
+            return Err(NonceRejection::Replayed);
+        }
+        if nonces.seen.len() >= self.max_per_uid {
+            return Err(NonceRejection::CacheFull);
+        }
+        nonces.seen.insert(*nonce);
+        nonces.order.push_back((now, *nonce));
+        Ok(())
+    }
+}
+
+impl Default for NonceCache {
+    fn default() -> Self {
+        Self::new(NONCE_TTL)
+    }
+}
+
+/// Clés des applications enregistrées
+///
+/// Une clé par application (`<app_id>.key`, Base64, 0600), générée par la
+/// commande `register_app` et transmise à l'application lors de son déploiement.
+pub struct AppKeyStore {
+    dir: PathBuf,
+    keys: RwLock<HashMap<String, Zeroizing<Vec<u8>>>>,
+}
+
+impl AppKeyStore {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            keys: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Clé d'une application enregistrée
+    pub async fn key(&self, app_id: &str) -> AgentResult<Zeroizing<Vec<u8>>> {
+        validate_app_id(app_id)?;
+        if let Some(key) = self.keys.read().await.get(app_id) {
+            return Ok(key.clone());
+        }
+
+        let path = self.key_path(app_id);
+        let encoded = Zeroizing::new(tokio::fs::read_to_string(&path).await.map_err(|e| {
+            if e.kind() == std::io::ErrorKind::NotFound {
+                AgentError::ConfigError(format!("Unknown application: {}", app_id))
+            } else {
+                AgentError::InternalError(format!("Failed to read {}: {}", path.display(), e))
+            }
+        })?);
+        let key = Zeroizing::new(
+            general_purpose::STANDARD
+                .decode(encoded.trim())
+                .map_err(|_| AgentError::ConfigError(format!("Invalid key file for application {}", app_id)))?,
+        );
+
+        self.keys.write().await.insert(app_id.to_string(), key.clone());
+        Ok(key)
+    }
+
+    /// Génère (ou remplace) la clé d'une application
+    pub async fn register(&self, app_id: &str) -> AgentResult<Zeroizing<Vec<u8>>> {
+        validate_app_id(app_id)?;
+        let key = Zeroizing::new(generate_nonce(APP_KEY_LEN));
+        let encoded = Zeroizing::new(general_purpose::STANDARD.encode(&*key));
+
+        let io_error = |action: &str, path: &Path, e: std::io::Error| {
+            AgentError::InternalError(format!("Failed to {} {}: {}", action, path.display(), e))
+        };
+
+        tokio::fs::create_dir_all(&self.dir)
+            .await
+            .map_err(|e| io_error("create", &self.dir, e))?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            tokio::fs::set_permissions(&self.dir, std::fs::Permissions::from_mode(0o700))
+                .await
+                .map_err(|e| io_error("chmod", &self.dir, e))?;
+        }
+
+        let path = self.key_path(app_id);
+        let tmp_path = self.dir.join(format!(".{}.key.tmp", app_id));
+        let mut options = tokio::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        options.mode(0o600);
+
+        let mut file = options
+            .open(&tmp_path)
+            .await
+            .map_err(|e| io_error("create", &tmp_path, e))?;
+        file.write_all(encoded.as_bytes())
+            .await
+            .map_err(|e| io_error("write", &tmp_path, e))?;
+        file.sync_all().await.map_err(|e| io_error("sync", &tmp_path, e))?;
+        drop(file);
+        tokio::fs::rename(&tmp_path, &path)
+            .await
+            .map_err(|e| io_error("rename", &path, e))?;
+
+        if self.keys.write().await.insert(app_id.to_string(), key.clone()).is_some() {
+            warn!("Application key for {} replaced", app_id);
+        }
+        info!("Application {} registered", app_id);
+        Ok(key)
+    }
+
+    fn key_path(&self, app_id: &str) -> PathBuf {
+        self.dir.join(format!("{}.key", app_id))
+    }
+}
+
+/// Identifiant d'application : lettres, chiffres, `-` et `_` (64 caractères au plus)
+fn validate_app_id(app_id: &str) -> AgentResult<()> {
+    let valid = !app_id.is_empty()
+        && app_id.len() <= 64
+        && app_id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        return Err(AgentError::ConfigError(format!("Invalid application id: {:?}", app_id)));
+    }
+    Ok(())
+}
@@ -1,13 +1,16 @@
 use crate::config::Config;
+use crate::executable::ExecutableCheck;
+use crate::types::TamperStatus;
 use chrono::{DateTime, Utc};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::io::AsyncWriteExt;
 use tokio::sync::Mutex;
 use tracing::{debug, error, info};
 
 /// Événement d'audit
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AuditEvent {
     pub timestamp: DateTime<Utc>,
     pub event: String,
@@ -15,7 +18,7 @@ pub struct AuditEvent {
     pub data: serde_json::Value,
 }
 
-#[derive(Debug, Clone, Copy, Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum AuditLevel {
     Info,
@@ -24,9 +27,24 @@ pub enum AuditLevel {
     Critical,
 }
 
+impl std::str::FromStr for AuditLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "info" => Ok(AuditLevel::Info),
+            "warning" | "warn" => Ok(AuditLevel::Warning),
+            "error" => Ok(AuditLevel::Error),
+            "critical" => Ok(AuditLevel::Critical),
+            other => Err(format!("Unknown audit level: {}", other)),
+        }
+    }
+}
+
 /// Logger d'audit
 pub struct AuditLogger {
     file: Arc<Mutex<tokio::fs::File>>,
+    path: PathBuf,
 }
 
 impl AuditLogger {
@@ -49,9 +67,37 @@ impl AuditLogger {
 
         Ok(Self {
             file: Arc::new(Mutex::new(file)),
+            path: log_path,
         })
     }
 
+    /// Relit les derniers événements du journal, avec filtres optionnels
+    pub async fn read_events(
+        &self,
+        tail: usize,
+        event: Option<&str>,
+        min_level: Option<AuditLevel>,
+        since: Option<DateTime<Utc>>,
+    ) -> anyhow::Result<Vec<AuditEvent>> {
+        // Attendre la fin d'une écriture éventuelle
+        let _guard = self.file.lock().await;
+        let content = tokio::fs::read_to_string(&self.path).await?;
+
+        let mut events: Vec<AuditEvent> = content
+            .lines()
+            .filter_map(|line| serde_json::from_str::<AuditEvent>(line).ok())
+            .filter(|e| event.is_none_or(|name| e.event == name))
+            .filter(|e| min_level.is_none_or(|level| e.level >= level))
+            .filter(|e| since.is_none_or(|since| e.timestamp >= since))
+            .collect();
+
+        if events.len() > tail {
+            events.drain(..events.len() - tail);
+        }
+
+        Ok(events)
+    }
+
     /// Log un événement
     pub async fn log(&self, event: &str, level: AuditLevel, data: serde_json::Value) {
         let audit_event = AuditEvent {
@@ -137,6 +183,18 @@ impl AuditLogger {
         .await;
     }
 
+    /// Log signature serveur invalide sur une réponse de rotation
+    pub async fn rotation_signature_invalid(&self, version: u64, reason: &str) {
+        self.critical(
+            "rotation_signature_invalid",
+            serde_json::json!({
+                "version": version,
+                "reason": reason,
+            }),
+        )
+        .await;
+    }
+
     /// Log validation licence
     pub async fn license_validated(&self, license_id: &str, version: u64, result: &str) {
         self.info(
@@ -172,6 +230,95 @@ impl AuditLogger {
         .await;
     }
 
+    /// Log commande d'administration (identité de l'appelant et résultat)
+    pub async fn admin_command(&self, command: &str, caller: serde_json::Value, error: Option<&str>) {
+        let data = serde_json::json!({
+            "command": command,
+            "caller": caller,
+            "result": if error.is_some() { "error" } else { "ok" },
+            "error": error,
+        });
+        if error.is_some() {
+            self.warning("admin_command", data).await;
+        } else {
+            self.info("admin_command", data).await;
+        }
+    }
+
+    /// Log échec d'authentification d'une commande d'administration
+    pub async fn admin_auth_failed(&self, command: &str, caller: serde_json::Value, reason: &str) {
+        self.warning(
+            "admin_auth_failed",
+            serde_json::json!({
+                "command": command,
+                "caller": caller,
+                "reason": reason,
+            }),
+        )
+        .await;
+    }
+
+    /// Log refus d'accès : client sans rôle, ou rôle insuffisant pour la commande
+    pub async fn access_denied(
+        &self,
+        command: Option<&str>,
+        caller: serde_json::Value,
+        role: Option<&str>,
+        required_role: Option<&str>,
+    ) {
+        self.warning(
+            "access_denied",
+            serde_json::json!({
+                "command": command,
+                "caller": caller,
+                "role": role,
+                "required_role": required_role,
+            }),
+        )
+        .await;
+    }
+
+    /// Log vérification de l'exécutable d'un client
+    pub async fn executable_checked(&self, caller: serde_json::Value, check: &ExecutableCheck) {
+        let data = serde_json::json!({
+            "caller": caller,
+            "result": if check.is_allowed() { "allowed" } else { "denied" },
+            "check": check,
+        });
+        if check.is_allowed() {
+            self.info("executable_checked", data).await;
+        } else {
+            self.warning("executable_checked", data).await;
+        }
+    }
+
+    /// Log requête de validation rejouée (nonce déjà présenté)
+    pub async fn replay_detected(&self, uid: u32, pid: u32, nonce: &[u8]) {
+        self.warning(
+            "replay_detected",
+            serde_json::json!({
+                "uid": uid,
+                "pid": pid,
+                "nonce": hex::encode(nonce),
+            }),
+        )
+        .await;
+    }
+
+    /// Log dépassement d'une limite de débit IPC
+    pub async fn rate_limit_exceeded(&self, uid: u32, pid: u32, budget: &str, command: &str) {
+        self.warning(
+            "rate_limit_exceeded",
+            serde_json::json!({
+                "uid": uid,
+                "pid": pid,
+                "budget": budget,
+                "command": command,
+            }),
+        )
+        .await;
+    }
+
     /// Log invalidation secret
     pub async fn secret_invalidated(&self, version: u64, reason: Option<&str>) {
         self.warning(
@@ -183,4 +330,22 @@ impl AuditLogger {
         )
         .await;
     }
+
+    /// Log clé du MAC de l'état non descellable au démarrage (état non chargé)
+    pub async fn state_key_unavailable(&self, reason: &str) {
+        self.critical("state_key_unavailable", serde_json::json!({ "reason": reason })).await;
+    }
+
+    /// Log réponse à une altération du fichier d'état (à chaque démarrage tant qu'elle est active)
+    pub async fn state_tamper_response(&self, tamper: &TamperStatus) {
+        self.critical(
+            "state_tamper_response",
+            serde_json::json!({
+                "detected_at": tamper.detected_at,
+                "reason": tamper.reason,
+                "quarantined": tamper.quarantined,
+            }),
+        )
+        .await;
+    }
 }
@@ -1,8 +1,15 @@
+use crate::audit::AuditLevel;
+use crate::crypto::{sha256, CryptoManager};
+use crate::protocol::{
+    admin_challenge_message, read_frame, write_frame, AdminCredential, IpcRequest, IpcResponse,
+    LogsRequest, RequestEnvelope, ResponseEnvelope, PROTOCOL_VERSION,
+};
 use anyhow::Result;
+use base64::{engine::general_purpose, Engine as _};
+use chrono::{DateTime, Utc};
 use clap::{Parser, Subcommand};
 use std::path::PathBuf;
 use tokio::net::UnixStream;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
 
 #[derive(Parser)]
 #[command(name = "license-agent-cli")]
@@ -11,21 +18,34 @@ pub struct Cli {
     #[command(subcommand)]
     pub command: Commands,
 
-    /// Chemin vers le certificat client pour authentification
+    /// Certificat d'administrateur (déclaré dans `management.admin_certificates`)
     #[arg(long, default_value = "/etc/license-agent/admin.crt")]
     pub cert: Option<PathBuf>,
 
-    /// Chemin vers la clé privée client
+    /// Clé privée RSA du certificat, utilisée pour signer les challenges de l'agent
     #[arg(long, default_value = "/etc/license-agent/admin.key")]
     pub key: Option<PathBuf>,
 
-    /// Token d'authentification (alternative au certificat)
+    /// Jeton d'administration (alternative au certificat)
     #[arg(long)]
     pub token: Option<String>,
 
     /// Chemin vers le socket IPC
-    #[arg(long, default_value = "/var/run/license-agent.sock")]
+    #[arg(long, default_value = crate::config::DEFAULT_SOCKET_PATH)]
     pub socket: PathBuf,
+
+    #[arg(skip)]
+    login: Option<AdminLogin>,
+}
+
+/// Identifiants d'administration présentés à l'agent
+enum AdminLogin {
+    Token(String),
+    /// Signature des challenges avec la clé associée au certificat `--cert`
+    Key {
+        certificate_sha256: String,
+        signer: CryptoManager,
+    },
 }
 
 #[derive(Subcommand)]
@@ -96,12 +116,35 @@ pub enum Commands {
         #[arg(long)]
         confirm_again: bool,
     },
+
+    /// Rechiffre tous les secrets sous une nouvelle clé (KEK)
+    Rekey {
+        /// Confirmer le rechiffrement
+        #[arg(long)]
+        confirm: bool,
+    },
+
+    /// Rescelle les secrets TPM sur les valeurs courantes des PCR
+    Reseal {
+        /// Rescelle sans politique PCR avant une mise à jour firmware
+        #[arg(long)]
+        suspend_pcr_binding: bool,
+        /// Confirmer le rescellement
+        #[arg(long)]
+        confirm: bool,
+    },
+
+    /// Enregistre une application et génère sa clé d'authentification des réponses
+    RegisterApp {
+        /// Identifiant de l'application (lettres, chiffres, `-` et `_`)
+        app_id: String,
+    },
 }
 
 impl Cli {
-    pub async fn run(self) -> Result<()> {
-        // Authentification
-        self.authenticate().await?;
+    pub async fn run(mut self) -> Result<()> {
+        // Identifiants vérifiés par l'agent à chaque commande
+        self.login = Some(self.admin_login()?);
 
         // Exécuter commande
         match &self.command {
@@ -121,44 +164,74 @@ impl Cli {
             Commands::Reset { confirm, confirm_again } => {
                 self.cmd_reset(*confirm, *confirm_again).await
             }
+            Commands::Rekey { confirm } => self.cmd_rekey(*confirm).await,
+            Commands::Reseal { suspend_pcr_binding, confirm } => {
+                self.cmd_reseal(*suspend_pcr_binding, *confirm).await
+            }
+            Commands::RegisterApp { app_id } => self.cmd_register_app(app_id.clone()).await,
         }
     }
 
-    async fn authenticate(&self) -> Result<()> {
-        // Vérifier certificat ou token
+    fn admin_login(&self) -> Result<AdminLogin> {
         if let Some(token) = &self.token {
-            // Vérifier token
-            self.verify_token(token).await?;
-        } else if let Some(cert_path) = &self.cert {
-            // Vérifier certificat
-            self.verify_certificate(cert_path).await?;
-        } else {
-            anyhow::bail!("Authentification requise: --cert ou --token");
+            return Ok(AdminLogin::Token(token.clone()));
         }
-        Ok(())
-    }
 
-    async fn verify_token(&self, _token: &str) -> Result<()> {
-        // TODO: Implémenter vérification token
-        Ok(())
+        match (&self.cert, &self.key) {
+            (Some(cert), Some(key)) if cert.exists() && key.exists() => {
+                let certificate = crate::tls::load_certificates(cert)?.remove(0);
+                let signer = CryptoManager::from_pem_files(&key.to_string_lossy(), None)
+                    .map_err(|e| anyhow::anyhow!("Clé d'administration illisible ({}): {}", key.display(), e))?;
+                Ok(AdminLogin::Key {
+                    certificate_sha256: hex::encode(sha256(&certificate.0)),
+                    signer,
+                })
+            }
+            _ => anyhow::bail!("Authentification requise: --cert et --key, ou --token"),
+        }
     }
 
-    async fn verify_certificate(&self, _cert_path: &PathBuf) -> Result<()> {
-        // TODO: Implémenter vérification certificat
-        Ok(())
+    /// Identifiants joints à une commande d'administration
+    ///
+    /// Avec une clé, demande un challenge sur la connexion puis le signe.
+    async fn admin_credential(&self, stream: &mut UnixStream, request: &IpcRequest) -> Result<Option<AdminCredential>> {
+        if !request.is_admin() {
+            return Ok(None);
+        }
+
+        match &self.login {
+            None => anyhow::bail!("Authentification requise: --cert et --key, ou --token"),
+            Some(AdminLogin::Token(token)) => Ok(Some(AdminCredential::Token { token: token.clone() })),
+            Some(AdminLogin::Key { certificate_sha256, signer }) => {
+                let challenge = match Self::exchange(stream, IpcRequest::AuthChallenge {}, None).await? {
+                    IpcResponse::AuthChallenge(result) => hex::decode(&result.challenge)?,
+                    other => anyhow::bail!("Réponse inattendue au challenge: {:?}", other),
+                };
+                let signature = signer.sign_pss(&admin_challenge_message(&challenge, request.command()))?;
+                Ok(Some(AdminCredential::Signature {
+                    certificate_sha256: certificate_sha256.clone(),
+                    challenge: hex::encode(&challenge),
+                    signature: general_purpose::STANDARD.encode(signature),
+                }))
+            }
+        }
     }
 
     async fn cmd_status(&self) -> Result<()> {
-        let status_value = self.send_request("status", serde_json::json!({})).await?;
-        // Pour l'instant, afficher directement le JSON
-        // TODO: Implémenter désérialisation SystemStatus si nécessaire
-        println!("{}", serde_json::to_string_pretty(&status_value)?);
-        Ok(())
+        let response = self.send_request(IpcRequest::Status {}).await?;
+        Self::print_json(&response)
     }
 
     async fn cmd_rotate(&self, force: bool) -> Result<()> {
-        let result = self.send_request("rotate", serde_json::json!({ "force": force })).await?;
-        println!("Rotation: {}", result);
+        match self.send_request(IpcRequest::Rotate { force }).await? {
+            IpcResponse::Rotate(result) if result.rotated => {
+                println!("Rotation effectuée (version active: {:?})", result.active_version);
+            }
+            IpcResponse::Rotate(result) => {
+                println!("Rotation non nécessaire (version active: {:?})", result.active_version);
+            }
+            other => Self::print_json(&other)?,
+        }
         Ok(())
     }
 
@@ -167,30 +240,40 @@ impl Cli {
             anyhow::bail!("Confirmation requise pour invalider un secret (--confirm)");
         }
         
-        let result = self.send_request("invalidate", serde_json::json!({
-            "version": version,
-            "reason": reason
-        })).await?;
-        
-        println!("Secret {} invalidé: {}", version, result);
+        match self.send_request(IpcRequest::Invalidate { version, reason }).await? {
+            IpcResponse::Invalidate(result) => {
+                println!("Secret {} invalidé (version active: {:?})", result.version, result.active_version);
+            }
+            other => Self::print_json(&other)?,
+        }
         Ok(())
     }
 
     async fn cmd_logs(&self, tail: usize, event: Option<String>, level: Option<String>, since: Option<String>) -> Result<()> {
-        let result = self.send_request("logs", serde_json::json!({
-            "tail": tail,
-            "event": event,
-            "level": level,
-            "since": since
-        })).await?;
-        
-        println!("{}", result);
+        let level = level
+            .map(|l| l.parse::<AuditLevel>().map_err(|e| anyhow::anyhow!(e)))
+            .transpose()?;
+        let since = since
+            .map(|s| DateTime::parse_from_rfc3339(&s).map(|d| d.with_timezone(&Utc)))
+            .transpose()
+            .map_err(|e| anyhow::anyhow!("Date invalide pour --since (ISO 8601 attendu): {}", e))?;
+
+        match self.send_request(IpcRequest::Logs(LogsRequest { tail, event, level, since })).await? {
+            IpcResponse::Logs(result) => {
+                for event in result.events {
+                    println!("{}", serde_json::to_string(&event)?);
+                }
+            }
+            other => Self::print_json(&other)?,
+        }
         Ok(())
     }
 
     async fn cmd_metrics(&self) -> Result<()> {
-        let result = self.send_request("metrics", serde_json::json!({})).await?;
-        println!("{}", result);
+        match self.send_request(IpcRequest::Metrics {}).await? {
+            IpcResponse::Metrics(result) => print!("{}", result.text),
+            other => Self::print_json(&other)?,
+        }
         Ok(())
     }
 
@@ -203,20 +286,13 @@ impl Cli {
             anyhow::bail!("Raison requise pour activer le mode dégradé (--reason)");
         }
         
-        let result = self.send_request("degraded_mode", serde_json::json!({
-            "enable": enable,
-            "disable": disable,
-            "reason": reason
-        })).await?;
-        
-        println!("Mode dégradé: {}", result);
-        Ok(())
+        let response = self.send_request(IpcRequest::DegradedMode { enable, disable, reason }).await?;
+        Self::print_json(&response)
     }
 
     async fn cmd_tpm_status(&self) -> Result<()> {
-        let result = self.send_request("tpm_status", serde_json::json!({})).await?;
-        println!("{}", result);
-        Ok(())
+        let response = self.send_request(IpcRequest::TpmStatus {}).await?;
+        Self::print_json(&response)
     }
 
     async fn cmd_reset(&self, confirm: bool, confirm_again: bool) -> Result<()> {
@@ -224,32 +300,94 @@ impl Cli {
             anyhow::bail!("Double confirmation requise pour réinitialisation (--confirm --confirm-again)");
         }
         
-        let result = self.send_request("reset", serde_json::json!({})).await?;
-        println!("Réinitialisation: {}", result);
+        match self.send_request(IpcRequest::Reset {}).await? {
+            IpcResponse::Reset(result) => {
+                println!("Réinitialisation effectuée ({} secrets supprimés)", result.secrets_removed);
+            }
+            other => Self::print_json(&other)?,
+        }
         Ok(())
     }
 
-    async fn send_request(&self, command: &str, data: serde_json::Value) -> Result<serde_json::Value> {
+    async fn cmd_rekey(&self, confirm: bool) -> Result<()> {
+        if !confirm {
+            anyhow::bail!("Confirmation requise pour le rechiffrement (--confirm)");
+        }
+
+        match self.send_request(IpcRequest::Rekey {}).await? {
+            IpcResponse::Rekey(result) => {
+                println!(
+                    "Rechiffrement effectué : {} secrets sous la KEK {}",
+                    result.secrets_rewrapped, result.kek_id
+                );
+            }
+            other => Self::print_json(&other)?,
+        }
+        Ok(())
+    }
+
+    async fn cmd_reseal(&self, suspend_pcr_binding: bool, confirm: bool) -> Result<()> {
+        if !confirm {
+            anyhow::bail!("Confirmation requise pour le rescellement (--confirm)");
+        }
+
+        match self.send_request(IpcRequest::Reseal { suspend_pcr_binding }).await? {
+            IpcResponse::Reseal(result) if result.pcr_binding_suspended => {
+                println!(
+                    "{} secrets rescellés sans politique PCR. Après la mise à jour et le redémarrage, relancez `reseal --confirm`.",
+                    result.secrets_resealed
+                );
+            }
+            IpcResponse::Reseal(result) => {
+                println!("{} secrets rescellés sur l'état PCR courant", result.secrets_resealed);
+            }
+            other => Self::print_json(&other)?,
+        }
+        Ok(())
+    }
+
+    async fn cmd_register_app(&self, app_id: String) -> Result<()> {
+        match self.send_request(IpcRequest::RegisterApp { app_id }).await? {
+            IpcResponse::RegisterApp(result) => {
+                println!("Application {} enregistrée. Clé (à transmettre à l'application) :", result.app_id);
+                println!("{}", result.key);
+            }
+            other => Self::print_json(&other)?,
+        }
+        Ok(())
+    }
+
+    /// Affiche les données d'une réponse en JSON
+    fn print_json(response: &IpcResponse) -> Result<()> {
+        let value = serde_json::to_value(response)?;
+        let data = value.get("data").cloned().unwrap_or(serde_json::Value::Null);
+        println!("{}", serde_json::to_string_pretty(&data)?);
+        Ok(())
+    }
+
+    async fn send_request(&self, request: IpcRequest) -> Result<IpcResponse> {
         let mut stream = UnixStream::connect(&self.socket).await?;
+        let auth = self.admin_credential(&mut stream, &request).await?;
+        Self::exchange(&mut stream, request, auth).await
+    }
+
+    async fn exchange(stream: &mut UnixStream, request: IpcRequest, auth: Option<AdminCredential>) -> Result<IpcResponse> {
+        let envelope = RequestEnvelope {
+            version: PROTOCOL_VERSION,
+            id: None,
+            request,
+            auth,
+        };
         
-        let request = serde_json::json!({
-            "command": command,
-            "data": data
-        });
-        
-        let request_bytes = serde_json::to_vec(&request)?;
-        stream.write_all(&(request_bytes.len() as u32).to_be_bytes()).await?;
-        stream.write_all(&request_bytes).await?;
+        let request_bytes = serde_json::to_vec(&envelope)?;
+        write_frame(&mut *stream, &request_bytes).await?;
         
         // Lire réponse
-        let mut len_bytes = [0u8; 4];
-        stream.read_exact(&mut len_bytes).await?;
-        let len = u32::from_be_bytes(len_bytes) as usize;
-        
-        let mut response_bytes = vec![0u8; len];
-        stream.read_exact(&mut response_bytes).await?;
-        
-        let response: serde_json::Value = serde_json::from_slice(&response_bytes)?;
-        Ok(response)
+        let response_bytes = read_frame(&mut *stream).await?;
+        let response: ResponseEnvelope = serde_json::from_slice(&response_bytes)?;
+
+        response
+            .into_result()
+            .map_err(|e| anyhow::anyhow!("Erreur agent ({:?}): {}", e.code, e.message))
     }
 }
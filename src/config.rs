@@ -1,10 +1,28 @@
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
+use std::ffi::OsString;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::path::{Path, PathBuf};
 
-const DEFAULT_CONFIG_PATH: &str = "/etc/license-agent/config.toml";
-const DEFAULT_STATE_PATH: &str = "/var/lib/license-agent/state.json";
-const DEFAULT_AUDIT_LOG_PATH: &str = "/var/log/license-agent/audit.log";
+pub const DEFAULT_CONFIG_PATH: &str = "/etc/license-agent/config.toml";
+const DEFAULT_STATE_DIR: &str = "/var/lib/license-agent";
+const DEFAULT_LOG_DIR: &str = "/var/log/license-agent";
+const DEFAULT_RUNTIME_DIR: &str = "/run/license-agent";
+
+const STATE_FILE_NAME: &str = "state.json";
+const AUDIT_LOG_FILE_NAME: &str = "audit.log";
+const SECRET_STORE_DIR_NAME: &str = "secrets";
+const KEK_FILE_NAME: &str = "kek.json";
+const APP_KEYS_DIR_NAME: &str = "apps";
+const SOCKET_FILE_NAME: &str = "license-agent.sock";
+
+/// Socket IPC par défaut (`<runtime_dir>/license-agent.sock`), repris par les clients
+pub const DEFAULT_SOCKET_PATH: &str = "/run/license-agent/license-agent.sock";
+
+/// Répertoires fournis par systemd (`StateDirectory=`, `LogsDirectory=`, `RuntimeDirectory=`)
+const STATE_DIRECTORY_ENV: &str = "STATE_DIRECTORY";
+const LOGS_DIRECTORY_ENV: &str = "LOGS_DIRECTORY";
+const RUNTIME_DIRECTORY_ENV: &str = "RUNTIME_DIRECTORY";
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
@@ -13,6 +31,8 @@ pub struct Config {
     pub tpm: TpmConfig,
     pub management: ManagementConfig,
     pub degraded_mode: DegradedModeConfig,
+    #[serde(default)]
+    pub paths: PathsConfig,
     
     #[serde(skip)]
     config_path: PathBuf,
@@ -24,6 +44,10 @@ pub struct ServerConfig {
     pub cert_pin: String,
     pub client_cert: PathBuf,
     pub client_key: PathBuf,
+    /// Clé publique RSA du serveur (PEM), utilisée pour vérifier les réponses de rotation
+    pub public_key: Option<PathBuf>,
+    /// Bundle de CA privées (PEM) remplaçant les racines WebPKI
+    pub ca_bundle: Option<PathBuf>,
     pub timeout_seconds: Option<u64>,
 }
 
@@ -35,20 +59,154 @@ pub struct AgentConfig {
     #[serde(default = "default_grace_period")]
     pub grace_period: u64,
     pub rotation_threshold_seconds: Option<u64>,
+    /// Refuse le démarrage avec une graine de clé par défaut (sinon simple avertissement)
+    #[serde(default = "default_production_mode")]
+    pub production_mode: bool,
+    /// Accepte les tokens de licence v1 (en-tête non authentifié)
+    #[serde(default)]
+    pub accept_v1_tokens: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TpmConfig {
     pub enabled: bool,
+    /// Répertoire des secrets scellés quand le TPM est indisponible
     pub fallback_encrypted_storage: Option<PathBuf>,
+    /// Fichier de la KEK enveloppée (chiffrement logiciel)
+    pub kek_path: Option<PathBuf>,
+    /// Fichier contenant la graine de la clé d'enveloppe de la KEK
+    pub kek_seed_file: Option<PathBuf>,
+    /// Liaison des secrets scellés à l'état de démarrage (PCR)
+    pub pcr_policy: Option<PcrPolicyConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PcrPolicyConfig {
+    /// Banque PCR : "sha1", "sha256" ou "sha384"
+    #[serde(default = "default_pcr_bank")]
+    pub bank: String,
+    /// Index des PCR (0 à 23)
+    pub pcrs: Vec<u8>,
+}
+
+impl std::fmt::Display for PcrPolicyConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let pcrs: Vec<String> = self.pcrs.iter().map(u8::to_string).collect();
+        write!(f, "{}:{}", self.bank, pcrs.join(","))
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ManagementConfig {
+    /// UID des applications autorisées à se connecter (validation), sans `roles`
     pub allowed_uids: Vec<u32>,
+    /// UID autorisés à envoyer des commandes d'administration (défaut : root), sans `roles`
+    #[serde(default = "default_admin_uids")]
+    pub admin_uids: Vec<u32>,
+    /// Rôles des clients IPC ; remplace `allowed_uids` et `admin_uids`
+    pub roles: Option<RolesConfig>,
+    /// Empreintes des exécutables autorisés à valider des licences
+    pub executable_allowlist: Option<ExecutableAllowlistConfig>,
+    /// Répertoire des clés des applications enregistrées
+    pub app_keys_dir: Option<PathBuf>,
+    /// Empreintes SHA-256 (hexadécimal) des jetons d'administration
+    #[serde(default)]
+    pub admin_token_sha256: Vec<String>,
+    /// Certificats (PEM, RSA) dont la clé peut signer les challenges d'administration
+    #[serde(default)]
+    pub admin_certificates: Vec<PathBuf>,
     pub ipc_socket_path: Option<PathBuf>,
     pub api_port: Option<u16>,
+    /// Adresse d'écoute de l'API HTTP (défaut 127.0.0.1)
+    pub api_bind_address: Option<IpAddr>,
+    /// Empreintes SHA-256 (hexadécimal) des jetons bearer de l'API de gestion (rôle `operator`)
+    #[serde(default)]
+    pub api_token_sha256: Vec<String>,
+    /// Rôles des jetons et certificats clients de l'API de gestion
+    pub api_roles: Option<ApiRolesConfig>,
+    /// Délai de traitement d'une requête de l'API de gestion (secondes)
+    pub api_request_timeout_seconds: Option<u64>,
+    /// Délai de la poignée de main TLS et de la lecture des en-têtes d'une requête (secondes)
+    pub api_handshake_timeout_seconds: Option<u64>,
+    /// Nombre maximal de connexions HTTP simultanées
+    pub api_max_connections: Option<usize>,
+    /// Certificat et clé de l'API HTTP (HTTPS si renseignés)
+    pub api_tls_cert: Option<PathBuf>,
+    pub api_tls_key: Option<PathBuf>,
+    /// CA des certificats clients acceptés par l'API de gestion (mTLS)
+    pub api_client_ca: Option<PathBuf>,
+    /// Validations par minute et par processus client (illimité si absent)
     pub rate_limit_requests_per_minute: Option<u64>,
+    /// Commandes d'administration par minute et par processus (défaut 30)
+    pub admin_rate_limit_requests_per_minute: Option<u64>,
+    /// Fermeture d'une connexion IPC inactive (secondes)
+    pub ipc_idle_timeout_seconds: Option<u64>,
+    /// Délai de réception d'une requête commencée (secondes)
+    pub ipc_frame_timeout_seconds: Option<u64>,
+    /// Nombre maximal de requêtes pipelinées en cours par connexion
+    pub ipc_max_in_flight: Option<usize>,
+    /// Nombre maximal de requêtes par connexion (illimité si absent)
+    pub ipc_max_requests_per_connection: Option<u64>,
+    /// Nombre maximal de connexions IPC simultanées (défaut 256)
+    pub ipc_max_connections: Option<usize>,
+}
+
+/// Section `[management.roles]`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RolesConfig {
+    /// Validation de licence
+    #[serde(default)]
+    pub app: RoleBinding,
+    /// Statut, journaux, métriques, rotation
+    #[serde(default)]
+    pub operator: RoleBinding,
+    /// Invalidation, réinitialisation, mode dégradé, KEK et TPM
+    #[serde(default)]
+    pub admin: RoleBinding,
+}
+
+/// Clients auxquels un rôle est attribué : UID, GID ou exécutable
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RoleBinding {
+    #[serde(default)]
+    pub uids: Vec<u32>,
+    #[serde(default)]
+    pub gids: Vec<u32>,
+    /// Chemins absolus des exécutables (`/proc/<pid>/exe`)
+    #[serde(default)]
+    pub executables: Vec<PathBuf>,
+}
+
+/// Section `[management.api_roles]`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ApiRolesConfig {
+    #[serde(default)]
+    pub operator: ApiRoleBinding,
+    #[serde(default)]
+    pub admin: ApiRoleBinding,
+}
+
+/// Appelants de l'API auxquels un rôle est attribué
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ApiRoleBinding {
+    /// Empreintes SHA-256 (hexadécimal) des jetons bearer
+    #[serde(default)]
+    pub token_sha256: Vec<String>,
+    /// Empreintes SHA-256 (hexadécimal) des certificats clients (DER)
+    #[serde(default)]
+    pub certificate_sha256: Vec<String>,
+}
+
+/// Section `[management.executable_allowlist]`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ExecutableAllowlistConfig {
+    /// Empreintes SHA-256 (hexadécimal) des exécutables autorisés
+    #[serde(default)]
+    pub sha256: Vec<String>,
+    /// Répertoire des manifestes signés (`*.json` et `*.json.sig`)
+    pub manifest_dir: Option<PathBuf>,
+    /// Clé publique RSA de signature des manifestes
+    pub manifest_public_key: Option<PathBuf>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -60,6 +218,45 @@ pub struct DegradedModeConfig {
     pub alert_thresholds_hours: Vec<u64>,
 }
 
+/// Section `[paths]` : emplacements des fichiers de l'agent
+///
+/// Les valeurs absentes sont dérivées de `state_dir`, `runtime_dir` et des
+/// répertoires fournis par systemd, puis des emplacements par défaut.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PathsConfig {
+    /// État, KEK, clés des applications et secrets scellés (défaut `/var/lib/license-agent`)
+    pub state_dir: Option<PathBuf>,
+    /// Journal d'audit (défaut `/var/log/license-agent/audit.log`)
+    pub audit_log: Option<PathBuf>,
+    /// Secrets scellés hors TPM (défaut `<state_dir>/secrets`)
+    pub secret_store: Option<PathBuf>,
+    /// Socket IPC (défaut `<runtime_dir>/license-agent.sock`)
+    pub socket: Option<PathBuf>,
+    /// Répertoire d'exécution (défaut `/run/license-agent`, celui de `RuntimeDirectory=`)
+    pub runtime_dir: Option<PathBuf>,
+}
+
+impl PathsConfig {
+    /// Complète les répertoires absents avec `STATE_DIRECTORY`, `LOGS_DIRECTORY` et `RUNTIME_DIRECTORY`
+    ///
+    /// systemd peut y lister plusieurs répertoires séparés par `:` : le premier est retenu.
+    pub fn apply_systemd_directories(&mut self, lookup: impl Fn(&str) -> Option<OsString>) {
+        let directory = |name: &str| {
+            lookup(name).and_then(|value| std::env::split_paths(&value).find(|dir| !dir.as_os_str().is_empty()))
+        };
+
+        if self.state_dir.is_none() {
+            self.state_dir = directory(STATE_DIRECTORY_ENV);
+        }
+        if self.audit_log.is_none() {
+            self.audit_log = directory(LOGS_DIRECTORY_ENV).map(|dir| dir.join(AUDIT_LOG_FILE_NAME));
+        }
+        if self.runtime_dir.is_none() {
+            self.runtime_dir = directory(RUNTIME_DIRECTORY_ENV);
+        }
+    }
+}
+
 impl Config {
     pub fn load() -> Result<Self> {
         Self::load_from_path(DEFAULT_CONFIG_PATH)
@@ -74,6 +271,7 @@ impl Config {
             .with_context(|| format!("Failed to parse config from {}", path.display()))?;
         
         config.config_path = path.to_path_buf();
+        config.paths.apply_systemd_directories(|name| std::env::var_os(name));
         
         // Validation
         config.validate()?;
@@ -85,19 +283,70 @@ impl Config {
         &self.config_path
     }
 
+    /// Répertoire d'état (`paths.state_dir`, `STATE_DIRECTORY` ou défaut)
+    pub fn state_dir(&self) -> PathBuf {
+        self.paths
+            .state_dir
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_STATE_DIR))
+    }
+
     pub fn state_path(&self) -> PathBuf {
-        PathBuf::from(DEFAULT_STATE_PATH)
+        self.state_dir().join(STATE_FILE_NAME)
     }
 
     pub fn audit_log_path(&self) -> PathBuf {
-        PathBuf::from(DEFAULT_AUDIT_LOG_PATH)
+        self.paths
+            .audit_log
+            .clone()
+            .unwrap_or_else(|| Path::new(DEFAULT_LOG_DIR).join(AUDIT_LOG_FILE_NAME))
     }
 
-    pub fn ipc_socket_path(&self) -> PathBuf {
+    pub fn secret_store_path(&self) -> PathBuf {
+        self.paths
+            .secret_store
+            .clone()
+            .or_else(|| self.tpm.fallback_encrypted_storage.clone())
+            .unwrap_or_else(|| self.state_dir().join(SECRET_STORE_DIR_NAME))
+    }
+
+    pub fn kek_path(&self) -> PathBuf {
+        self.tpm
+            .kek_path
+            .clone()
+            .unwrap_or_else(|| self.state_dir().join(KEK_FILE_NAME))
+    }
+
+    pub fn app_keys_path(&self) -> PathBuf {
         self.management
-            .ipc_socket_path
+            .app_keys_dir
             .clone()
-            .unwrap_or_else(|| PathBuf::from("/var/run/license-agent.sock"))
+            .unwrap_or_else(|| self.state_dir().join(APP_KEYS_DIR_NAME))
+    }
+
+    /// Répertoire d'exécution (`paths.runtime_dir`, `RUNTIME_DIRECTORY` ou défaut)
+    pub fn runtime_dir(&self) -> PathBuf {
+        self.paths
+            .runtime_dir
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_RUNTIME_DIR))
+    }
+
+    pub fn ipc_socket_path(&self) -> PathBuf {
+        self.paths
+            .socket
+            .clone()
+            .or_else(|| self.management.ipc_socket_path.clone())
+            .unwrap_or_else(|| self.runtime_dir().join(SOCKET_FILE_NAME))
+    }
+
+    /// Adresse d'écoute de l'API HTTP (`None` si `api_port` est absent)
+    pub fn api_listen_addr(&self) -> Option<SocketAddr> {
+        let address = self
+            .management
+            .api_bind_address
+            .unwrap_or(IpAddr::V4(Ipv4Addr::LOCALHOST));
+        self.management.api_port.map(|port| SocketAddr::new(address, port))
     }
 
     fn validate(&self) -> Result<()> {
@@ -120,6 +369,33 @@ impl Config {
             anyhow::bail!("Client key not found: {}", self.server.client_key.display());
         }
 
+        if let Some(ca_bundle) = &self.server.ca_bundle {
+            if !ca_bundle.exists() {
+                anyhow::bail!("CA bundle not found: {}", ca_bundle.display());
+            }
+        }
+
+        crate::tls::parse_cert_pins(&self.server.cert_pin)
+            .context("Invalid server.cert_pin")?;
+
+        match &self.server.public_key {
+            Some(path) if !path.exists() => {
+                anyhow::bail!("Server public key not found: {}", path.display());
+            }
+            Some(_) => {}
+            None => {
+                tracing::warn!("⚠️  server.public_key not set - rotations will be rejected until the server key is pinned");
+            }
+        }
+
+        if self.paths.secret_store.is_some() && self.tpm.fallback_encrypted_storage.is_some() {
+            anyhow::bail!("paths.secret_store and tpm.fallback_encrypted_storage are mutually exclusive");
+        }
+
+        if self.paths.socket.is_some() && self.management.ipc_socket_path.is_some() {
+            anyhow::bail!("paths.socket and management.ipc_socket_path are mutually exclusive");
+        }
+
         // Validation intervalles
         if self.agent.rotation_interval == 0 {
             anyhow::bail!("Rotation interval must be > 0");
@@ -129,6 +405,120 @@ impl Config {
             anyhow::bail!("Grace period must be > 0");
         }
 
+        if let Some(address) = self.management.api_bind_address {
+            if !address.is_loopback() {
+                tracing::warn!("⚠️  management.api_bind_address {} is not a loopback address - metrics are exposed to the network", address);
+            }
+        }
+
+        match (&self.management.api_tls_cert, &self.management.api_tls_key) {
+            (Some(cert), Some(key)) => {
+                for path in [cert, key] {
+                    if !path.exists() {
+                        anyhow::bail!("API TLS file not found: {}", path.display());
+                    }
+                }
+            }
+            (None, None) => {
+                if self.management.api_client_ca.is_some() {
+                    anyhow::bail!("management.api_client_ca requires api_tls_cert and api_tls_key");
+                }
+            }
+            _ => anyhow::bail!("management.api_tls_cert and api_tls_key must be set together"),
+        }
+
+        if let Some(ca) = &self.management.api_client_ca {
+            if !ca.exists() {
+                anyhow::bail!("API client CA not found: {}", ca.display());
+            }
+        }
+
+        if let Some(hash) = self
+            .management
+            .api_token_sha256
+            .iter()
+            .find(|hash| !hex::decode(hash).is_ok_and(|bytes| bytes.len() == 32))
+        {
+            anyhow::bail!("Invalid management.api_token_sha256 entry: {}", hash);
+        }
+
+        if let Some(roles) = &self.management.api_roles {
+            for (name, binding) in [("operator", &roles.operator), ("admin", &roles.admin)] {
+                if let Some(hash) = binding
+                    .token_sha256
+                    .iter()
+                    .chain(&binding.certificate_sha256)
+                    .find(|hash| !hex::decode(hash).is_ok_and(|bytes| bytes.len() == 32))
+                {
+                    anyhow::bail!("Invalid management.api_roles.{} entry: {}", name, hash);
+                }
+            }
+        }
+
+        if let Some(hash) = self
+            .management
+            .admin_token_sha256
+            .iter()
+            .find(|hash| !hex::decode(hash).is_ok_and(|bytes| bytes.len() == 32))
+        {
+            anyhow::bail!("Invalid management.admin_token_sha256 entry: {}", hash);
+        }
+
+        if let Some(cert) = self.management.admin_certificates.iter().find(|cert| !cert.exists()) {
+            anyhow::bail!("Admin certificate not found: {}", cert.display());
+        }
+
+        if self.management.admin_token_sha256.is_empty() && self.management.admin_certificates.is_empty() {
+            tracing::warn!("⚠️  No admin token or certificate configured - administration commands over IPC are disabled");
+        }
+
+        if let Some(roles) = &self.management.roles {
+            if let Some(path) = [&roles.app, &roles.operator, &roles.admin]
+                .iter()
+                .flat_map(|binding| &binding.executables)
+                .find(|path| !path.is_absolute())
+            {
+                anyhow::bail!("management.roles executables must be absolute paths: {}", path.display());
+            }
+        }
+
+        if let Some(allowlist) = &self.management.executable_allowlist {
+            if let Some(hash) = allowlist
+                .sha256
+                .iter()
+                .find(|hash| !hex::decode(hash).is_ok_and(|bytes| bytes.len() == 32))
+            {
+                anyhow::bail!("Invalid management.executable_allowlist.sha256 entry: {}", hash);
+            }
+            match (&allowlist.manifest_dir, &allowlist.manifest_public_key) {
+                (Some(_), Some(key)) if !key.exists() => {
+                    anyhow::bail!("Manifest public key not found: {}", key.display());
+                }
+                (Some(_), Some(_)) | (None, None) => {}
+                _ => anyhow::bail!(
+                    "management.executable_allowlist.manifest_dir and manifest_public_key must be set together"
+                ),
+            }
+        }
+
+        if let Some(policy) = &self.tpm.pcr_policy {
+            if policy.pcrs.is_empty() {
+                anyhow::bail!("tpm.pcr_policy.pcrs must not be empty");
+            }
+            if let Some(pcr) = policy.pcrs.iter().find(|pcr| **pcr > 23) {
+                anyhow::bail!("Invalid PCR index in tpm.pcr_policy: {}", pcr);
+            }
+            if !matches!(policy.bank.as_str(), "sha1" | "sha256" | "sha384") {
+                anyhow::bail!("Unsupported PCR bank in tpm.pcr_policy: {}", policy.bank);
+            }
+        }
+
+        if let Some(seed_file) = &self.tpm.kek_seed_file {
+            if !seed_file.exists() {
+                anyhow::bail!("Key seed file not found: {}", seed_file.display());
+            }
+        }
+
         Ok(())
     }
 }
@@ -141,6 +531,18 @@ fn default_grace_period() -> u64 {
     604800 // 7 jours
 }
 
+fn default_admin_uids() -> Vec<u32> {
+    vec![0]
+}
+
+fn default_pcr_bank() -> String {
+    "sha256".to_string()
+}
+
+fn default_production_mode() -> bool {
+    false
+}
+
 impl Default for ServerConfig {
     fn default() -> Self {
         Self {
@@ -148,6 +550,8 @@ impl Default for ServerConfig {
             cert_pin: String::new(),
             client_cert: PathBuf::from("/etc/license-agent/client.pem"),
             client_key: PathBuf::from("/etc/license-agent/client.key"),
+            public_key: Some(PathBuf::from("/etc/license-agent/server_public_key.pem")),
+            ca_bundle: None,
             timeout_seconds: Some(30),
         }
     }
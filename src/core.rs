@@ -1,16 +1,29 @@
-use crate::audit::AuditLogger;
+use crate::admin::AdminAuthenticator;
+use crate::api::ApiServer;
+use crate::app_auth::{compute_response_mac, AppKeyStore};
+use crate::audit::{AuditEvent, AuditLogger};
 use crate::config::Config;
 use crate::crypto::CryptoManager;
-use crate::ipc::IpcServer;
+use crate::executable::ExecutableVerifier;
+use crate::ipc::{ConnectionLimits, IpcServer};
+use crate::kek::KeyHierarchy;
 use crate::license::LicenseValidator;
+use crate::metrics::{create_metrics, Metrics};
+use crate::protocol::LogsRequest;
+use crate::ratelimit::IpcRateLimiter;
+use crate::roles::RoleResolver;
 use crate::rotation::RotationManager;
 use crate::secret::SecretManager;
 use crate::tpm::TpmManager;
-use crate::types::{AgentResult, DegradedModeStatus, SystemStatus};
+use crate::types::{
+    AgentError, AgentResult, DegradedModeStatus, SystemStatus, TpmStatus, ValidationResult,
+};
 use chrono::{DateTime, Utc};
+use prometheus::Registry;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use tracing::{debug, error, info, warn};
+use zeroize::Zeroizing;
 
 /// Moteur principal
 pub struct CoreEngine {
@@ -19,7 +32,10 @@ pub struct CoreEngine {
     secret_manager: Arc<SecretManager>,
     validator: Arc<LicenseValidator>,
     rotation_manager: Arc<RotationManager>,
+    app_keys: AppKeyStore,
     audit: Arc<AuditLogger>,
+    registry: Arc<Registry>,
+    metrics: Arc<Metrics>,
     degraded_mode: Arc<RwLock<DegradedModeState>>,
     shutdown: Arc<tokio::sync::Notify>,
 }
@@ -34,14 +50,27 @@ impl CoreEngine {
     pub async fn new(config: Config) -> anyhow::Result<Self> {
         let config = Arc::new(config);
 
+        // Initialiser hiérarchie de clés (refus de la graine par défaut en production)
+        let kek = Arc::new(KeyHierarchy::from_config(&config)?);
+        info!("Key hierarchy initialized (KEK {})", hex::encode(kek.current_id()));
+
         // Initialiser TPM
-        let tpm = Arc::new(TpmManager::new(config.tpm.enabled)?);
+        let tpm = Arc::new(TpmManager::new(
+            config.tpm.enabled,
+            kek,
+            config.tpm.pcr_policy.clone(),
+        )?);
         info!("TPM manager initialized (available: {})", tpm.is_available());
 
+        // Initialiser métriques
+        let (registry, metrics) = create_metrics()?;
+        metrics.update_tpm_status(tpm.is_available());
+
         // Initialiser Secret Manager
         let secret_manager = Arc::new(SecretManager::new(
             Arc::clone(&tpm),
             config.state_path(),
+            config.secret_store_path(),
         ));
 
         // Charger état
@@ -50,9 +79,18 @@ impl CoreEngine {
 
         // Initialiser Audit Logger
         let audit = Arc::new(AuditLogger::new(&config).await?);
+        if let Some(tamper) = secret_manager.tamper_status() {
+            audit.state_tamper_response(&tamper).await;
+        }
+        if let Some(reason) = secret_manager.state_key_failure() {
+            audit.state_key_unavailable(&reason).await;
+        }
 
         // Initialiser License Validator
-        let validator = Arc::new(LicenseValidator::new(Arc::clone(&secret_manager)));
+        let validator = Arc::new(
+            LicenseValidator::new(Arc::clone(&secret_manager), config.agent.accept_v1_tokens)
+                .with_metrics(Arc::clone(&metrics)),
+        );
 
         // Initialiser Crypto Manager
         // Charger ou générer clés RSA agent
@@ -70,12 +108,18 @@ impl CoreEngine {
         );
 
         // Initialiser Rotation Manager
-        let rotation_manager = Arc::new(RotationManager::new(
-            Arc::clone(&config),
-            Arc::clone(&secret_manager),
-            Arc::clone(&audit),
-            Arc::clone(&crypto),
-        )?);
+        let rotation_manager = Arc::new(
+            RotationManager::new(
+                Arc::clone(&config),
+                Arc::clone(&secret_manager),
+                Arc::clone(&audit),
+                Arc::clone(&crypto),
+            )?
+            .with_metrics(Arc::clone(&metrics)),
+        );
+
+        // Clés des applications enregistrées (MAC des réponses de validation)
+        let app_keys = AppKeyStore::new(config.app_keys_path());
 
         // État mode dégradé
         let degraded_mode = Arc::new(RwLock::new(DegradedModeState {
@@ -90,14 +134,38 @@ impl CoreEngine {
             secret_manager,
             validator,
             rotation_manager,
+            app_keys,
             audit,
+            registry,
+            metrics,
             degraded_mode,
             shutdown: Arc::new(tokio::sync::Notify::new()),
         })
     }
 
+    /// Serveur IPC de l'agent : rôles, authentification, limites de connexion et de débit
+    pub async fn ipc_server(self: &Arc<Self>) -> anyhow::Result<IpcServer> {
+        let mut ipc_server = IpcServer::new(
+            self.config.ipc_socket_path(),
+            Arc::clone(self),
+            Arc::new(RoleResolver::from_config(&self.config.management)),
+            Arc::new(AdminAuthenticator::from_config(&self.config.management)?),
+            ConnectionLimits::from_config(&self.config.management),
+        )
+        .await?
+        .with_rate_limiter(Arc::new(
+            IpcRateLimiter::from_config(&self.config.management)
+                .with_audit(Arc::clone(&self.audit))
+                .with_metrics(Arc::clone(&self.metrics)),
+        ));
+        if let Some(allowlist) = &self.config.management.executable_allowlist {
+            ipc_server = ipc_server.with_executable_verifier(Arc::new(ExecutableVerifier::from_config(allowlist)?));
+        }
+        Ok(ipc_server)
+    }
+
     /// Démarre le moteur
-    pub async fn start(&self) -> anyhow::Result<()> {
+    pub async fn start(self: &Arc<Self>) -> anyhow::Result<()> {
         info!("Starting core engine...");
 
         // Vérifier si rotation nécessaire au démarrage
@@ -113,14 +181,7 @@ impl CoreEngine {
         }
 
         // Démarrer serveur IPC
-        let ipc_server = Arc::new(
-            IpcServer::new(
-                self.config.ipc_socket_path(),
-                Arc::clone(&self.validator),
-                self.config.management.allowed_uids.clone(),
-            )
-            .await?,
-        );
+        let ipc_server = Arc::new(self.ipc_server().await?);
 
         // Démarrer serveur IPC en arrière-plan
         let ipc_server_clone = Arc::clone(&ipc_server);
@@ -130,6 +191,17 @@ impl CoreEngine {
             }
         });
 
+        // Démarrer l'API HTTP (métriques, santé, gestion) si un port est configuré
+        if let Some(addr) = self.config.api_listen_addr() {
+            let api_server = ApiServer::bind(&self.config, addr, Arc::clone(self)).await?;
+            let shutdown = Arc::clone(&self.shutdown);
+            tokio::spawn(async move {
+                if let Err(e) = api_server.run(shutdown).await {
+                    error!("HTTP API error: {}", e);
+                }
+            });
+        }
+
         // Démarrer tâches périodiques
         self.start_periodic_tasks();
 
@@ -144,11 +216,13 @@ impl CoreEngine {
         let degraded_mode = Arc::clone(&self.degraded_mode);
         let audit = Arc::clone(&self.audit);
         let config = Arc::clone(&self.config);
+        let metrics = Arc::clone(&self.metrics);
         let shutdown = Arc::clone(&self.shutdown);
 
         // Tâche de rotation périodique
         let secret_manager_clone = Arc::clone(&secret_manager);
         let degraded_mode_clone = Arc::clone(&degraded_mode);
+        let metrics_rotation = Arc::clone(&metrics);
         let config_rotation = Arc::clone(&config);
         let shutdown_rotation = Arc::clone(&shutdown);
         tokio::spawn(async move {
@@ -168,6 +242,7 @@ impl CoreEngine {
                                         state.activated_at = Some(Utc::now());
                                         let grace_period_days = config_rotation.degraded_mode.grace_period_days;
                                         state.grace_period_end = Some(Utc::now() + chrono::Duration::days(grace_period_days as i64));
+                                        metrics_rotation.update_degraded_mode(true, None);
                                     }
                                 }
                             }
@@ -191,6 +266,10 @@ impl CoreEngine {
                         if let Err(e) = secret_manager.cleanup_expired().await {
                             warn!("Cleanup failed: {}", e);
                         }
+                        // Sauvegarde périodique des statistiques de validation
+                        if let Err(e) = secret_manager.save_state().await {
+                            warn!("Failed to save state: {}", e);
+                        }
                     }
                     _ = shutdown_cleanup.notified() => {
                         break;
@@ -204,6 +283,7 @@ impl CoreEngine {
         let audit_clone = Arc::clone(&audit);
         let degraded_mode_retry = Arc::clone(&degraded_mode);
         let config_retry = Arc::clone(&config);
+        let metrics_retry = Arc::clone(&metrics);
         let shutdown_retry = Arc::clone(&shutdown);
         tokio::spawn(async move {
             let retry_interval = tokio::time::Duration::from_secs(
@@ -231,6 +311,10 @@ impl CoreEngine {
                                 } else {
                                     // Rotation réussie, désactiver mode dégradé
                                     let mut state = degraded_mode_retry.write().await;
+                                    let duration_seconds = state
+                                        .activated_at
+                                        .map(|a| Utc::now().signed_duration_since(a).num_seconds() as f64);
+                                    metrics_retry.update_degraded_mode(false, duration_seconds);
                                     state.active = false;
                                     state.activated_at = None;
                                     state.grace_period_end = None;
@@ -289,7 +373,8 @@ impl CoreEngine {
         });
     }
 
-    async fn activate_degraded_mode(&self, reason: &str) {
+    /// Active le mode dégradé
+    pub async fn activate_degraded_mode(&self, reason: &str) {
         let mut state = self.degraded_mode.write().await;
         if !state.active {
             state.active = true;
@@ -299,11 +384,194 @@ impl CoreEngine {
             let grace_period_days = self.config.degraded_mode.grace_period_days;
             state.grace_period_end = Some(Utc::now() + chrono::Duration::days(grace_period_days as i64));
             
+            self.metrics.update_degraded_mode(true, None);
             self.audit.degraded_mode_activated(reason).await;
             warn!("Degraded mode activated: {}", reason);
         }
     }
 
+    /// Désactive le mode dégradé
+    pub async fn deactivate_degraded_mode(&self) {
+        let mut state = self.degraded_mode.write().await;
+        if state.active {
+            let duration_seconds = state
+                .activated_at
+                .map(|a| Utc::now().signed_duration_since(a).num_seconds())
+                .unwrap_or(0);
+
+            state.active = false;
+            state.activated_at = None;
+            state.grace_period_end = None;
+
+            self.metrics.update_degraded_mode(false, Some(duration_seconds as f64));
+            self.audit.degraded_mode_deactivated(duration_seconds).await;
+            info!("Degraded mode deactivated after {}s", duration_seconds);
+        }
+    }
+
+    /// Valide un token de licence
+    pub async fn validate(&self, license_token: &[u8]) -> AgentResult<ValidationResult> {
+        self.validator.validate(license_token).await
+    }
+
+    /// MAC d'une réponse de validation avec la clé de l'application `app_id`
+    pub async fn validation_mac(
+        &self,
+        app_id: &str,
+        nonce: &[u8; 16],
+        license_token: &[u8],
+        result: &ValidationResult,
+    ) -> AgentResult<String> {
+        let key = self.app_keys.key(app_id).await?;
+        compute_response_mac(&key, nonce, license_token, result)
+    }
+
+    /// Enregistre une application et retourne sa nouvelle clé
+    pub async fn register_app(&self, app_id: &str) -> AgentResult<Zeroizing<Vec<u8>>> {
+        self.app_keys.register(app_id).await
+    }
+
+    /// Déclenche une rotation (si nécessaire, ou toujours si `force`)
+    ///
+    /// Retourne `false` si aucune rotation n'était nécessaire.
+    pub async fn rotate(&self, force: bool) -> AgentResult<bool> {
+        if !force && !self.rotation_manager.check_rotation_needed().await {
+            debug!("Rotation not needed, skipping");
+            return Ok(false);
+        }
+
+        self.rotation_manager.rotate(force).await?;
+        Ok(true)
+    }
+
+    /// Invalide un secret
+    pub async fn invalidate(&self, version: u64, reason: Option<String>) -> AgentResult<()> {
+        self.secret_manager.invalidate(version, reason.clone()).await?;
+        self.audit.secret_invalidated(version, reason.as_deref()).await;
+        Ok(())
+    }
+
+    /// Réinitialise complètement le système (suppression de tous les secrets)
+    pub async fn reset(&self) -> AgentResult<usize> {
+        let tamper_cleared = self.secret_manager.tamper_status().is_some();
+        let removed = self.secret_manager.reset().await?;
+        self.audit.critical(
+            "system_reset",
+            serde_json::json!({ "secrets_removed": removed, "tamper_response_cleared": tamper_cleared })
+        ).await;
+        Ok(removed)
+    }
+
+    /// Rechiffre tous les secrets sous une nouvelle KEK
+    pub async fn rekey(&self) -> AgentResult<usize> {
+        let rewrapped = self.secret_manager.rekey().await?;
+        self.audit.warning(
+            "kek_rotated",
+            serde_json::json!({
+                "kek_id": self.kek_id(),
+                "secrets_rewrapped": rewrapped,
+            })
+        ).await;
+        Ok(rewrapped)
+    }
+
+    /// Rescelle les secrets TPM (suspension ou rétablissement de la liaison PCR)
+    pub async fn reseal(&self, suspend_pcr_binding: bool) -> AgentResult<usize> {
+        let resealed = self.secret_manager.reseal(suspend_pcr_binding).await?;
+        let data = serde_json::json!({
+            "secrets_resealed": resealed,
+            "pcr_policy": self.tpm.pcr_policy().map(ToString::to_string),
+            "pcr_binding_suspended": suspend_pcr_binding,
+        });
+        if suspend_pcr_binding {
+            self.audit.critical("pcr_binding_suspended", data).await;
+        } else {
+            self.audit.warning("secrets_resealed", data).await;
+        }
+        Ok(resealed)
+    }
+
+    /// Identifiant (hexadécimal) de la KEK courante
+    pub fn kek_id(&self) -> String {
+        hex::encode(self.tpm.kek().current_id())
+    }
+
+    /// Lit les événements du journal d'audit
+    pub async fn audit_events(&self, request: &LogsRequest) -> AgentResult<Vec<AuditEvent>> {
+        self.audit
+            .read_events(request.tail, request.event.as_deref(), request.level, request.since)
+            .await
+            .map_err(|e| AgentError::InternalError(format!("Failed to read audit log: {}", e)))
+    }
+
+    /// Exporte les métriques au format texte Prometheus
+    pub async fn metrics_text(&self) -> AgentResult<String> {
+        use prometheus::Encoder;
+
+        self.refresh_metrics().await;
+
+        let encoder = prometheus::TextEncoder::new();
+        let mut buffer = Vec::new();
+        encoder
+            .encode(&self.registry.gather(), &mut buffer)
+            .map_err(|e| AgentError::InternalError(format!("Failed to encode metrics: {}", e)))?;
+
+        String::from_utf8(buffer)
+            .map_err(|e| AgentError::InternalError(format!("Invalid metrics encoding: {}", e)))
+    }
+
+    /// Met à jour les jauges dérivées de l'état courant
+    async fn refresh_metrics(&self) {
+        use crate::types::SecretState;
+
+        let states: Vec<SecretState> = self
+            .secret_manager
+            .list_versions()
+            .iter()
+            .filter_map(|v| self.secret_manager.get_metadata(*v).map(|m| m.state))
+            .collect();
+        let count = |state: SecretState| states.iter().filter(|s| **s == state).count();
+
+        self.metrics.update_secrets(
+            count(SecretState::Actif),
+            count(SecretState::Grace),
+            count(SecretState::Invalide),
+        );
+        self.metrics.update_tpm_status(self.tpm.is_available());
+        self.metrics.update_degraded_mode(self.degraded_mode.read().await.active, None);
+    }
+
+    /// Journal d'audit
+    pub fn audit_logger(&self) -> &Arc<AuditLogger> {
+        &self.audit
+    }
+
+    /// Obtient la version active du secret
+    pub fn active_version(&self) -> Option<u64> {
+        self.secret_manager.active_version()
+    }
+
+    /// Obtient le statut TPM
+    pub fn tpm_status(&self) -> TpmStatus {
+        self.tpm.get_status()
+    }
+
+    /// Obtient le statut du mode dégradé
+    pub async fn degraded_mode_status(&self) -> DegradedModeStatus {
+        let degraded_state = self.degraded_mode.read().await;
+        DegradedModeStatus {
+            active: degraded_state.active,
+            activated_at: degraded_state.activated_at,
+            duration_seconds: degraded_state.activated_at.map(|a| {
+                Utc::now().signed_duration_since(a).num_seconds()
+            }),
+            grace_period_end: degraded_state.grace_period_end,
+            remaining_seconds: degraded_state.grace_period_end.map(|g| {
+                g.signed_duration_since(Utc::now()).num_seconds().max(0)
+            }),
+        }
+    }
+
     /// Arrêt gracieux
     pub async fn shutdown(&self) -> anyhow::Result<()> {
         info!("Shutting down core engine...");
@@ -357,12 +625,10 @@ impl CoreEngine {
                             valid_from: m.valid_from,
                             valid_until: m.valid_until,
                             grace_until: m.grace_until,
-                            remaining_seconds: m.grace_until.and_then(|g| {
+                            remaining_seconds: m.grace_until.map(|g| {
                                 g.signed_duration_since(Utc::now())
                                     .num_seconds()
                                     .max(0)
-                                    .try_into()
-                                    .ok()
                             }),
                         })
                     } else {
@@ -372,22 +638,11 @@ impl CoreEngine {
             })
             .collect();
 
-        let degraded_state = self.degraded_mode.read().await;
-        let degraded_mode_status = DegradedModeStatus {
-            active: degraded_state.active,
-            activated_at: degraded_state.activated_at,
-            duration_seconds: degraded_state.activated_at.map(|a| {
-                Utc::now().signed_duration_since(a).num_seconds()
-            }),
-            grace_period_end: degraded_state.grace_period_end,
-            remaining_seconds: degraded_state.grace_period_end.map(|g| {
-                g.signed_duration_since(Utc::now()).num_seconds().max(0)
-            }),
-        };
+        let degraded_mode_status = self.degraded_mode_status().await;
 
-        let next_rotation = active_secret.as_ref().and_then(|s| {
-            s.valid_until.signed_duration_since(Utc::now()).num_seconds().try_into().ok()
-                .map(|secs: i64| Utc::now() + chrono::Duration::seconds(secs))
+        let next_rotation = active_secret.as_ref().map(|s| {
+            let secs = s.valid_until.signed_duration_since(Utc::now()).num_seconds();
+            Utc::now() + chrono::Duration::seconds(secs)
         });
 
         Ok(SystemStatus {
@@ -397,6 +652,7 @@ impl CoreEngine {
             license_status: self.validator.get_stats().await,
             degraded_mode: degraded_mode_status,
             next_rotation,
+            tamper_response: self.secret_manager.tamper_status(),
         })
     }
 }
@@ -97,22 +97,7 @@ impl CryptoManager {
 
     /// Vérifie une signature RSA-PSS
     pub fn verify_pss(&self, data: &[u8], signature: &[u8]) -> Result<bool, AgentError> {
-        use rsa::Pss;
-        
-        // Hasher les données
-        let mut hasher = Sha256::new();
-        hasher.update(data);
-        let hash = hasher.finalize();
-        
-        // Créer le padding PSS avec SHA-256 (salt length = hash length = 32 bytes)
-        let padding = Pss::new_with_salt::<Sha256>(32);
-        
-        // Vérifier la signature
-        // L'API attend: verify(padding, hash, signature)
-        match self.public_key.verify(padding, &hash, signature) {
-            Ok(_) => Ok(true),
-            Err(_) => Ok(false),
-        }
+        verify_pss_with_key(&self.public_key, data, signature)
     }
 
     /// Obtient la clé publique (pour export)
@@ -149,6 +134,41 @@ impl CryptoManager {
     }
 }
 
+/// Charge une clé publique RSA depuis un fichier PEM (PKCS#1 ou SPKI)
+pub fn load_public_key_pem(path: &std::path::Path) -> anyhow::Result<RsaPublicKey> {
+    use rsa::pkcs1::DecodeRsaPublicKey;
+    use rsa::pkcs8::DecodePublicKey;
+
+    let pem = std::fs::read_to_string(path)?;
+    RsaPublicKey::from_pkcs1_pem(&pem)
+        .or_else(|_| RsaPublicKey::from_public_key_pem(&pem))
+        .map_err(|e| anyhow::anyhow!("Invalid RSA public key {}: {}", path.display(), e))
+}
+
+/// Vérifie une signature RSA-PSS avec une clé publique donnée
+pub fn verify_pss_with_key(
+    public_key: &RsaPublicKey,
+    data: &[u8],
+    signature: &[u8],
+) -> Result<bool, AgentError> {
+    use rsa::Pss;
+    
+    // Hasher les données
+    let mut hasher = Sha256::new();
+    hasher.update(data);
+    let hash = hasher.finalize();
+    
+    // Créer le padding PSS avec SHA-256 (salt length = hash length = 32 bytes)
+    let padding = Pss::new_with_salt::<Sha256>(32);
+    
+    // Vérifier la signature
+    // L'API attend: verify(padding, hash, signature)
+    match public_key.verify(padding, &hash, signature) {
+        Ok(_) => Ok(true),
+        Err(_) => Ok(false),
+    }
+}
+
 /// Hash SHA-256
 pub fn sha256(data: &[u8]) -> Vec<u8> {
     let mut hasher = Sha256::new();
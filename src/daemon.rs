@@ -0,0 +1,232 @@
+use crate::config::{Config, DEFAULT_CONFIG_PATH};
+use crate::core::CoreEngine;
+use anyhow::{Context, Result};
+use clap::{Parser, ValueEnum};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::Arc;
+use tracing::{error, info, warn};
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::util::SubscriberInitExt;
+
+/// Configuration d'exemple affichée par `--print-default-config`
+const DEFAULT_CONFIG: &str = include_str!("../deploy/config.toml.example");
+
+/// Journal du démon détaché, à côté du journal d'audit
+const DAEMON_LOG_FILE_NAME: &str = "license-agent.log";
+
+/// Fonctionnalités Cargo compilées dans l'agent
+pub const BUILD_FEATURES: &[&str] = &[
+    #[cfg(feature = "tpm")]
+    "tpm",
+];
+
+#[derive(Parser)]
+#[command(name = "license-agent")]
+#[command(about = "Agent de gestion des secrets de licence")]
+#[command(disable_version_flag = true)]
+pub struct DaemonCli {
+    /// Fichier de configuration
+    #[arg(long, short = 'c', default_value = DEFAULT_CONFIG_PATH)]
+    pub config: PathBuf,
+
+    /// Valide la configuration, affiche un diagnostic et quitte (code 1 si invalide)
+    #[arg(long, conflicts_with_all = ["print_default_config", "version"])]
+    pub check_config: bool,
+
+    /// Reste au premier plan (systemd, conteneur) au lieu de se détacher du terminal
+    #[arg(long, short = 'f')]
+    pub foreground: bool,
+
+    /// Format des journaux
+    #[arg(long, value_enum, default_value_t = LogFormat::Text)]
+    pub log_format: LogFormat,
+
+    /// Affiche une configuration d'exemple et quitte
+    #[arg(long, conflicts_with = "version")]
+    pub print_default_config: bool,
+
+    /// Affiche la version et les fonctionnalités compilées
+    #[arg(long, short = 'V')]
+    pub version: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+impl DaemonCli {
+    /// Exécute l'agent ou la commande demandée
+    ///
+    /// Synchrone : le détachement (fork) doit précéder le runtime tokio.
+    pub fn run(self) -> Result<ExitCode> {
+        if self.version {
+            println!("{}", version_text());
+            return Ok(ExitCode::SUCCESS);
+        }
+        if self.print_default_config {
+            print!("{}", DEFAULT_CONFIG);
+            return Ok(ExitCode::SUCCESS);
+        }
+
+        // Avertissements de validation affichés sur le terminal, avant un éventuel détachement
+        let loaded = tracing::subscriber::with_default(self.subscriber(std::io::stderr, true), || {
+            Config::load_from_path(&self.config)
+        });
+
+        if self.check_config {
+            return Ok(match loaded {
+                Ok(config) => {
+                    print_config_diagnostic(&config);
+                    ExitCode::SUCCESS
+                }
+                Err(e) => {
+                    eprintln!("Configuration invalide ({}) : {:#}", self.config.display(), e);
+                    ExitCode::FAILURE
+                }
+            });
+        }
+        let config = loaded?;
+
+        if self.foreground {
+            self.subscriber(std::io::stdout, true).init();
+        } else {
+            let log_file = detach(&config)?;
+            self.subscriber(std::sync::Mutex::new(log_file), false).init();
+        }
+
+        tokio::runtime::Runtime::new()
+            .context("Failed to start tokio runtime")?
+            .block_on(serve(config))?;
+        Ok(ExitCode::SUCCESS)
+    }
+
+    fn subscriber<W>(&self, writer: W, ansi: bool) -> Box<dyn tracing::Subscriber + Send + Sync>
+    where
+        W: for<'a> MakeWriter<'a> + Send + Sync + 'static,
+    {
+        let builder = tracing_subscriber::fmt()
+            .with_env_filter(
+                tracing_subscriber::EnvFilter::try_from_default_env()
+                    .unwrap_or_else(|_| "license_secret_agent=info".into()),
+            )
+            .with_target(false)
+            .with_ansi(ansi)
+            .with_writer(writer);
+        match self.log_format {
+            LogFormat::Text => Box::new(builder.finish()),
+            LogFormat::Json => Box::new(builder.json().finish()),
+        }
+    }
+}
+
+/// `license-agent <version>` suivi des fonctionnalités compilées
+pub fn version_text() -> String {
+    let features = if BUILD_FEATURES.is_empty() {
+        "aucune".to_string()
+    } else {
+        BUILD_FEATURES.join(", ")
+    };
+    format!("license-agent {}\nfonctionnalités : {}", env!("CARGO_PKG_VERSION"), features)
+}
+
+fn print_config_diagnostic(config: &Config) {
+    println!("Configuration valide : {}", config.config_path().display());
+    println!("  état            : {}", config.state_path().display());
+    println!("  secrets scellés : {}", config.secret_store_path().display());
+    println!("  KEK             : {}", config.kek_path().display());
+    println!("  clés des apps   : {}", config.app_keys_path().display());
+    println!("  journal d'audit : {}", config.audit_log_path().display());
+    println!("  socket IPC      : {}", config.ipc_socket_path().display());
+    println!("  TPM             : {}", if config.tpm.enabled { "activé" } else { "désactivé" });
+    match config.api_listen_addr() {
+        Some(addr) => println!("  API HTTP        : {}", addr),
+        None => println!("  API HTTP        : désactivée"),
+    }
+}
+
+/// Se détache du terminal ; les journaux vont dans `license-agent.log`
+///
+/// Le répertoire courant est conservé : les chemins relatifs de la
+/// configuration restent valides.
+fn detach(config: &Config) -> Result<std::fs::File> {
+    let log_dir = config
+        .audit_log_path()
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| PathBuf::from("."));
+    std::fs::create_dir_all(&log_dir)
+        .with_context(|| format!("Failed to create log directory {}", log_dir.display()))?;
+    let log_path = log_dir.join(DAEMON_LOG_FILE_NAME);
+    let log_file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&log_path)
+        .with_context(|| format!("Failed to open log file {}", log_path.display()))?;
+
+    eprintln!("license-agent : passage en arrière-plan, journaux dans {}", log_path.display());
+    // Aucun thread n'est encore démarré : le fork est sûr
+    if unsafe { libc::daemon(1, 0) } != 0 {
+        return Err(std::io::Error::last_os_error()).context("Failed to detach from terminal");
+    }
+    Ok(log_file)
+}
+
+async fn serve(config: Config) -> Result<()> {
+    info!("License Secret Agent {} starting...", env!("CARGO_PKG_VERSION"));
+    info!("Configuration loaded from {}", config.config_path().display());
+
+    // Installé avant le démarrage : un SIGTERM pendant la rotation initiale arrête proprement
+    let mut terminate = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())?;
+
+    // Création moteur principal
+    let engine = Arc::new(CoreEngine::new(config).await?);
+    info!("Core engine initialized");
+
+    // Démarrage du moteur
+    if let Err(e) = engine.start().await {
+        error!("Failed to start core engine: {}", e);
+        return Err(e);
+    }
+
+    info!("License Secret Agent started successfully");
+    notify_systemd("READY=1");
+
+    // Attente signal d'arrêt (Ctrl+C au premier plan, SIGTERM de systemd ou de kill)
+    tokio::select! {
+        result = tokio::signal::ctrl_c() => result?,
+        _ = terminate.recv() => {}
+    }
+    info!("Shutdown signal received");
+    notify_systemd("STOPPING=1");
+
+    // Arrêt gracieux
+    engine.shutdown().await?;
+    info!("License Secret Agent stopped");
+
+    Ok(())
+}
+
+/// Notifie systemd (`Type=notify`) si `NOTIFY_SOCKET` est défini
+fn notify_systemd(state: &str) {
+    use std::os::unix::net::{SocketAddr, UnixDatagram};
+
+    let Some(path) = std::env::var_os("NOTIFY_SOCKET") else {
+        return;
+    };
+    let path = path.to_string_lossy().into_owned();
+    let address = match path.strip_prefix('@') {
+        Some(name) => {
+            use std::os::linux::net::SocketAddrExt;
+            SocketAddr::from_abstract_name(name.as_bytes())
+        }
+        None => SocketAddr::from_pathname(&path),
+    };
+
+    let sent = address.and_then(|address| UnixDatagram::unbound()?.send_to_addr(state.as_bytes(), &address));
+    if let Err(e) = sent {
+        warn!("Failed to notify systemd ({}): {}", path, e);
+    }
+}
@@ -0,0 +1,256 @@
+use crate::config::ExecutableAllowlistConfig;
+use crate::crypto::{load_public_key_pem, verify_pss_with_key};
+use crate::roles::process_start_time;
+use anyhow::Context;
+use base64::{engine::general_purpose, Engine as _};
+use rsa::RsaPublicKey;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::io::Read;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::{debug, warn};
+
+/// Intervalle minimal entre deux relectures des manifestes sur empreinte inconnue
+const MANIFEST_RELOAD_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Manifeste d'empreintes publié avec une version d'application
+///
+/// Fichier `<nom>.json` accompagné de sa signature RSA-PSS `<nom>.json.sig` (Base64).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExecutableManifest {
+    pub name: String,
+    #[serde(default)]
+    pub version: Option<String>,
+    /// Empreintes SHA-256 (hexadécimal) des exécutables autorisés
+    pub sha256: Vec<String>,
+}
+
+/// Résultat de la vérification de l'exécutable d'un client
+#[derive(Debug, Clone, Serialize)]
+pub struct ExecutableCheck {
+    pub executable: Option<PathBuf>,
+    pub sha256: Option<String>,
+    /// Origine de l'autorisation : `config` ou `manifest:<nom>[@version]`
+    pub source: Option<String>,
+    pub error: Option<String>,
+}
+
+impl ExecutableCheck {
+    pub fn is_allowed(&self) -> bool {
+        self.source.is_some()
+    }
+}
+
+#[derive(Default)]
+struct ManifestCache {
+    /// Empreinte → manifeste qui l'autorise
+    hashes: HashMap<[u8; 32], String>,
+    loaded_at: Option<Instant>,
+}
+
+/// Vérification de l'identité des exécutables clients
+///
+/// L'exécutable du processus client (`/proc/<pid>/exe`) est haché et comparé
+/// aux empreintes de la configuration et des manifestes signés. Les manifestes
+/// sont relus quand une empreinte inconnue est présentée, ce qui permet de
+/// déployer une nouvelle version d'application sans redémarrer l'agent.
+pub struct ExecutableVerifier {
+    allowed: Vec<[u8; 32]>,
+    manifest_dir: Option<PathBuf>,
+    manifest_key: Option<RsaPublicKey>,
+    manifests: Mutex<ManifestCache>,
+    /// Empreintes déjà calculées, par identité du fichier (voir [`FileKey`])
+    digests: Mutex<HashMap<FileKey, [u8; 32]>>,
+}
+
+/// Périphérique, inode, taille, dates de modification et de changement d'état
+///
+/// La date de changement d'état (ctime) ne peut pas être remise en arrière
+/// par `utimes` : un fichier réécrit puis antidaté est haché à nouveau.
+type FileKey = (u64, u64, u64, i64, i64, i64, i64);
+
+fn file_key(metadata: &std::fs::Metadata) -> FileKey {
+    (
+        metadata.dev(),
+        metadata.ino(),
+        metadata.size(),
+        metadata.mtime(),
+        metadata.mtime_nsec(),
+        metadata.ctime(),
+        metadata.ctime_nsec(),
+    )
+}
+
+impl ExecutableVerifier {
+    pub fn from_config(config: &ExecutableAllowlistConfig) -> anyhow::Result<Self> {
+        let allowed = config
+            .sha256
+            .iter()
+            .map(|hash| parse_digest(hash).with_context(|| format!("Invalid executable hash: {}", hash)))
+            .collect::<anyhow::Result<_>>()?;
+        let manifest_key = config
+            .manifest_public_key
+            .as_deref()
+            .map(load_public_key_pem)
+            .transpose()?;
+
+        let verifier = Self {
+            allowed,
+            manifest_dir: config.manifest_dir.clone(),
+            manifest_key,
+            manifests: Mutex::new(ManifestCache::default()),
+            digests: Mutex::new(HashMap::new()),
+        };
+        verifier.reload_manifests();
+        Ok(verifier)
+    }
+
+    /// Vérifie l'exécutable du processus `pid` (lecture et hachage bloquants)
+    ///
+    /// `start_time` est la date de démarrage du processus relevée à la
+    /// connexion : si le PID a été réattribué entre-temps, la vérification
+    /// échoue au lieu de porter sur un autre processus.
+    pub fn verify(&self, pid: u32, start_time: Option<u64>) -> ExecutableCheck {
+        let exe = PathBuf::from(format!("/proc/{}/exe", pid));
+        let mut check = ExecutableCheck {
+            executable: std::fs::read_link(&exe).ok(),
+            sha256: None,
+            source: None,
+            error: None,
+        };
+
+        match self.digest(&exe) {
+            Ok(digest) => {
+                check.sha256 = Some(hex::encode(digest));
+                check.source = self.check_digest(&digest);
+                if check.source.is_none() {
+                    check.error = Some("Executable hash not in allowlist".to_string());
+                }
+            }
+            Err(e) => check.error = Some(format!("Failed to hash executable: {}", e)),
+        }
+
+        if start_time.is_none() || process_start_time(pid) != start_time {
+            check.source = None;
+            check.error = Some(format!("Process {} exited or was replaced during verification", pid));
+        }
+
+        check
+    }
+
+    /// Origine de l'autorisation d'une empreinte, `None` si elle est inconnue
+    pub fn check_digest(&self, digest: &[u8; 32]) -> Option<String> {
+        if self.allowed.contains(digest) {
+            return Some("config".to_string());
+        }
+
+        if let Some(source) = self.manifests.lock().unwrap().hashes.get(digest) {
+            return Some(source.clone());
+        }
+
+        // Empreinte inconnue : un nouveau manifeste a peut-être été déployé
+        let stale = self
+            .manifests
+            .lock()
+            .unwrap()
+            .loaded_at
+            .is_none_or(|at| at.elapsed() >= MANIFEST_RELOAD_INTERVAL);
+        if stale {
+            self.reload_manifests();
+            return self.manifests.lock().unwrap().hashes.get(digest).cloned();
+        }
+
+        None
+    }
+
+    fn digest(&self, exe: &Path) -> std::io::Result<[u8; 32]> {
+        // Métadonnées du fichier ouvert : celui qui est haché, même si le chemin change
+        let mut file = std::fs::File::open(exe)?;
+        let key = file_key(&file.metadata()?);
+        if let Some(digest) = self.digests.lock().unwrap().get(&key) {
+            return Ok(*digest);
+        }
+
+        let mut hasher = Sha256::new();
+        let mut buf = vec![0u8; 64 * 1024];
+        loop {
+            let n = file.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buf[..n]);
+        }
+        let digest: [u8; 32] = hasher.finalize().into();
+
+        // Fichier modifié pendant le hachage : empreinte non mise en cache
+        if file_key(&file.metadata()?) == key {
+            self.digests.lock().unwrap().insert(key, digest);
+        }
+        Ok(digest)
+    }
+
+    /// Relit les manifestes ; ceux dont la signature est invalide sont ignorés
+    fn reload_manifests(&self) {
+        let mut hashes = HashMap::new();
+
+        if let (Some(dir), Some(key)) = (&self.manifest_dir, &self.manifest_key) {
+            match std::fs::read_dir(dir) {
+                Ok(entries) => {
+                    for path in entries.filter_map(|entry| entry.ok().map(|entry| entry.path())) {
+                        if path.extension().is_none_or(|ext| ext != "json") {
+                            continue;
+                        }
+                        match load_manifest(&path, key) {
+                            Ok(manifest) => {
+                                let source = match &manifest.version {
+                                    Some(version) => format!("manifest:{}@{}", manifest.name, version),
+                                    None => format!("manifest:{}", manifest.name),
+                                };
+                                for hash in &manifest.sha256 {
+                                    match parse_digest(hash) {
+                                        Ok(digest) => {
+                                            hashes.insert(digest, source.clone());
+                                        }
+                                        Err(_) => warn!("Invalid hash {} in manifest {}", hash, path.display()),
+                                    }
+                                }
+                            }
+                            Err(e) => warn!("Ignoring executable manifest {}: {}", path.display(), e),
+                        }
+                    }
+                }
+                Err(e) => warn!("Failed to read manifest directory {}: {}", dir.display(), e),
+            }
+        }
+
+        debug!("Loaded {} executable hashes from manifests", hashes.len());
+        let mut cache = self.manifests.lock().unwrap();
+        cache.hashes = hashes;
+        cache.loaded_at = Some(Instant::now());
+    }
+}
+
+/// Charge un manifeste et vérifie sa signature détachée
+pub fn load_manifest(path: &Path, key: &RsaPublicKey) -> anyhow::Result<ExecutableManifest> {
+    let data = std::fs::read(path)?;
+    let mut sig_path = path.as_os_str().to_owned();
+    sig_path.push(".sig");
+    let signature = std::fs::read_to_string(&sig_path).context("Missing manifest signature")?;
+    let signature = general_purpose::STANDARD
+        .decode(signature.trim())
+        .context("Invalid manifest signature encoding")?;
+
+    if !verify_pss_with_key(key, &data, &signature)? {
+        anyhow::bail!("Invalid manifest signature");
+    }
+
+    Ok(serde_json::from_slice(&data)?)
+}
+
+fn parse_digest(hash: &str) -> anyhow::Result<[u8; 32]> {
+    <[u8; 32]>::try_from(hex::decode(hash)?.as_slice()).map_err(|_| anyhow::anyhow!("SHA-256 hash must be 32 bytes"))
+}
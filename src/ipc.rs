@@ -1,22 +1,107 @@
-use crate::license::LicenseValidator;
-use crate::types::{ValidateLicenseRequest, ValidateLicenseResponse};
+use crate::admin::{AdminAuthenticator, Challenge, CHALLENGE_TTL};
+use crate::app_auth::{NonceCache, NonceRejection};
+use crate::config::ManagementConfig;
+use crate::core::CoreEngine;
+use crate::executable::{ExecutableCheck, ExecutableVerifier};
+use crate::protocol::{
+    is_connection_closed, read_frame_body, read_frame_len, write_frame, AuthChallengeResult, ErrorCode,
+    ErrorPayload, InvalidateResult, IpcRequest, IpcResponse, LogsResult, MetricsResult, RegisterAppResult,
+    RekeyResult, RequestEnvelope, ResealResult, ResetResult, ResponseEnvelope, RotateResult, PROTOCOL_VERSION,
+};
+use crate::ratelimit::{IpcRateLimiter, RequestClass};
+use crate::roles::{Peer, Role, RoleResolver};
+use crate::types::{AgentError, AgentResult, ValidateLicenseResponse, ValidationResult};
+use base64::{engine::general_purpose, Engine as _};
 use std::path::Path;
 use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::unix::OwnedWriteHalf;
 use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::{Mutex, Semaphore};
 use tracing::{debug, error, info, warn};
 
+/// Limites appliquées à chaque connexion IPC
+#[derive(Debug, Clone)]
+pub struct ConnectionLimits {
+    /// Durée d'inactivité avant fermeture de la connexion
+    pub idle_timeout: Duration,
+    /// Délai de réception d'une trame une fois son préfixe de longueur lu
+    pub frame_timeout: Duration,
+    /// Nombre maximal de requêtes pipelinées traitées en parallèle
+    pub max_in_flight: usize,
+    /// Nombre maximal de requêtes par connexion
+    pub max_requests: Option<u64>,
+    /// Nombre maximal de connexions simultanées, tous clients confondus
+    pub max_connections: usize,
+}
+
+impl ConnectionLimits {
+    pub fn from_config(config: &ManagementConfig) -> Self {
+        let defaults = Self::default();
+        Self {
+            idle_timeout: config
+                .ipc_idle_timeout_seconds
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.idle_timeout),
+            frame_timeout: config
+                .ipc_frame_timeout_seconds
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.frame_timeout),
+            max_in_flight: config
+                .ipc_max_in_flight
+                .unwrap_or(defaults.max_in_flight)
+                .max(1),
+            max_requests: config.ipc_max_requests_per_connection,
+            max_connections: config
+                .ipc_max_connections
+                .unwrap_or(defaults.max_connections)
+                .max(1),
+        }
+    }
+}
+
+impl Default for ConnectionLimits {
+    fn default() -> Self {
+        Self {
+            idle_timeout: Duration::from_secs(30),
+            frame_timeout: Duration::from_secs(10),
+            max_in_flight: 16,
+            max_requests: None,
+            max_connections: 256,
+        }
+    }
+}
+
+/// Collaborateurs partagés par les connexions IPC
+struct ConnectionContext {
+    engine: Arc<CoreEngine>,
+    roles: Arc<RoleResolver>,
+    admin_auth: Arc<AdminAuthenticator>,
+    limits: ConnectionLimits,
+    rate_limiter: Option<Arc<IpcRateLimiter>>,
+    executables: Option<Arc<ExecutableVerifier>>,
+    nonces: Arc<NonceCache>,
+}
+
 /// Serveur IPC (Unix Domain Socket)
 pub struct IpcServer {
     listener: UnixListener,
-    validator: Arc<LicenseValidator>,
-    allowed_uids: Vec<u32>,
+    engine: Arc<CoreEngine>,
+    roles: Arc<RoleResolver>,
+    admin_auth: Arc<AdminAuthenticator>,
+    limits: ConnectionLimits,
+    rate_limiter: Option<Arc<IpcRateLimiter>>,
+    executables: Option<Arc<ExecutableVerifier>>,
+    nonces: Arc<NonceCache>,
 }
 
 impl IpcServer {
     pub async fn new<P: AsRef<Path>>(
         socket_path: P,
-        validator: Arc<LicenseValidator>,
-        allowed_uids: Vec<u32>,
+        engine: Arc<CoreEngine>,
+        roles: Arc<RoleResolver>,
+        admin_auth: Arc<AdminAuthenticator>,
+        limits: ConnectionLimits,
     ) -> anyhow::Result<Self> {
         // Supprimer socket existant si présent
         if socket_path.as_ref().exists() {
@@ -30,7 +115,7 @@ impl IpcServer {
 
         // Créer listener
         let listener = UnixListener::bind(&socket_path)?;
-        
+
         // Permissions socket (600)
         #[cfg(unix)]
         {
@@ -44,23 +129,53 @@ impl IpcServer {
 
         Ok(Self {
             listener,
-            validator,
-            allowed_uids,
+            engine,
+            roles,
+            admin_auth,
+            limits,
+            rate_limiter: None,
+            executables: None,
+            nonces: Arc::new(NonceCache::default()),
         })
     }
 
+    /// Applique des budgets de requêtes par UID et par PID
+    pub fn with_rate_limiter(mut self, rate_limiter: Arc<IpcRateLimiter>) -> Self {
+        self.rate_limiter = Some(rate_limiter);
+        self
+    }
+
+    /// Restreint les validations aux exécutables dont l'empreinte est autorisée
+    pub fn with_executable_verifier(mut self, executables: Arc<ExecutableVerifier>) -> Self {
+        self.executables = Some(executables);
+        self
+    }
+
     /// Démarre le serveur IPC
     pub async fn run(&self) -> anyhow::Result<()> {
+        let context = Arc::new(ConnectionContext {
+            engine: Arc::clone(&self.engine),
+            roles: Arc::clone(&self.roles),
+            admin_auth: Arc::clone(&self.admin_auth),
+            limits: self.limits.clone(),
+            rate_limiter: self.rate_limiter.clone(),
+            executables: self.executables.clone(),
+            nonces: Arc::clone(&self.nonces),
+        });
+
+        let connections = Arc::new(Semaphore::new(self.limits.max_connections));
+
         loop {
+            // Au-delà du plafond, les connexions attendent dans la file d'écoute du noyau
+            let permit = Arc::clone(&connections).acquire_owned().await?;
             match self.listener.accept().await {
                 Ok((stream, addr)) => {
                     debug!("New IPC connection from {:?}", addr);
-                    
-                    let validator = Arc::clone(&self.validator);
-                    let allowed_uids = self.allowed_uids.clone();
-                    
+
+                    let context = Arc::clone(&context);
                     tokio::spawn(async move {
-                        if let Err(e) = Self::handle_connection(stream, validator, allowed_uids).await {
+                        let _permit = permit;
+                        if let Err(e) = Self::handle_connection(stream, &context).await {
                             error!("Error handling IPC connection: {}", e);
                         }
                     });
@@ -73,78 +188,390 @@ impl IpcServer {
         }
     }
 
-    async fn handle_connection(
-        mut stream: UnixStream,
-        validator: Arc<LicenseValidator>,
-        allowed_uids: Vec<u32>,
-    ) -> anyhow::Result<()> {
-        // Vérifier UID du client
-        let peer_uid = Self::get_peer_uid(&stream)?;
-        
-        if !allowed_uids.is_empty() && !allowed_uids.contains(&peer_uid) {
+    async fn handle_connection(mut stream: UnixStream, context: &ConnectionContext) -> anyhow::Result<()> {
+        let ConnectionContext { engine, roles, admin_auth, limits, rate_limiter, executables, nonces } = context;
+
+        // Rôle du client (UID, GID, exécutable)
+        let peer = Self::get_peer(&stream)?;
+        let (peer_uid, peer_pid) = (peer.uid, peer.pid);
+
+        let Some(role) = roles.resolve(&peer) else {
             warn!("Rejected connection from unauthorized UID: {}", peer_uid);
+            engine.audit_logger().access_denied(None, peer.to_json(), None, None).await;
+            let response = ResponseEnvelope::error(None, ErrorPayload::new(
+                ErrorCode::Unauthorized,
+                format!("Unauthorized UID: {}", peer_uid),
+            ));
+            let response_json = serde_json::to_vec(&response)?;
+            write_frame(&mut stream, &response_json).await?;
             return Err(anyhow::anyhow!("Unauthorized UID: {}", peer_uid));
+        };
+
+        debug!("Accepted connection from UID {} with role {}", peer_uid, role);
+
+        let (mut reader, writer) = stream.into_split();
+        let writer = Arc::new(Mutex::new(writer));
+        let in_flight = Arc::new(Semaphore::new(limits.max_in_flight));
+        let mut handled: u64 = 0;
+        // Challenge d'administration en attente sur cette connexion
+        let mut challenge: Option<Challenge> = None;
+        // Exécutable du processus SO_PEERCRED, vérifié dès l'acceptation ; audité à la première validation
+        let executable_check: Option<ExecutableCheck> = match executables {
+            Some(verifier) => {
+                let verifier = Arc::clone(verifier);
+                let start_time = peer.start_time;
+                Some(tokio::task::spawn_blocking(move || verifier.verify(peer_pid, start_time)).await?)
+            }
+            None => None,
+        };
+        let mut executable_audited = false;
+
+        loop {
+            if limits.max_requests.is_some_and(|max| handled >= max) {
+                debug!("Connection from UID {} reached its request cap ({})", peer_uid, handled);
+                break;
+            }
+
+            // Attendre la requête suivante (ou fermer après inactivité)
+            let len = match tokio::time::timeout(limits.idle_timeout, read_frame_len(&mut reader)).await {
+                Ok(Ok(len)) => len,
+                Ok(Err(e)) if is_connection_closed(&e) => break,
+                Ok(Err(e)) => return Err(e),
+                Err(_) => {
+                    debug!("Closing idle IPC connection from UID {}", peer_uid);
+                    break;
+                }
+            };
+            // Corps de la trame : délai propre, une trame incomplète ne bloque pas la connexion
+            let data = match tokio::time::timeout(limits.frame_timeout, read_frame_body(&mut reader, len)).await {
+                Ok(Ok(data)) => data,
+                Ok(Err(e)) if is_connection_closed(&e) => break,
+                Ok(Err(e)) => return Err(e),
+                Err(_) => {
+                    warn!("Closing IPC connection from UID {}: incomplete frame of {} bytes", peer_uid, len);
+                    break;
+                }
+            };
+            handled += 1;
+
+            // Processus client terminé et PID réattribué : la vérification ne vaut plus
+            if executable_check.is_some() && !peer.is_same_process() {
+                warn!("Closing IPC connection from UID {}: process {} is gone", peer_uid, peer_pid);
+                break;
+            }
+
+            let RequestEnvelope { id, request, auth, .. } = match Self::parse_request(&data) {
+                Ok(envelope) => envelope,
+                Err((id, error)) => {
+                    warn!("Invalid IPC request from UID {}: {}", peer_uid, error.message);
+                    Self::send_response(&writer, &ResponseEnvelope::error(id, error)).await?;
+                    continue;
+                }
+            };
+
+            // Budgets de débit : une requête refusée n'est pas exécutée
+            if let Some(rate_limiter) = &rate_limiter {
+                let class = match request {
+                    IpcRequest::Validate(_) => RequestClass::Validation,
+                    _ => RequestClass::Admin,
+                };
+                if let Err(retry_after) = rate_limiter.check(class, peer_uid, peer_pid, request.command()).await {
+                    let error = ErrorPayload::new(
+                        ErrorCode::RateLimited,
+                        format!(
+                            "Rate limit exceeded for {} requests, retry in {}ms",
+                            class.as_str(),
+                            retry_after.as_millis()
+                        ),
+                    );
+                    Self::send_response(&writer, &ResponseEnvelope::error(id, error)).await?;
+                    continue;
+                }
+            }
+
+            // Autorisation : rôle du client suffisant pour la commande
+            let required_role = request.required_role();
+            if role < required_role {
+                let command = request.command();
+                warn!("Denied {} to UID {} with role {} (requires {})", command, peer_uid, role, required_role);
+                engine
+                    .audit_logger()
+                    .access_denied(Some(command), peer.to_json(), Some(role.as_str()), Some(required_role.as_str()))
+                    .await;
+                let error = ErrorPayload::new(
+                    ErrorCode::Forbidden,
+                    format!("Command {} requires role {}", command, required_role),
+                );
+                Self::send_response(&writer, &ResponseEnvelope::error(id, error)).await?;
+                continue;
+            }
+
+            // Validation réservée aux exécutables autorisés
+            if let (IpcRequest::Validate(_), Some(check)) = (&request, &executable_check) {
+                if !executable_audited {
+                    engine.audit_logger().executable_checked(peer.to_json(), check).await;
+                    executable_audited = true;
+                }
+                if !check.is_allowed() {
+                    warn!(
+                        "Denied validation to PID {} ({}): {}",
+                        peer_pid,
+                        check.executable.as_deref().unwrap_or(Path::new("?")).display(),
+                        check.error.as_deref().unwrap_or_default()
+                    );
+                    let error = ErrorPayload::new(ErrorCode::Forbidden, "Executable not allowed to validate licenses");
+                    Self::send_response(&writer, &ResponseEnvelope::error(id, error)).await?;
+                    continue;
+                }
+            }
+
+            // Anti-rejeu : un nonce n'est accepté qu'une fois par UID pendant `NONCE_TTL`
+            if let IpcRequest::Validate(validate) = &request {
+                let rejection = nonces.check_and_insert(peer_uid, &validate.nonce).err();
+                let error = match rejection {
+                    None => None,
+                    Some(NonceRejection::Replayed) => {
+                        warn!("Replayed validation nonce from UID {} (PID {})", peer_uid, peer_pid);
+                        engine.audit_logger().replay_detected(peer_uid, peer_pid, &validate.nonce).await;
+                        Some(ErrorPayload::new(ErrorCode::ReplayDetected, "Nonce already used"))
+                    }
+                    Some(NonceRejection::CacheFull) => {
+                        warn!("Too many pending validation nonces for UID {} (PID {})", peer_uid, peer_pid);
+                        Some(ErrorPayload::new(
+                            ErrorCode::RateLimited,
+                            "Too many validations in the replay window, retry later",
+                        ))
+                    }
+                };
+                if let Some(error) = error {
+                    Self::send_response(&writer, &ResponseEnvelope::error(id, error)).await?;
+                    continue;
+                }
+            }
+
+            if let IpcRequest::AuthChallenge {} = request {
+                let issued = Challenge::new();
+                let response = IpcResponse::AuthChallenge(AuthChallengeResult {
+                    challenge: hex::encode(issued.nonce()),
+                    expires_in_seconds: CHALLENGE_TTL.as_secs(),
+                });
+                challenge = Some(issued);
+                Self::send_response(&writer, &ResponseEnvelope::ok(id, response)).await?;
+                continue;
+            }
+
+            // Commandes d'administration : rôle `admin` et identifiants vérifiés par l'agent ;
+            // commandes d'exploitation : le rôle suffit, elles sont journalisées sans principal
+            let caller = if request.is_admin() {
+                let command = request.command();
+                let challenge = challenge.take();
+                match admin_auth.authorize(auth.as_ref(), challenge.as_ref(), command) {
+                    Ok(principal) => {
+                        let mut caller = peer.to_json();
+                        caller["role"] = role.as_str().into();
+                        caller["principal"] = principal.into();
+                        Some(caller)
+                    }
+                    Err(reason) => {
+                        warn!("Rejected admin command {} from UID {}: {}", command, peer_uid, reason);
+                        engine.audit_logger().admin_auth_failed(command, peer.to_json(), &reason).await;
+                        let error = ErrorPayload::new(ErrorCode::Unauthorized, reason);
+                        Self::send_response(&writer, &ResponseEnvelope::error(id, error)).await?;
+                        continue;
+                    }
+                }
+            } else if request.required_role() == Role::Operator {
+                let mut caller = peer.to_json();
+                caller["role"] = role.as_str().into();
+                Some(caller)
+            } else {
+                None
+            };
+
+            // Réserver une place parmi les requêtes en cours
+            let permit = Arc::clone(&in_flight).acquire_owned().await?;
+
+            if id.is_none() {
+                // Sans identifiant : traitement séquentiel pour préserver l'ordre
+                let response = Self::execute(engine, id, request, caller).await;
+                drop(permit);
+                Self::send_response(&writer, &response).await?;
+                continue;
+            }
+
+            let engine = Arc::clone(engine);
+            let writer = Arc::clone(&writer);
+            tokio::spawn(async move {
+                let response = Self::execute(&engine, id, request, caller).await;
+                if let Err(e) = Self::send_response(&writer, &response).await {
+                    debug!("Failed to send pipelined IPC response: {}", e);
+                }
+                drop(permit);
+            });
         }
 
-        debug!("Accepted connection from UID: {}", peer_uid);
+        // Attendre la fin des requêtes pipelinées avant de fermer
+        let _ = in_flight.acquire_many(limits.max_in_flight as u32).await?;
+
+        debug!("IPC connection from UID {} closed after {} requests", peer_uid, handled);
+
+        Ok(())
+    }
+
+    fn parse_request(data: &[u8]) -> Result<RequestEnvelope, (Option<u64>, ErrorPayload)> {
+        let value: serde_json::Value = serde_json::from_slice(data).map_err(|e| {
+            (None, ErrorPayload::new(ErrorCode::InvalidRequest, format!("Failed to parse request: {}", e)))
+        })?;
 
-        // Lire requête
-        use tokio::io::{AsyncReadExt, AsyncWriteExt};
-        
-        // Lire longueur (4 bytes)
-        let mut len_bytes = [0u8; 4];
-        stream.read_exact(&mut len_bytes).await?;
-        let len = u32::from_be_bytes(len_bytes) as usize;
+        // Récupérer l'identifiant même si le reste de la requête est invalide
+        let id = value.get("id").and_then(|id| id.as_u64());
 
-        if len > 1024 * 1024 {
-            return Err(anyhow::anyhow!("Request too large: {} bytes", len));
+        let envelope: RequestEnvelope = serde_json::from_value(value).map_err(|e| {
+            (id, ErrorPayload::new(ErrorCode::InvalidRequest, format!("Failed to parse request: {}", e)))
+        })?;
+
+        if envelope.version > PROTOCOL_VERSION {
+            return Err((id, ErrorPayload::new(
+                ErrorCode::UnsupportedVersion,
+                format!(
+                    "Unsupported protocol version {} (max supported: {})",
+                    envelope.version, PROTOCOL_VERSION
+                ),
+            )));
         }
 
-        // Lire données
-        let mut data = vec![0u8; len];
-        stream.read_exact(&mut data).await?;
-
-        // Désérialiser requête
-        let request: ValidateLicenseRequest = serde_json::from_slice(&data)
-            .map_err(|e| anyhow::anyhow!("Failed to parse request: {}", e))?;
-
-        // Valider licence
-        let result = validator.validate(&request.license_token).await;
-
-        let response = match result {
-            Ok(validation_result) => ValidateLicenseResponse {
-                result: validation_result,
-            },
-            Err(e) => ValidateLicenseResponse {
-                result: crate::types::ValidationResult {
-                    valid: false,
-                    expires_at: None,
-                    features: vec![],
-                    metadata: std::collections::HashMap::new(),
-                    error: Some(e.to_string()),
-                },
-            },
-        };
+        Ok(envelope)
+    }
 
-        // Sérialiser réponse
-        let response_json = serde_json::to_vec(&response)?;
-        let response_len = response_json.len() as u32;
+    /// Exécute une commande ; `caller` : administrateur authentifié, audité avec le résultat
+    async fn execute(
+        engine: &CoreEngine,
+        id: Option<u64>,
+        request: IpcRequest,
+        caller: Option<serde_json::Value>,
+    ) -> ResponseEnvelope {
+        let command = request.command();
+        let result = Self::dispatch(engine, request).await;
 
-        // Envoyer longueur + données
-        stream.write_all(&response_len.to_be_bytes()).await?;
-        stream.write_all(&response_json).await?;
-        stream.flush().await?;
+        if let Some(caller) = caller {
+            let error = result.as_ref().err().map(ToString::to_string);
+            engine.audit_logger().admin_command(command, caller, error.as_deref()).await;
+        }
 
-        debug!("Response sent to UID: {}", peer_uid);
+        match result {
+            Ok(response) => ResponseEnvelope::ok(id, response),
+            Err(e) => {
+                debug!("IPC command failed: {}", e);
+                ResponseEnvelope::error(id, ErrorPayload::from(&e))
+            }
+        }
+    }
 
-        Ok(())
+    /// Exécute une commande IPC sur le moteur
+    pub(crate) async fn dispatch(engine: &CoreEngine, request: IpcRequest) -> AgentResult<IpcResponse> {
+        match request {
+            IpcRequest::Validate(request) => {
+                // Un échec de validation n'est pas une erreur protocolaire :
+                // le client reçoit un résultat `valid: false`
+                let result = match engine.validate(&request.license_token).await {
+                    Ok(validation_result) => validation_result,
+                    Err(e) => ValidationResult {
+                        valid: false,
+                        expires_at: None,
+                        features: vec![],
+                        metadata: std::collections::HashMap::new(),
+                        error: Some(e.to_string()),
+                    },
+                };
+                // Réponse authentifiée pour les applications enregistrées
+                let mac = match &request.app_id {
+                    Some(app_id) => Some(
+                        engine
+                            .validation_mac(app_id, &request.nonce, &request.license_token, &result)
+                            .await?,
+                    ),
+                    None => None,
+                };
+                Ok(IpcResponse::Validate(ValidateLicenseResponse { result, mac }))
+            }
+            IpcRequest::Status {} => Ok(IpcResponse::Status(Box::new(engine.get_status().await?))),
+            IpcRequest::Rotate { force } => {
+                let rotated = engine.rotate(force).await?;
+                Ok(IpcResponse::Rotate(RotateResult {
+                    rotated,
+                    active_version: engine.active_version(),
+                }))
+            }
+            IpcRequest::Invalidate { version, reason } => {
+                engine.invalidate(version, reason).await?;
+                Ok(IpcResponse::Invalidate(InvalidateResult {
+                    version,
+                    active_version: engine.active_version(),
+                }))
+            }
+            IpcRequest::Logs(request) => Ok(IpcResponse::Logs(LogsResult {
+                events: engine.audit_events(&request).await?,
+            })),
+            IpcRequest::Metrics {} => Ok(IpcResponse::Metrics(MetricsResult {
+                text: engine.metrics_text().await?,
+            })),
+            IpcRequest::DegradedMode { enable, disable, reason } => {
+                if enable && disable {
+                    return Err(AgentError::IpcError(
+                        "Cannot enable and disable degraded mode simultaneously".to_string(),
+                    ));
+                }
+                if enable {
+                    let reason = reason.ok_or_else(|| {
+                        AgentError::IpcError("Reason required to enable degraded mode".to_string())
+                    })?;
+                    engine.activate_degraded_mode(&reason).await;
+                } else if disable {
+                    engine.deactivate_degraded_mode().await;
+                }
+                Ok(IpcResponse::DegradedMode(engine.degraded_mode_status().await))
+            }
+            IpcRequest::TpmStatus {} => Ok(IpcResponse::TpmStatus(engine.tpm_status())),
+            IpcRequest::Reset {} => Ok(IpcResponse::Reset(ResetResult {
+                secrets_removed: engine.reset().await?,
+            })),
+            IpcRequest::Rekey {} => {
+                let secrets_rewrapped = engine.rekey().await?;
+                Ok(IpcResponse::Rekey(RekeyResult {
+                    kek_id: engine.kek_id(),
+                    secrets_rewrapped,
+                }))
+            }
+            IpcRequest::Reseal { suspend_pcr_binding } => Ok(IpcResponse::Reseal(ResealResult {
+                secrets_resealed: engine.reseal(suspend_pcr_binding).await?,
+                pcr_binding_suspended: suspend_pcr_binding,
+            })),
+            IpcRequest::RegisterApp { app_id } => {
+                let key = engine.register_app(&app_id).await?;
+                Ok(IpcResponse::RegisterApp(RegisterAppResult {
+                    app_id,
+                    key: general_purpose::STANDARD.encode(&*key),
+                }))
+            }
+            // Traité par la connexion IPC, qui conserve le challenge émis
+            IpcRequest::AuthChallenge {} => Err(AgentError::IpcError(
+                "auth_challenge is only available on IPC connections".to_string(),
+            )),
+        }
     }
 
-    fn get_peer_uid(stream: &UnixStream) -> anyhow::Result<u32> {
+    async fn send_response(writer: &Mutex<OwnedWriteHalf>, response: &ResponseEnvelope) -> anyhow::Result<()> {
+        let response_json = serde_json::to_vec(response)?;
+        let mut writer = writer.lock().await;
+        write_frame(&mut *writer, &response_json).await
+    }
+
+    /// Identité du processus client (SO_PEERCRED, complétée par /proc)
+    fn get_peer(stream: &UnixStream) -> anyhow::Result<Peer> {
         use nix::sys::socket::{getsockopt, sockopt};
-        
+
         let creds = getsockopt(stream, sockopt::PeerCredentials)
             .map_err(|e| anyhow::anyhow!("Failed to get peer credentials: {}", e))?;
-        Ok(creds.uid() as u32)
+        Ok(Peer::from_credentials(creds.uid(), creds.gid(), creds.pid() as u32))
     }
 }
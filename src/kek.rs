@@ -0,0 +1,483 @@
+use crate::config::Config;
+use crate::types::{AgentError, AgentResult};
+use aes_gcm::{
+    aead::{Aead, KeyInit, Payload},
+    Aes256Gcm, Key, Nonce,
+};
+use base64::{engine::general_purpose, Engine as _};
+use chrono::{DateTime, Utc};
+use hkdf::Hkdf;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tracing::{debug, info, warn};
+use zeroize::Zeroizing;
+
+/// Graine utilisée quand aucune n'est configurée (refusée en mode production)
+///
+/// Une KEK enveloppée avec cette graine est ré-enveloppée avec la graine
+/// configurée au démarrage suivant : c'est la migration des installations
+/// antérieures à `kek_seed_file`.
+pub const DEFAULT_SEED: &str = "CHANGE_THIS_IN_PRODUCTION";
+
+/// Variable d'environnement fournissant la graine
+pub const SEED_ENV_VAR: &str = "LICENSE_AGENT_FALLBACK_KEY";
+
+const DEFAULT_MACHINE_ID_PATH: &str = "/etc/machine-id";
+const KEK_FILE_FORMAT: u32 = 1;
+const KEK_ID_LEN: usize = 4;
+const NONCE_LEN: usize = 12;
+const WRAP_INFO: &[u8] = b"license-agent kek-wrap v1";
+const WRAP_AAD: &[u8] = b"license-agent kek";
+/// Ajouté aux données associées de l'enveloppe une fois l'état authentifié
+const STATE_AUTHENTICATED_AAD: &[u8] = b"\0state-authenticated";
+const SECRET_INFO: &[u8] = b"license-agent secret v1";
+
+/// Identifiant d'une KEK
+pub type KekId = [u8; KEK_ID_LEN];
+
+/// Hiérarchie de clés du chiffrement logiciel
+///
+/// ```text
+/// graine + /etc/machine-id --HKDF--> clé d'enveloppe
+/// clé d'enveloppe --AES-GCM--> KEK aléatoire (fichier `kek_path`)
+/// KEK + version --HKDF--> clé de données du secret
+/// ```
+///
+/// Le fichier de KEK ne peut être déballé que sur la machine qui l'a créé et
+/// avec la même graine. Pendant un `rekey`, l'ancienne et la nouvelle KEK
+/// coexistent dans le fichier jusqu'à ce que tous les secrets soient
+/// rechiffrés.
+///
+/// Le fichier enregistre aussi que l'état de l'agent a été authentifié (MAC)
+/// au moins une fois. Ce marqueur entre dans les données associées de
+/// l'enveloppe des KEK : le retirer rend le fichier illisible.
+pub struct KeyHierarchy {
+    path: PathBuf,
+    wrapping_key: Zeroizing<[u8; 32]>,
+    state: Mutex<KekState>,
+    default_seed: bool,
+}
+
+struct KekState {
+    current: KekId,
+    keys: BTreeMap<KekId, KekEntry>,
+    state_authenticated: bool,
+}
+
+struct KekEntry {
+    key: Zeroizing<[u8; 32]>,
+    created_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct KekFile {
+    format: u32,
+    current: String,
+    keys: Vec<WrappedKek>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    state_authenticated: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct WrappedKek {
+    id: String,
+    /// Base64(nonce || clé chiffrée)
+    wrapped: String,
+    created_at: DateTime<Utc>,
+}
+
+impl KeyHierarchy {
+    /// Ouvre la hiérarchie décrite par la configuration
+    ///
+    /// En mode production, refuse la graine par défaut et exige `/etc/machine-id`.
+    /// Hors production, la graine par défaut n'est qu'un avertissement.
+    pub fn from_config(config: &Config) -> AgentResult<Self> {
+        let production = config.agent.production_mode;
+        let (seed, default_seed) = load_seed(config.tpm.kek_seed_file.as_deref())?;
+
+        if default_seed {
+            if production {
+                return Err(AgentError::ConfigError(format!(
+                    "Refusing to start in production mode with the default key seed: set {} or tpm.kek_seed_file",
+                    SEED_ENV_VAR
+                )));
+            }
+            warn!(
+                "⚠️  Using the default key seed - secrets are not protected: set {} or tpm.kek_seed_file, then production_mode = true",
+                SEED_ENV_VAR
+            );
+        }
+
+        let machine_id = match std::fs::read_to_string(DEFAULT_MACHINE_ID_PATH) {
+            Ok(id) if !id.trim().is_empty() => id.trim().to_string(),
+            Ok(_) | Err(_) if production => {
+                return Err(AgentError::ConfigError(format!(
+                    "Cannot read machine id from {}",
+                    DEFAULT_MACHINE_ID_PATH
+                )));
+            }
+            _ => {
+                warn!("Machine id unavailable, key hierarchy not bound to this host");
+                String::new()
+            }
+        };
+
+        let mut hierarchy = if default_seed {
+            Self::open(config.kek_path(), &seed, machine_id.as_bytes())?
+        } else {
+            Self::open_with_previous_seed(config.kek_path(), &seed, DEFAULT_SEED.as_bytes(), machine_id.as_bytes())?
+        };
+        hierarchy.default_seed = default_seed;
+        Ok(hierarchy)
+    }
+
+    /// Ouvre (ou crée au premier démarrage) le fichier de KEK
+    pub fn open(path: PathBuf, seed: &[u8], machine_id: &[u8]) -> AgentResult<Self> {
+        let hierarchy = Self {
+            path,
+            wrapping_key: derive_wrapping_key(seed, machine_id)?,
+            state: Mutex::new(KekState {
+                current: [0u8; KEK_ID_LEN],
+                keys: BTreeMap::new(),
+                state_authenticated: false,
+            }),
+            default_seed: false,
+        };
+
+        if hierarchy.path.exists() {
+            hierarchy.load()?;
+            debug!("Key hierarchy loaded from {}", hierarchy.path.display());
+        } else {
+            let (id, entry) = generate_kek();
+            {
+                let mut state = hierarchy.state.lock().unwrap();
+                state.current = id;
+                state.keys.insert(id, entry);
+            }
+            hierarchy.persist()?;
+            info!("New key-encryption key {} generated in {}", hex::encode(id), hierarchy.path.display());
+        }
+
+        Ok(hierarchy)
+    }
+
+    /// Ouvre le fichier de KEK avec `seed`, ou avec `previous_seed` puis le ré-enveloppe avec `seed`
+    ///
+    /// Permet de changer de graine sans perdre les secrets : l'ancienne graine
+    /// n'est plus nécessaire une fois le fichier réécrit.
+    pub fn open_with_previous_seed(
+        path: PathBuf,
+        seed: &[u8],
+        previous_seed: &[u8],
+        machine_id: &[u8],
+    ) -> AgentResult<Self> {
+        let error = match Self::open(path.clone(), seed, machine_id) {
+            Ok(hierarchy) => return Ok(hierarchy),
+            Err(e) => e,
+        };
+        let Ok(mut hierarchy) = Self::open(path, previous_seed, machine_id) else {
+            return Err(error);
+        };
+
+        hierarchy.wrapping_key = derive_wrapping_key(seed, machine_id)?;
+        hierarchy.persist()?;
+        info!("Key-encryption keys in {} re-wrapped with the new key seed", hierarchy.path.display());
+        Ok(hierarchy)
+    }
+
+    /// Indique si la graine par défaut est utilisée
+    pub fn uses_default_seed(&self) -> bool {
+        self.default_seed
+    }
+
+    /// Identifiant de la KEK courante
+    pub fn current_id(&self) -> KekId {
+        self.state.lock().unwrap().current
+    }
+
+    /// Clé de données de la KEK courante pour une version de secret
+    pub fn current_data_key(&self, version: u64) -> AgentResult<(KekId, Zeroizing<[u8; 32]>)> {
+        let id = self.current_id();
+        Ok((id, self.data_key(&id, version)?))
+    }
+
+    /// Clé de données d'une KEK donnée pour une version de secret
+    pub fn data_key(&self, id: &KekId, version: u64) -> AgentResult<Zeroizing<[u8; 32]>> {
+        let state = self.state.lock().unwrap();
+        let entry = state.keys.get(id).ok_or_else(|| {
+            AgentError::CryptoError(format!("Unknown key-encryption key {}", hex::encode(id)))
+        })?;
+
+        let mut info = SECRET_INFO.to_vec();
+        info.extend_from_slice(&version.to_be_bytes());
+
+        let mut key = Zeroizing::new([0u8; 32]);
+        Hkdf::<Sha256>::new(Some(id), entry.key.as_ref())
+            .expand(&info, key.as_mut())
+            .map_err(|e| AgentError::CryptoError(format!("Key derivation failed: {}", e)))?;
+        Ok(key)
+    }
+
+    /// L'état de l'agent a déjà été authentifié : sa clé de MAC ne peut plus manquer
+    pub fn state_authenticated(&self) -> bool {
+        self.state.lock().unwrap().state_authenticated
+    }
+
+    /// Enregistre, définitivement, que l'état de l'agent est authentifié
+    pub fn mark_state_authenticated(&self) -> AgentResult<()> {
+        {
+            let mut state = self.state.lock().unwrap();
+            if state.state_authenticated {
+                return Ok(());
+            }
+            state.state_authenticated = true;
+        }
+        self.persist()?;
+        info!("State authentication recorded in {}", self.path.display());
+        Ok(())
+    }
+
+    /// Génère une nouvelle KEK courante, en conservant les précédentes
+    pub fn begin_rekey(&self) -> AgentResult<KekId> {
+        let (id, entry) = generate_kek();
+        {
+            let mut state = self.state.lock().unwrap();
+            state.current = id;
+            state.keys.insert(id, entry);
+        }
+        self.persist()?;
+        info!("Key-encryption key {} generated for rekey", hex::encode(id));
+        Ok(id)
+    }
+
+    /// Supprime les KEK autres que la courante, une fois les secrets rechiffrés
+    pub fn retire_previous(&self) -> AgentResult<usize> {
+        let retired = {
+            let mut state = self.state.lock().unwrap();
+            let current = state.current;
+            let before = state.keys.len();
+            state.keys.retain(|id, _| *id == current);
+            before - state.keys.len()
+        };
+        self.persist()?;
+        Ok(retired)
+    }
+
+    fn load(&self) -> AgentResult<()> {
+        let content = std::fs::read_to_string(&self.path)
+            .map_err(|e| kek_error("read", &self.path, e))?;
+        let file: KekFile = serde_json::from_str(&content)
+            .map_err(|e| AgentError::CryptoError(format!("Invalid key file {}: {}", self.path.display(), e)))?;
+
+        if file.format != KEK_FILE_FORMAT {
+            return Err(AgentError::CryptoError(format!(
+                "Unsupported key file format {}",
+                file.format
+            )));
+        }
+
+        let current = parse_id(&file.current)?;
+        let mut keys = BTreeMap::new();
+        for wrapped in &file.keys {
+            let id = parse_id(&wrapped.id)?;
+            let key = self.unwrap_key(&id, &wrapped.wrapped, file.state_authenticated)?;
+            keys.insert(id, KekEntry { key, created_at: wrapped.created_at });
+        }
+
+        if !keys.contains_key(&current) {
+            return Err(AgentError::CryptoError(format!(
+                "Current key-encryption key {} missing from {}",
+                file.current,
+                self.path.display()
+            )));
+        }
+
+        *self.state.lock().unwrap() = KekState {
+            current,
+            keys,
+            state_authenticated: file.state_authenticated,
+        };
+        Ok(())
+    }
+
+    fn persist(&self) -> AgentResult<()> {
+        let file = {
+            let state = self.state.lock().unwrap();
+            let keys = state
+                .keys
+                .iter()
+                .map(|(id, entry)| {
+                    Ok(WrappedKek {
+                        id: hex::encode(id),
+                        wrapped: self.wrap_key(id, &entry.key, state.state_authenticated)?,
+                        created_at: entry.created_at,
+                    })
+                })
+                .collect::<AgentResult<Vec<_>>>()?;
+            KekFile {
+                format: KEK_FILE_FORMAT,
+                current: hex::encode(state.current),
+                keys,
+                state_authenticated: state.state_authenticated,
+            }
+        };
+
+        let content = serde_json::to_vec_pretty(&file)
+            .map_err(|e| AgentError::InternalError(format!("Failed to serialize key file: {}", e)))?;
+
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| kek_error("create", parent, e))?;
+        }
+
+        // Écriture atomique (fichier temporaire + fsync + rename)
+        let tmp_path = self.path.with_extension("tmp");
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+
+        let mut tmp = options.open(&tmp_path).map_err(|e| kek_error("create", &tmp_path, e))?;
+        tmp.write_all(&content).map_err(|e| kek_error("write", &tmp_path, e))?;
+        tmp.sync_all().map_err(|e| kek_error("sync", &tmp_path, e))?;
+        drop(tmp);
+
+        std::fs::rename(&tmp_path, &self.path).map_err(|e| kek_error("rename", &self.path, e))?;
+        if let Some(parent) = self.path.parent() {
+            if let Err(e) = std::fs::File::open(parent).and_then(|d| d.sync_all()) {
+                warn!("Failed to sync {}: {}", parent.display(), e);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn wrap_key(&self, id: &KekId, key: &[u8; 32], state_authenticated: bool) -> AgentResult<String> {
+        let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(self.wrapping_key.as_ref()));
+
+        let mut nonce_bytes = [0u8; NONCE_LEN];
+        rand::thread_rng().fill_bytes(&mut nonce_bytes);
+
+        let aad = wrap_aad(id, state_authenticated);
+        let ciphertext = cipher
+            .encrypt(Nonce::from_slice(&nonce_bytes), Payload { msg: key, aad: &aad })
+            .map_err(|e| AgentError::CryptoError(format!("Key wrapping failed: {}", e)))?;
+
+        let mut wrapped = nonce_bytes.to_vec();
+        wrapped.extend_from_slice(&ciphertext);
+        Ok(general_purpose::STANDARD.encode(wrapped))
+    }
+
+    fn unwrap_key(&self, id: &KekId, wrapped: &str, state_authenticated: bool) -> AgentResult<Zeroizing<[u8; 32]>> {
+        let wrapped = general_purpose::STANDARD
+            .decode(wrapped)
+            .map_err(|e| AgentError::CryptoError(format!("Invalid wrapped key: {}", e)))?;
+        if wrapped.len() < NONCE_LEN {
+            return Err(AgentError::CryptoError("Invalid wrapped key".to_string()));
+        }
+
+        let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(self.wrapping_key.as_ref()));
+        let aad = wrap_aad(id, state_authenticated);
+        let plaintext = Zeroizing::new(
+            cipher
+                .decrypt(
+                    Nonce::from_slice(&wrapped[..NONCE_LEN]),
+                    Payload { msg: &wrapped[NONCE_LEN..], aad: &aad },
+                )
+                .map_err(|_| {
+                    AgentError::CryptoError(format!(
+                        "Cannot unwrap key-encryption key {} (wrong seed or machine id, or altered key file?)",
+                        hex::encode(id)
+                    ))
+                })?,
+        );
+
+        let mut key = Zeroizing::new([0u8; 32]);
+        if plaintext.len() != key.len() {
+            return Err(AgentError::CryptoError("Invalid key-encryption key length".to_string()));
+        }
+        key.copy_from_slice(&plaintext);
+        Ok(key)
+    }
+}
+
+/// Charge la graine : variable d'environnement, puis fichier, puis valeur par défaut
+///
+/// Retourne la graine et `true` si c'est la graine par défaut, même recopiée
+/// dans la variable ou le fichier.
+fn load_seed(seed_file: Option<&Path>) -> AgentResult<(Zeroizing<Vec<u8>>, bool)> {
+    if let Ok(seed) = std::env::var(SEED_ENV_VAR) {
+        if !seed.is_empty() {
+            return Ok(check_default_seed(seed.into_bytes()));
+        }
+    }
+
+    if let Some(path) = seed_file {
+        let seed = std::fs::read(path).map_err(|e| {
+            AgentError::ConfigError(format!("Failed to read key seed {}: {}", path.display(), e))
+        })?;
+        if seed.iter().all(|b| b.is_ascii_whitespace()) {
+            return Err(AgentError::ConfigError(format!("Key seed {} is empty", path.display())));
+        }
+        return Ok(check_default_seed(seed));
+    }
+
+    Ok((Zeroizing::new(DEFAULT_SEED.as_bytes().to_vec()), true))
+}
+
+/// Reconnaît la graine par défaut (fin de ligne comprise) et la remplace par sa forme exacte
+fn check_default_seed(seed: Vec<u8>) -> (Zeroizing<Vec<u8>>, bool) {
+    let seed = Zeroizing::new(seed);
+    if seed.trim_ascii() == DEFAULT_SEED.as_bytes() {
+        return (Zeroizing::new(DEFAULT_SEED.as_bytes().to_vec()), true);
+    }
+    (seed, false)
+}
+
+/// Clé d'enveloppe des KEK : HKDF(graine, sel = identifiant machine)
+fn derive_wrapping_key(seed: &[u8], machine_id: &[u8]) -> AgentResult<Zeroizing<[u8; 32]>> {
+    let mut wrapping_key = Zeroizing::new([0u8; 32]);
+    Hkdf::<Sha256>::new(Some(machine_id), seed)
+        .expand(WRAP_INFO, wrapping_key.as_mut())
+        .map_err(|e| AgentError::CryptoError(format!("Key derivation failed: {}", e)))?;
+    Ok(wrapping_key)
+}
+
+fn wrap_aad(id: &KekId, state_authenticated: bool) -> Vec<u8> {
+    let mut aad = [WRAP_AAD, id.as_slice()].concat();
+    if state_authenticated {
+        aad.extend_from_slice(STATE_AUTHENTICATED_AAD);
+    }
+    aad
+}
+
+fn generate_kek() -> (KekId, KekEntry) {
+    let mut id = [0u8; KEK_ID_LEN];
+    let mut key = Zeroizing::new([0u8; 32]);
+    rand::thread_rng().fill_bytes(&mut id);
+    rand::thread_rng().fill_bytes(key.as_mut());
+    (id, KekEntry { key, created_at: Utc::now() })
+}
+
+fn parse_id(value: &str) -> AgentResult<KekId> {
+    hex::decode(value)
+        .ok()
+        .and_then(|bytes| KekId::try_from(bytes.as_slice()).ok())
+        .ok_or_else(|| AgentError::CryptoError(format!("Invalid key id: {}", value)))
+}
+
+fn kek_error(action: &str, path: &Path, error: std::io::Error) -> AgentError {
+    AgentError::InternalError(format!(
+        "Key hierarchy: failed to {} {}: {}",
+        action,
+        path.display(),
+        error
+    ))
+}
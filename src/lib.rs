@@ -1,13 +1,25 @@
+pub mod admin;
+pub mod api;
+pub mod app_auth;
 pub mod audit;
 pub mod cli;
 pub mod config;
 pub mod core;
 pub mod crypto;
+pub mod daemon;
+pub mod executable;
 pub mod ipc;
+pub mod kek;
 pub mod license;
 pub mod metrics;
+pub mod protocol;
+pub mod ratelimit;
+pub mod roles;
 pub mod rotation;
 pub mod secret;
+pub mod state;
+pub mod store;
+pub mod tls;
 pub mod tpm;
 pub mod types;
 
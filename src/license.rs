@@ -1,60 +1,311 @@
+use crate::crypto::{constant_time_compare, sha256};
+use crate::metrics::Metrics;
 use crate::secret::SecretManager;
-use crate::types::{AgentError, AgentResult, LicenseInfo, Secret, ValidationResult};
+use crate::types::{
+    AgentError, AgentResult, LicenseInfo, LicenseStatus, Secret, ValidationFailureReason,
+    ValidationResult,
+};
 use aes_gcm::{
-    aead::{Aead, KeyInit},
+    aead::{Aead, KeyInit, Payload},
     Aes256Gcm, Nonce,
 };
-use chrono::Utc;
-use std::sync::Arc;
+use chrono::{DateTime, Utc};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
 use base64::{engine::general_purpose, Engine as _};
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
+
+/// Magic des tokens de licence v2
+pub const TOKEN_MAGIC: &[u8; 4] = b"LSAT";
+
+/// Version courante du format de token
+pub const TOKEN_FORMAT_VERSION: u8 = 2;
+
+/// En-tête v2 : magic (4) | format (1) | version du secret (8, BE) | key ID (4)
+const TOKEN_V2_HEADER_LEN: usize = 4 + 1 + 8 + 4;
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+
+/// Identifiant court du secret ayant chiffré un token
+pub type KeyId = [u8; 4];
 
 /// Validateur de licences
 pub struct LicenseValidator {
     secret_manager: Arc<SecretManager>,
+    /// Accepte les tokens v1 (en-tête non authentifié)
+    accept_v1_tokens: bool,
+    /// Compteurs de validation, persistés avec le fichier d'état
+    stats: Arc<ValidationStats>,
+    metrics: Option<Arc<Metrics>>,
+}
+
+/// Compteurs de validation
+///
+/// Détenus par le `SecretManager` pour être sauvegardés avec l'état,
+/// mis à jour par le `LicenseValidator`.
+#[derive(Default)]
+pub struct ValidationStats {
+    total: AtomicU64,
+    successful: AtomicU64,
+    failed: AtomicU64,
+    /// Indexé comme `ValidationFailureReason::ALL`
+    failures_by_reason: [AtomicU64; ValidationFailureReason::ALL.len()],
+    last: Mutex<LastValidation>,
+}
+
+#[derive(Default)]
+struct LastValidation {
+    at: Option<DateTime<Utc>>,
+    error: Option<String>,
+}
+
+impl ValidationStats {
+    pub fn record_success(&self) {
+        self.total.fetch_add(1, Ordering::Relaxed);
+        self.successful.fetch_add(1, Ordering::Relaxed);
+        self.last.lock().unwrap().at = Some(Utc::now());
+    }
+
+    pub fn record_failure(&self, reason: ValidationFailureReason, error: &AgentError) {
+        self.total.fetch_add(1, Ordering::Relaxed);
+        self.failed.fetch_add(1, Ordering::Relaxed);
+        self.failures_by_reason[Self::reason_index(reason)].fetch_add(1, Ordering::Relaxed);
+
+        let mut last = self.last.lock().unwrap();
+        last.at = Some(Utc::now());
+        last.error = Some(error.to_string());
+    }
+
+    /// Copie cohérente à l'échelle de chaque compteur
+    pub fn snapshot(&self) -> LicenseStatus {
+        let last = self.last.lock().unwrap();
+        LicenseStatus {
+            last_validation: last.at,
+            total_validations: self.total.load(Ordering::Relaxed),
+            successful_validations: self.successful.load(Ordering::Relaxed),
+            failed_validations: self.failed.load(Ordering::Relaxed),
+            last_error: last.error.clone(),
+            failures_by_reason: ValidationFailureReason::ALL
+                .iter()
+                .map(|reason| (*reason, self.failures_by_reason[Self::reason_index(*reason)].load(Ordering::Relaxed)))
+                .filter(|(_, count)| *count > 0)
+                .collect(),
+        }
+    }
+
+    /// Restaure les compteurs sauvegardés
+    pub fn restore(&self, status: &LicenseStatus) {
+        self.total.store(status.total_validations, Ordering::Relaxed);
+        self.successful.store(status.successful_validations, Ordering::Relaxed);
+        self.failed.store(status.failed_validations, Ordering::Relaxed);
+        for reason in ValidationFailureReason::ALL {
+            let count = status.failures_by_reason.get(&reason).copied().unwrap_or(0);
+            self.failures_by_reason[Self::reason_index(reason)].store(count, Ordering::Relaxed);
+        }
+
+        let mut last = self.last.lock().unwrap();
+        last.at = status.last_validation;
+        last.error = status.last_error.clone();
+    }
+
+    fn reason_index(reason: ValidationFailureReason) -> usize {
+        ValidationFailureReason::ALL
+            .iter()
+            .position(|r| *r == reason)
+            .expect("reason listed in ValidationFailureReason::ALL")
+    }
+}
+
+/// Token de licence décodé (non déchiffré)
+struct ParsedToken<'a> {
+    format: u8,
+    secret_version: u64,
+    key_id: Option<KeyId>,
+    /// Données associées authentifiées (en-tête v2, vide en v1)
+    aad: &'a [u8],
+    nonce: &'a [u8],
+    /// Données chiffrées suivies du tag GCM
+    ciphertext: &'a [u8],
+}
+
+/// Identifiant d'un secret : 4 premiers octets de SHA-256("license-agent key-id" || secret)
+pub fn key_id(secret: &[u8]) -> KeyId {
+    let digest = sha256(&[b"license-agent key-id".as_slice(), secret].concat());
+    let mut id = [0u8; 4];
+    id.copy_from_slice(&digest[..4]);
+    id
+}
+
+/// Chiffre une licence (JSON) en token v2, sans encodage Base64
+///
+/// ```text
+/// magic "LSAT" (4) | format 2 (1) | version du secret (8, BE) | key ID (4) | nonce (12) | données chiffrées + tag
+/// ```
+///
+/// L'en-tête complet est authentifié comme données associées AES-GCM.
+pub fn encrypt_license_v2(secret: &[u8], secret_version: u64, license_json: &[u8]) -> AgentResult<Vec<u8>> {
+    use rand::RngCore;
+
+    let mut token = Vec::with_capacity(TOKEN_V2_HEADER_LEN + NONCE_LEN + license_json.len() + TAG_LEN);
+    token.extend_from_slice(TOKEN_MAGIC);
+    token.push(TOKEN_FORMAT_VERSION);
+    token.extend_from_slice(&secret_version.to_be_bytes());
+    token.extend_from_slice(&key_id(secret));
+
+    let mut nonce = [0u8; NONCE_LEN];
+    rand::thread_rng().fill_bytes(&mut nonce);
+
+    let cipher = Aes256Gcm::new_from_slice(secret)
+        .map_err(|_| AgentError::CryptoError("Invalid license secret length".to_string()))?;
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), Payload { msg: license_json, aad: &token })
+        .map_err(|e| AgentError::CryptoError(format!("License encryption failed: {}", e)))?;
+
+    token.extend_from_slice(&nonce);
+    token.extend_from_slice(&ciphertext);
+    Ok(token)
+}
+
+impl<'a> ParsedToken<'a> {
+    /// Décode un token v2, ou v1 (`version (8) | nonce (12) | données chiffrées + tag`)
+    fn parse(data: &'a [u8]) -> AgentResult<Self> {
+        let invalid = |reason: &str| AgentError::LicenseValidationFailed(format!("Invalid token format: {}", reason));
+
+        if data.starts_with(TOKEN_MAGIC) {
+            if data.len() < TOKEN_V2_HEADER_LEN + NONCE_LEN + TAG_LEN {
+                return Err(invalid("token too short"));
+            }
+            let format = data[4];
+            if format != TOKEN_FORMAT_VERSION {
+                return Err(invalid(&format!("unsupported format {}", format)));
+            }
+
+            let (header, rest) = data.split_at(TOKEN_V2_HEADER_LEN);
+            let mut version = [0u8; 8];
+            version.copy_from_slice(&header[5..13]);
+            let mut key_id = [0u8; 4];
+            key_id.copy_from_slice(&header[13..17]);
+
+            return Ok(Self {
+                format,
+                secret_version: u64::from_be_bytes(version),
+                key_id: Some(key_id),
+                aad: header,
+                nonce: &rest[..NONCE_LEN],
+                ciphertext: &rest[NONCE_LEN..],
+            });
+        }
+
+        if data.len() < 8 + NONCE_LEN + TAG_LEN {
+            return Err(AgentError::LicenseValidationFailed("Token too short".to_string()));
+        }
+
+        let mut version = [0u8; 8];
+        version.copy_from_slice(&data[..8]);
+        Ok(Self {
+            format: 1,
+            secret_version: u64::from_be_bytes(version),
+            key_id: None,
+            aad: &[],
+            nonce: &data[8..20],
+            ciphertext: &data[20..],
+        })
+    }
 }
 
 impl LicenseValidator {
-    pub fn new(secret_manager: Arc<SecretManager>) -> Self {
-        Self { secret_manager }
+    /// `accept_v1_tokens` : accepte les tokens v1, dont l'en-tête n'est pas authentifié
+    pub fn new(secret_manager: Arc<SecretManager>, accept_v1_tokens: bool) -> Self {
+        let stats = secret_manager.validation_stats();
+        Self {
+            secret_manager,
+            accept_v1_tokens,
+            stats,
+            metrics: None,
+        }
+    }
+
+    /// Publie durée et résultat des validations
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
     }
 
     /// Valide un token de licence
     pub async fn validate(&self, license_token: &[u8]) -> AgentResult<ValidationResult> {
         debug!("Validating license token ({} bytes)", license_token.len());
 
-        // 1. Décoder le token
-        let token_data = general_purpose::STANDARD
-            .decode(license_token)
-            .map_err(|e| AgentError::LicenseValidationFailed(format!("Invalid base64: {}", e)))?;
+        let start_time = std::time::Instant::now();
+        let result = self.validate_token(license_token).await;
+        if let Some(metrics) = &self.metrics {
+            metrics.record_validation(result.is_ok(), start_time.elapsed().as_secs_f64());
+        }
 
-        if token_data.len() < 13 {
-            return Err(AgentError::LicenseValidationFailed("Token too short".to_string()));
+        match result {
+            Ok(result) => {
+                self.stats.record_success();
+                Ok(result)
+            }
+            Err((reason, e)) => {
+                self.stats.record_failure(reason, &e);
+                Err(e)
+            }
         }
+    }
 
-        // 2. Extraire version, IV, ciphertext, auth_tag
-        let version_bytes = u64::from_be_bytes([
-            token_data[0], token_data[1], token_data[2], token_data[3],
-            token_data[4], token_data[5], token_data[6], token_data[7],
-        ]);
-        let version = version_bytes;
+    async fn validate_token(
+        &self,
+        license_token: &[u8],
+    ) -> Result<ValidationResult, (ValidationFailureReason, AgentError)> {
+        use ValidationFailureReason::*;
+
+        // 1. Décoder le token
+        let token_data = general_purpose::STANDARD
+            .decode(license_token)
+            .map_err(|e| (MalformedToken, AgentError::LicenseValidationFailed(format!("Invalid base64: {}", e))))?;
 
-        let iv = &token_data[8..20]; // 12 bytes
-        let ciphertext_with_tag = &token_data[20..];
+        // 2. Extraire en-tête, nonce et données chiffrées
+        let token = ParsedToken::parse(&token_data).map_err(|e| (MalformedToken, e))?;
 
-        if ciphertext_with_tag.len() < 16 {
-            return Err(AgentError::LicenseValidationFailed("Invalid token format".to_string()));
+        if token.format == 1 {
+            if !self.accept_v1_tokens {
+                return Err((
+                    DisabledFormat,
+                    AgentError::LicenseValidationFailed(
+                        "Legacy v1 license tokens are disabled (agent.accept_v1_tokens)".to_string(),
+                    ),
+                ));
+            }
+            warn!("Accepting legacy v1 license token (unauthenticated header)");
         }
 
-        let ciphertext = &ciphertext_with_tag[..ciphertext_with_tag.len() - 16];
-        let auth_tag = &ciphertext_with_tag[ciphertext_with_tag.len() - 16..];
-
         // 3. Récupérer le secret correspondant
-        let secret = self.get_secret_for_version(version).await?;
+        let secret = self
+            .get_secret_for_version(token.secret_version)
+            .await
+            .map_err(|e| match e {
+                AgentError::SecretNotFound(_) | AgentError::SecretExpired(_) | AgentError::SecretInvalid(_) => {
+                    (UnknownVersion, e)
+                }
+                e => (Other, e),
+            })?;
+
+        if let Some(expected) = token.key_id {
+            if !constant_time_compare(&expected, &key_id(&secret.data)) {
+                return Err((
+                    DecryptFailure,
+                    AgentError::LicenseValidationFailed(format!(
+                        "Key ID mismatch for secret version {}",
+                        token.secret_version
+                    )),
+                ));
+            }
+        }
 
         // 4. Déchiffrer la licence
-        let license_info = self.decrypt_license(&secret, iv, ciphertext, auth_tag, version)
-            .map_err(|e| AgentError::LicenseValidationFailed(format!("Decryption failed: {}", e)))?;
+        let license_info = self.decrypt_license(&secret, &token).map_err(|(reason, e)| {
+            (reason, AgentError::LicenseValidationFailed(format!("Decryption failed: {}", e)))
+        })?;
 
         // 5. Valider la licence (dates, règles métier)
         self.validate_license_rules(&license_info)?;
@@ -97,45 +348,33 @@ impl LicenseValidator {
     fn decrypt_license(
         &self,
         secret: &Secret,
-        iv: &[u8],
-        ciphertext: &[u8],
-        auth_tag: &[u8],
-        license_version: u64,
-    ) -> Result<LicenseInfo, String> {
-        // Reconstruire le ciphertext avec auth_tag pour GCM
-        let mut full_ciphertext = ciphertext.to_vec();
-        full_ciphertext.extend_from_slice(auth_tag);
-
-        let key = aes_gcm::Key::<Aes256Gcm>::from_slice(&secret.data);
-        let cipher = Aes256Gcm::new(key);
-        let nonce = Nonce::from_slice(iv);
-
-        // AAD (Additional Authenticated Data) : version du secret utilisée
-        // Cela garantit que le token ne peut pas être réutilisé avec un autre secret
-        let _aad = license_version.to_be_bytes();
-
-        // Déchiffrer
+        token: &ParsedToken<'_>,
+    ) -> Result<LicenseInfo, (ValidationFailureReason, String)> {
+        let cipher = Aes256Gcm::new_from_slice(&secret.data)
+            .map_err(|_| (ValidationFailureReason::Other, "Invalid secret length".to_string()))?;
+        let nonce = Nonce::from_slice(token.nonce);
+
+        // AAD : en-tête v2 complet (magic, format, version du secret, key ID).
+        // Un en-tête modifié ou un token présenté avec un autre secret est rejeté.
         let plaintext = cipher
-            .decrypt(nonce, full_ciphertext.as_ref())
-            .map_err(|e| format!("GCM decryption failed: {}", e))?;
+            .decrypt(nonce, Payload { msg: token.ciphertext, aad: token.aad })
+            .map_err(|e| (ValidationFailureReason::DecryptFailure, format!("GCM decryption failed: {}", e)))?;
 
         // Désérialiser JSON
         let license_info: LicenseInfo = serde_json::from_slice(&plaintext)
-            .map_err(|e| format!("Failed to parse license JSON: {}", e))?;
-
-        // Valider que la version dans la licence correspond
-        // (si stockée dans la licence elle-même)
+            .map_err(|e| (ValidationFailureReason::MalformedToken, format!("Failed to parse license JSON: {}", e)))?;
 
         Ok(license_info)
     }
 
-    fn validate_license_rules(&self, license: &LicenseInfo) -> AgentResult<()> {
+    fn validate_license_rules(&self, license: &LicenseInfo) -> Result<(), (ValidationFailureReason, AgentError)> {
         let now = Utc::now();
 
         // Vérifier expiration
         if now > license.expires_at {
-            return Err(AgentError::LicenseValidationFailed(
-                format!("License expired at {}", license.expires_at)
+            return Err((
+                ValidationFailureReason::Expired,
+                AgentError::LicenseValidationFailed(format!("License expired at {}", license.expires_at)),
             ));
         }
 
@@ -149,14 +388,7 @@ impl LicenseValidator {
     }
 
     /// Obtient les statistiques de validation
-    pub async fn get_stats(&self) -> crate::types::LicenseStatus {
-        // TODO: Implémenter compteurs réels
-        crate::types::LicenseStatus {
-            last_validation: Some(Utc::now()),
-            total_validations: 0,
-            successful_validations: 0,
-            failed_validations: 0,
-            last_error: None,
-        }
+    pub async fn get_stats(&self) -> LicenseStatus {
+        self.stats.snapshot()
     }
 }
@@ -1,6 +1,6 @@
 use prometheus::{
-    register_counter_with_registry, register_gauge_with_registry, register_histogram_with_registry,
-    Counter, Gauge, Histogram, Registry,
+    register_counter_vec_with_registry, register_counter_with_registry, register_gauge_with_registry,
+    register_histogram_with_registry, Counter, CounterVec, Gauge, Histogram, Registry,
 };
 use std::sync::Arc;
 
@@ -20,6 +20,7 @@ pub struct Metrics {
     pub degraded_mode_duration: Histogram,
     pub tpm_available: Gauge,
     pub last_rotation_timestamp: Gauge,
+    pub ipc_rate_limited: CounterVec,
 }
 
 impl Metrics {
@@ -95,6 +96,12 @@ impl Metrics {
                 "Timestamp of last rotation",
                 registry
             )?,
+            ipc_rate_limited: register_counter_vec_with_registry!(
+                "license_agent_ipc_rate_limited_total",
+                "IPC requests rejected by the rate limiter",
+                &["budget"],
+                registry
+            )?,
         })
     }
 
@@ -136,6 +143,11 @@ impl Metrics {
         }
     }
 
+    /// Enregistre une requête IPC refusée par le limiteur de débit
+    pub fn record_rate_limited(&self, budget: &str) {
+        self.ipc_rate_limited.with_label_values(&[budget]).inc();
+    }
+
     /// Met à jour le statut TPM
     pub fn update_tpm_status(&self, available: bool) {
         self.tpm_available.set(if available { 1.0 } else { 0.0 });
@@ -0,0 +1,406 @@
+use crate::audit::{AuditEvent, AuditLevel};
+use crate::roles::Role;
+use crate::types::{
+    AgentError, DegradedModeStatus, SystemStatus, TpmStatus, ValidateLicenseRequest,
+    ValidateLicenseResponse,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Version courante du protocole IPC
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Taille maximale d'une trame IPC (1 MiB)
+pub const MAX_FRAME_SIZE: usize = 1024 * 1024;
+
+/// Enveloppe d'une requête IPC
+///
+/// Format JSON : `{"version": 1, "id": 42, "command": "...", "data": {...}}`.
+/// Les champs `version` et `id` sont optionnels pour rester compatibles avec
+/// les clients qui n'envoient que `command` et `data`. Les commandes
+/// d'administration portent en plus un champ `auth` (voir [`AdminCredential`]).
+///
+/// Une connexion peut transporter plusieurs requêtes successives. Les requêtes
+/// portant un `id` peuvent être pipelinées : leurs réponses reprennent le même
+/// `id` et peuvent arriver dans un ordre différent. Les requêtes sans `id` sont
+/// traitées séquentiellement, dans l'ordre d'arrivée.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RequestEnvelope {
+    #[serde(default = "default_protocol_version")]
+    pub version: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<u64>,
+    #[serde(flatten)]
+    pub request: IpcRequest,
+    /// Identifiants d'administration
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth: Option<AdminCredential>,
+}
+
+/// Identifiants joints à une commande d'administration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "method", rename_all = "snake_case")]
+pub enum AdminCredential {
+    /// Jeton dont l'empreinte SHA-256 figure dans `admin_token_sha256`
+    Token { token: String },
+    /// Signature RSA-PSS de [`admin_challenge_message`] par la clé d'un certificat
+    /// de `admin_certificates`
+    Signature {
+        /// SHA-256 (hexadécimal) du certificat DER
+        certificate_sha256: String,
+        /// Challenge obtenu par `auth_challenge` sur la même connexion (hexadécimal)
+        challenge: String,
+        /// Signature en Base64
+        signature: String,
+    },
+}
+
+/// Message signé pour authentifier une commande d'administration
+///
+/// La signature couvre le challenge (usage unique, lié à la connexion) et le
+/// nom de la commande.
+pub fn admin_challenge_message(challenge: &[u8], command: &str) -> Vec<u8> {
+    [b"license-agent admin challenge v1\0".as_slice(), challenge, command.as_bytes()].concat()
+}
+
+/// Enveloppe d'une réponse IPC
+///
+/// En cas de succès : `{"version": 1, "command": "...", "data": {...}}`.
+/// En cas d'erreur : `{"version": 1, "error": {"code": "...", "message": "..."}}`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ResponseEnvelope {
+    pub version: u32,
+    /// Identifiant de la requête correspondante
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<u64>,
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    pub response: Option<IpcResponse>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<ErrorPayload>,
+}
+
+/// Commandes IPC (applications et administration)
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "command", content = "data", rename_all = "snake_case")]
+pub enum IpcRequest {
+    Validate(ValidateLicenseRequest),
+    Status {},
+    Rotate {
+        #[serde(default)]
+        force: bool,
+    },
+    Invalidate {
+        version: u64,
+        #[serde(default)]
+        reason: Option<String>,
+    },
+    Logs(LogsRequest),
+    Metrics {},
+    DegradedMode {
+        #[serde(default)]
+        enable: bool,
+        #[serde(default)]
+        disable: bool,
+        #[serde(default)]
+        reason: Option<String>,
+    },
+    TpmStatus {},
+    Reset {},
+    Rekey {},
+    Reseal {
+        #[serde(default)]
+        suspend_pcr_binding: bool,
+    },
+    AuthChallenge {},
+    RegisterApp {
+        app_id: String,
+    },
+}
+
+impl IpcRequest {
+    /// Nom de la commande (champ `command`)
+    pub fn command(&self) -> &'static str {
+        match self {
+            IpcRequest::Validate(_) => "validate",
+            IpcRequest::Status {} => "status",
+            IpcRequest::Rotate { .. } => "rotate",
+            IpcRequest::Invalidate { .. } => "invalidate",
+            IpcRequest::Logs(_) => "logs",
+            IpcRequest::Metrics {} => "metrics",
+            IpcRequest::DegradedMode { .. } => "degraded_mode",
+            IpcRequest::TpmStatus {} => "tpm_status",
+            IpcRequest::Reset {} => "reset",
+            IpcRequest::Rekey {} => "rekey",
+            IpcRequest::Reseal { .. } => "reseal",
+            IpcRequest::AuthChallenge {} => "auth_challenge",
+            IpcRequest::RegisterApp { .. } => "register_app",
+        }
+    }
+
+    /// Commande du rôle `admin` : identifiants d'administration exigés en plus du rôle
+    pub fn is_admin(&self) -> bool {
+        self.required_role() == Role::Admin
+    }
+
+    /// Rôle minimal requis pour exécuter la commande
+    pub fn required_role(&self) -> Role {
+        match self {
+            IpcRequest::Validate(_) | IpcRequest::AuthChallenge {} => Role::App,
+            IpcRequest::Status {}
+            | IpcRequest::Rotate { .. }
+            | IpcRequest::Logs(_)
+            | IpcRequest::Metrics {}
+            | IpcRequest::TpmStatus {} => Role::Operator,
+            IpcRequest::Invalidate { .. }
+            | IpcRequest::DegradedMode { .. }
+            | IpcRequest::Reset {}
+            | IpcRequest::Rekey {}
+            | IpcRequest::Reseal { .. }
+            | IpcRequest::RegisterApp { .. } => Role::Admin,
+        }
+    }
+}
+
+/// Réponses IPC, une variante par commande
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "command", content = "data", rename_all = "snake_case")]
+pub enum IpcResponse {
+    Validate(ValidateLicenseResponse),
+    Status(Box<SystemStatus>),
+    Rotate(RotateResult),
+    Invalidate(InvalidateResult),
+    Logs(LogsResult),
+    Metrics(MetricsResult),
+    DegradedMode(DegradedModeStatus),
+    TpmStatus(TpmStatus),
+    Reset(ResetResult),
+    Rekey(RekeyResult),
+    Reseal(ResealResult),
+    AuthChallenge(AuthChallengeResult),
+    RegisterApp(RegisterAppResult),
+}
+
+/// Filtres de la commande `logs`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LogsRequest {
+    #[serde(default = "default_logs_tail")]
+    pub tail: usize,
+    #[serde(default)]
+    pub event: Option<String>,
+    #[serde(default)]
+    pub level: Option<AuditLevel>,
+    #[serde(default)]
+    pub since: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RotateResult {
+    pub rotated: bool,
+    pub active_version: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InvalidateResult {
+    pub version: u64,
+    pub active_version: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LogsResult {
+    pub events: Vec<AuditEvent>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MetricsResult {
+    /// Métriques au format texte Prometheus
+    pub text: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ResetResult {
+    pub secrets_removed: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RekeyResult {
+    /// Identifiant de la nouvelle KEK
+    pub kek_id: String,
+    pub secrets_rewrapped: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ResealResult {
+    pub secrets_resealed: usize,
+    pub pcr_binding_suspended: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RegisterAppResult {
+    pub app_id: String,
+    /// Clé de l'application (Base64), à transmettre à l'application
+    pub key: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuthChallengeResult {
+    /// Challenge à signer (hexadécimal)
+    pub challenge: String,
+    pub expires_in_seconds: u64,
+}
+
+/// Codes d'erreur structurés renvoyés aux clients
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCode {
+    InvalidRequest,
+    UnsupportedVersion,
+    Unauthorized,
+    Forbidden,
+    RateLimited,
+    ReplayDetected,
+    SecretNotFound,
+    SecretExpired,
+    SecretInvalid,
+    LicenseValidationFailed,
+    TpmError,
+    NetworkError,
+    ConfigError,
+    IpcError,
+    RotationFailed,
+    CryptoError,
+    SignatureVerificationFailed,
+    InternalError,
+}
+
+/// Erreur renvoyée dans une réponse IPC
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ErrorPayload {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+impl ErrorPayload {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<&AgentError> for ErrorCode {
+    fn from(error: &AgentError) -> Self {
+        match error {
+            AgentError::SecretNotFound(_) => ErrorCode::SecretNotFound,
+            AgentError::SecretExpired(_) => ErrorCode::SecretExpired,
+            AgentError::SecretInvalid(_) => ErrorCode::SecretInvalid,
+            AgentError::LicenseValidationFailed(_) => ErrorCode::LicenseValidationFailed,
+            AgentError::TpmError(_) => ErrorCode::TpmError,
+            AgentError::NetworkError(_) => ErrorCode::NetworkError,
+            AgentError::ConfigError(_) => ErrorCode::ConfigError,
+            AgentError::IpcError(_) => ErrorCode::IpcError,
+            AgentError::RotationFailed(_) => ErrorCode::RotationFailed,
+            AgentError::CryptoError(_) => ErrorCode::CryptoError,
+            AgentError::SignatureVerificationFailed(_) => ErrorCode::SignatureVerificationFailed,
+            AgentError::InternalError(_) => ErrorCode::InternalError,
+        }
+    }
+}
+
+impl From<&AgentError> for ErrorPayload {
+    fn from(error: &AgentError) -> Self {
+        Self::new(ErrorCode::from(error), error.to_string())
+    }
+}
+
+impl ResponseEnvelope {
+    pub fn ok(id: Option<u64>, response: IpcResponse) -> Self {
+        Self {
+            version: PROTOCOL_VERSION,
+            id,
+            response: Some(response),
+            error: None,
+        }
+    }
+
+    pub fn error(id: Option<u64>, error: ErrorPayload) -> Self {
+        Self {
+            version: PROTOCOL_VERSION,
+            id,
+            response: None,
+            error: Some(error),
+        }
+    }
+
+    /// Convertit l'enveloppe en résultat
+    pub fn into_result(self) -> Result<IpcResponse, ErrorPayload> {
+        match (self.response, self.error) {
+            (_, Some(error)) => Err(error),
+            (Some(response), None) => Ok(response),
+            (None, None) => Err(ErrorPayload::new(
+                ErrorCode::InvalidRequest,
+                "Response contains neither data nor error",
+            )),
+        }
+    }
+}
+
+/// Lit une trame préfixée par sa longueur (u32 big-endian)
+pub async fn read_frame<R: AsyncRead + Unpin>(reader: &mut R) -> anyhow::Result<Vec<u8>> {
+    let len = read_frame_len(reader).await?;
+    read_frame_body(reader, len).await
+}
+
+/// Lit le préfixe de longueur d'une trame (attente de la requête suivante)
+pub async fn read_frame_len<R: AsyncRead + Unpin>(reader: &mut R) -> anyhow::Result<usize> {
+    let mut len_bytes = [0u8; 4];
+    reader.read_exact(&mut len_bytes).await?;
+    let len = u32::from_be_bytes(len_bytes) as usize;
+
+    if len > MAX_FRAME_SIZE {
+        return Err(anyhow::anyhow!("Frame too large: {} bytes", len));
+    }
+    Ok(len)
+}
+
+/// Lit le contenu d'une trame dont le préfixe a été lu
+pub async fn read_frame_body<R: AsyncRead + Unpin>(reader: &mut R, len: usize) -> anyhow::Result<Vec<u8>> {
+    let mut data = vec![0u8; len];
+    reader.read_exact(&mut data).await?;
+    Ok(data)
+}
+
+/// Indique si l'erreur correspond à une fermeture de la connexion par le pair
+pub fn is_connection_closed(error: &anyhow::Error) -> bool {
+    error
+        .downcast_ref::<std::io::Error>()
+        .is_some_and(|e| {
+            matches!(
+                e.kind(),
+                std::io::ErrorKind::UnexpectedEof
+                    | std::io::ErrorKind::ConnectionReset
+                    | std::io::ErrorKind::BrokenPipe
+            )
+        })
+}
+
+/// Écrit une trame préfixée par sa longueur (u32 big-endian)
+pub async fn write_frame<W: AsyncWrite + Unpin>(writer: &mut W, data: &[u8]) -> anyhow::Result<()> {
+    if data.len() > MAX_FRAME_SIZE {
+        return Err(anyhow::anyhow!("Frame too large: {} bytes", data.len()));
+    }
+
+    writer.write_all(&(data.len() as u32).to_be_bytes()).await?;
+    writer.write_all(data).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+fn default_protocol_version() -> u32 {
+    PROTOCOL_VERSION
+}
+
+fn default_logs_tail() -> usize {
+    100
+}
@@ -0,0 +1,209 @@
+use crate::audit::AuditLogger;
+use crate::config::ManagementConfig;
+use crate::metrics::Metrics;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+/// Budget d'un UID : multiple du budget d'un processus
+const UID_BUDGET_MULTIPLIER: u32 = 4;
+
+/// Budget d'administration par défaut (requêtes par minute et par processus)
+const DEFAULT_ADMIN_REQUESTS_PER_MINUTE: u64 = 30;
+
+/// Nombre de clés suivies au-delà duquel les entrées inactives sont purgées
+const MAX_TRACKED_KEYS: usize = 1024;
+
+/// Intervalle minimal entre deux événements d'audit pour un même UID et budget
+const AUDIT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Budget auquel une commande IPC est imputée
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RequestClass {
+    /// Validation de licence (applications)
+    Validation,
+    /// Commandes d'administration et de consultation
+    Admin,
+}
+
+impl RequestClass {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RequestClass::Validation => "validation",
+            RequestClass::Admin => "admin",
+        }
+    }
+}
+
+/// Limite par clé (GCRA) : `n` requêtes par minute, en rafale de `n` au plus
+struct KeyedLimit {
+    /// Intervalle d'émission : une minute / `n`
+    interval: Duration,
+    /// Avance maximale sur l'intervalle d'émission (une minute)
+    burst: Duration,
+    /// Heure d'arrivée théorique de la prochaine requête, par clé
+    arrivals: HashMap<u32, Instant>,
+}
+
+impl KeyedLimit {
+    fn new(requests_per_minute: u32) -> Self {
+        Self {
+            interval: Duration::from_secs(60) / requests_per_minute,
+            burst: Duration::from_secs(60),
+            arrivals: HashMap::new(),
+        }
+    }
+
+    /// Délai avant la prochaine requête autorisée pour `key` (nul si autorisée)
+    fn wait_time(&self, key: u32, now: Instant) -> Duration {
+        let arrival = self.arrivals.get(&key).map_or(now, |at| (*at).max(now));
+        (arrival + self.interval).saturating_duration_since(now + self.burst)
+    }
+
+    fn consume(&mut self, key: u32, now: Instant) {
+        let arrival = self.arrivals.get(&key).map_or(now, |at| (*at).max(now));
+        self.arrivals.insert(key, arrival + self.interval);
+    }
+
+    /// Oublie les clés revenues à leur budget complet
+    fn purge(&mut self, now: Instant) {
+        if self.arrivals.len() > MAX_TRACKED_KEYS {
+            self.arrivals.retain(|_, at| *at > now);
+        }
+    }
+}
+
+/// Limite de débit d'un budget, par processus et par UID
+struct Budget {
+    /// (par PID, par UID), vérifiés et consommés ensemble
+    limits: Mutex<(KeyedLimit, KeyedLimit)>,
+}
+
+impl Budget {
+    fn new(requests_per_minute: u64) -> Option<Self> {
+        let per_pid = u32::try_from(requests_per_minute).unwrap_or(u32::MAX);
+        if per_pid == 0 {
+            return None;
+        }
+        let per_uid = per_pid.saturating_mul(UID_BUDGET_MULTIPLIER);
+        Some(Self {
+            limits: Mutex::new((KeyedLimit::new(per_pid), KeyedLimit::new(per_uid))),
+        })
+    }
+
+    /// Consomme une requête, ou retourne le délai avant la prochaine autorisée
+    ///
+    /// Rien n'est consommé si l'une des deux limites refuse : une requête
+    /// refusée pour l'UID n'entame pas le budget du processus, et inversement.
+    fn check(&self, uid: u32, pid: u32) -> Result<(), Duration> {
+        let mut limits = self.limits.lock().unwrap();
+        let (per_pid, per_uid) = &mut *limits;
+        let now = Instant::now();
+        per_pid.purge(now);
+        per_uid.purge(now);
+
+        let wait = per_pid.wait_time(pid, now).max(per_uid.wait_time(uid, now));
+        if !wait.is_zero() {
+            return Err(wait);
+        }
+        per_pid.consume(pid, now);
+        per_uid.consume(uid, now);
+        Ok(())
+    }
+}
+
+/// Limiteur de débit des clients IPC
+///
+/// Les validations et les commandes d'administration ont des budgets séparés :
+/// une application qui multiplie les validations ne bloque pas l'administration,
+/// et inversement. Chaque budget s'applique par processus (PID) et, multiplié
+/// par `UID_BUDGET_MULTIPLIER`, à l'ensemble des processus d'un même UID.
+pub struct IpcRateLimiter {
+    validation: Option<Budget>,
+    admin: Option<Budget>,
+    audit: Option<Arc<AuditLogger>>,
+    metrics: Option<Arc<Metrics>>,
+    last_reported: Mutex<HashMap<(RequestClass, u32), Instant>>,
+}
+
+impl IpcRateLimiter {
+    /// `None` ou `0` : budget illimité
+    pub fn new(validation_per_minute: Option<u64>, admin_per_minute: Option<u64>) -> Self {
+        Self {
+            validation: validation_per_minute.and_then(Budget::new),
+            admin: admin_per_minute.and_then(Budget::new),
+            audit: None,
+            metrics: None,
+            last_reported: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn from_config(config: &ManagementConfig) -> Self {
+        Self::new(
+            config.rate_limit_requests_per_minute,
+            Some(
+                config
+                    .admin_rate_limit_requests_per_minute
+                    .unwrap_or(DEFAULT_ADMIN_REQUESTS_PER_MINUTE),
+            ),
+        )
+    }
+
+    pub fn with_audit(mut self, audit: Arc<AuditLogger>) -> Self {
+        self.audit = Some(audit);
+        self
+    }
+
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Impute une requête au budget `class`
+    ///
+    /// Retourne le délai avant la prochaine requête autorisée si la limite est atteinte.
+    pub async fn check(&self, class: RequestClass, uid: u32, pid: u32, command: &str) -> Result<(), Duration> {
+        let budget = match class {
+            RequestClass::Validation => &self.validation,
+            RequestClass::Admin => &self.admin,
+        };
+        let Some(budget) = budget else {
+            return Ok(());
+        };
+
+        let Err(retry_after) = budget.check(uid, pid) else {
+            return Ok(());
+        };
+
+        if let Some(metrics) = &self.metrics {
+            metrics.record_rate_limited(class.as_str());
+        }
+
+        // Un seul événement d'audit par minute et par UID pour ne pas saturer le journal
+        let report = {
+            let mut last_reported = self.last_reported.lock().unwrap();
+            let now = Instant::now();
+            last_reported.retain(|_, at| now.duration_since(*at) < AUDIT_INTERVAL);
+            match last_reported.entry((class, uid)) {
+                std::collections::hash_map::Entry::Occupied(_) => false,
+                std::collections::hash_map::Entry::Vacant(entry) => {
+                    entry.insert(now);
+                    true
+                }
+            }
+        };
+
+        if report {
+            warn!(
+                "Rate limit exceeded for UID {} (PID {}) on {} budget ({})",
+                uid, pid, class.as_str(), command
+            );
+            if let Some(audit) = &self.audit {
+                audit.rate_limit_exceeded(uid, pid, class.as_str(), command).await;
+            }
+        }
+
+        Err(retry_after)
+    }
+}
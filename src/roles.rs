@@ -0,0 +1,180 @@
+use crate::config::{ManagementConfig, RoleBinding};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Rôle d'un client IPC, du moins au plus privilégié
+///
+/// Un rôle donne accès aux commandes des rôles inférieurs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    /// Applications : validation de licence
+    App,
+    /// Exploitation : statut, journaux, métriques, rotation
+    Operator,
+    /// Administration : invalidation, réinitialisation, mode dégradé, KEK et TPM
+    Admin,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::App => "app",
+            Role::Operator => "operator",
+            Role::Admin => "admin",
+        }
+    }
+}
+
+impl std::fmt::Display for Role {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Identité du processus client d'une connexion IPC
+#[derive(Debug, Clone)]
+pub struct Peer {
+    pub uid: u32,
+    pub pid: u32,
+    /// GID principal et groupes supplémentaires
+    pub gids: Vec<u32>,
+    /// Exécutable du processus (`/proc/<pid>/exe`)
+    pub executable: Option<PathBuf>,
+    /// Date de démarrage du processus, voir [`process_start_time`]
+    pub start_time: Option<u64>,
+}
+
+impl Peer {
+    /// Complète les identifiants SO_PEERCRED avec les groupes et l'exécutable lus dans /proc
+    pub fn from_credentials(uid: u32, gid: u32, pid: u32) -> Self {
+        let proc_dir = PathBuf::from(format!("/proc/{}", pid));
+        // Lue en premier : un PID réattribué pendant la lecture de /proc sera détecté
+        let start_time = process_start_time(pid);
+
+        let mut gids = vec![gid];
+        if let Ok(status) = std::fs::read_to_string(proc_dir.join("status")) {
+            let groups = status
+                .lines()
+                .find_map(|line| line.strip_prefix("Groups:"))
+                .unwrap_or_default();
+            gids.extend(groups.split_whitespace().filter_map(|g| g.parse::<u32>().ok()));
+        }
+        gids.sort_unstable();
+        gids.dedup();
+
+        Self {
+            uid,
+            pid,
+            gids,
+            executable: std::fs::read_link(proc_dir.join("exe")).ok(),
+            start_time,
+        }
+    }
+
+    /// Le processus est toujours celui de la connexion (PID non réattribué)
+    pub fn is_same_process(&self) -> bool {
+        self.start_time.is_some() && process_start_time(self.pid) == self.start_time
+    }
+
+    /// Identité de l'appelant pour le journal d'audit
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "transport": "ipc",
+            "uid": self.uid,
+            "pid": self.pid,
+            "executable": self.executable.as_ref().map(|path| path.display().to_string()),
+        })
+    }
+}
+
+/// Date de démarrage d'un processus (`/proc/<pid>/stat`, champ 22, en ticks depuis le boot)
+///
+/// Distingue un processus d'un autre qui aurait repris son PID.
+pub fn process_start_time(pid: u32) -> Option<u64> {
+    let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    // Le nom du processus (champ 2) peut contenir espaces et parenthèses
+    stat.rsplit_once(')')?.1.split_whitespace().nth(19)?.parse().ok()
+}
+
+/// Règles d'attribution d'un rôle
+struct RoleRule {
+    role: Role,
+    uids: Vec<u32>,
+    gids: Vec<u32>,
+    executables: Vec<PathBuf>,
+    /// Tout client obtient ce rôle (`allowed_uids` vide sans section `[management.roles]`)
+    any: bool,
+}
+
+impl RoleRule {
+    fn new(role: Role, binding: &RoleBinding) -> Self {
+        Self {
+            role,
+            uids: binding.uids.clone(),
+            gids: binding.gids.clone(),
+            // Comparaison avec la cible résolue de /proc/<pid>/exe
+            executables: binding
+                .executables
+                .iter()
+                .map(|path| std::fs::canonicalize(path).unwrap_or_else(|_| path.clone()))
+                .collect(),
+            any: false,
+        }
+    }
+
+    fn matches(&self, peer: &Peer) -> bool {
+        self.any
+            || self.uids.contains(&peer.uid)
+            || peer.gids.iter().any(|gid| self.gids.contains(gid))
+            || peer
+                .executable
+                .as_deref()
+                .is_some_and(|exe| self.executables.iter().any(|path| path.as_path() == exe))
+    }
+}
+
+/// Attribution des rôles aux clients IPC
+///
+/// Un client obtient le rôle le plus élevé dont l'une des règles (UID, GID ou
+/// exécutable) le désigne. Sans section `[management.roles]`, `allowed_uids`
+/// donne le rôle `app` et `admin_uids` le rôle `admin`.
+pub struct RoleResolver {
+    rules: Vec<RoleRule>,
+}
+
+impl RoleResolver {
+    pub fn from_config(config: &ManagementConfig) -> Self {
+        let rules = match &config.roles {
+            Some(roles) => vec![
+                RoleRule::new(Role::Admin, &roles.admin),
+                RoleRule::new(Role::Operator, &roles.operator),
+                RoleRule::new(Role::App, &roles.app),
+            ],
+            None => {
+                let admin = RoleBinding {
+                    uids: config.admin_uids.clone(),
+                    ..Default::default()
+                };
+                let app = RoleBinding {
+                    uids: config.allowed_uids.clone(),
+                    ..Default::default()
+                };
+                vec![
+                    RoleRule::new(Role::Admin, &admin),
+                    RoleRule {
+                        any: config.allowed_uids.is_empty(),
+                        ..RoleRule::new(Role::App, &app)
+                    },
+                ]
+            }
+        };
+
+        Self { rules }
+    }
+
+    /// Rôle du client, `None` s'il n'est pas autorisé à se connecter
+    pub fn resolve(&self, peer: &Peer) -> Option<Role> {
+        self.rules.iter().filter(|rule| rule.matches(peer)).map(|rule| rule.role).max()
+    }
+}
@@ -1,11 +1,13 @@
 use crate::audit::AuditLogger;
 use crate::config::Config;
-use crate::crypto::CryptoManager;
+use crate::crypto::{self, CryptoManager};
+use crate::metrics::Metrics;
 use crate::secret::SecretManager;
 use crate::types::{AgentError, AgentResult, RotationSource, Secret, SecretMetadata, SecretState};
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, SecondsFormat, Utc};
 use rand::Rng;
 use reqwest::Client;
+use rsa::RsaPublicKey;
 use std::sync::Arc;
 use std::time::Duration;
 use base64::{engine::general_purpose, Engine as _};
@@ -18,9 +20,11 @@ pub struct RotationManager {
     audit: Arc<AuditLogger>,
     client: Client,
     crypto: Arc<CryptoManager>,
+    server_public_key: Option<RsaPublicKey>,
     rotation_in_progress: Arc<tokio::sync::Mutex<bool>>,
     max_retries: u32,
     base_retry_delay_seconds: u64,
+    metrics: Option<Arc<Metrics>>,
 }
 
 #[derive(Debug, serde::Serialize)]
@@ -33,14 +37,63 @@ struct RotateSecretRequest {
     agent_public_key: String,
 }
 
-#[derive(Debug, serde::Deserialize)]
-struct RotateSecretResponse {
-    new_secret_encrypted: String, // Base64
-    version: u64,
-    valid_from: DateTime<Utc>,
-    valid_until: DateTime<Utc>,
-    grace_until: DateTime<Utc>,
-    signature: String,
+/// Réponse de rotation renvoyée par le serveur
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct RotateSecretResponse {
+    /// Agent destinataire et nonce (hexadécimal) de la requête, renvoyés par le serveur
+    pub agent_id: String,
+    pub nonce: String,
+    pub new_secret_encrypted: String, // Base64
+    pub version: u64,
+    pub valid_from: DateTime<Utc>,
+    pub valid_until: DateTime<Utc>,
+    pub grace_until: DateTime<Utc>,
+    /// Signature RSA-PSS (Base64) de `canonical_bytes()`
+    pub signature: String,
+}
+
+impl RotateSecretResponse {
+    /// Sérialisation canonique signée par le serveur
+    ///
+    /// Objet JSON compact, clés triées, sans le champ `signature`, dates en
+    /// RFC 3339 UTC à la nanoseconde (`2024-01-01T00:00:00.000000000Z`).
+    /// L'agent et le nonce lient la réponse à une requête : elle ne peut pas
+    /// être rejouée vers un autre agent ou lors d'une rotation ultérieure.
+    pub fn canonical_bytes(&self) -> Vec<u8> {
+        let timestamp = |d: &DateTime<Utc>| d.to_rfc3339_opts(SecondsFormat::Nanos, true);
+        let string = |s: &str| serde_json::Value::String(s.to_string());
+        format!(
+            "{{\"agent_id\":{},\"grace_until\":\"{}\",\"new_secret_encrypted\":{},\"nonce\":{},\"valid_from\":\"{}\",\"valid_until\":\"{}\",\"version\":{}}}",
+            string(&self.agent_id),
+            timestamp(&self.grace_until),
+            string(&self.new_secret_encrypted),
+            string(&self.nonce),
+            timestamp(&self.valid_from),
+            timestamp(&self.valid_until),
+            self.version,
+        )
+        .into_bytes()
+    }
+
+    /// Vérifie que la réponse (signature déjà vérifiée) répond à cette requête
+    ///
+    /// `agent_id` et `nonce` (hexadécimal) sont ceux de la requête ; la version
+    /// reçue doit être strictement supérieure à `latest_version`, la plus haute
+    /// connue de l'agent.
+    pub fn check_request_binding(&self, agent_id: &str, nonce: &str, latest_version: u64) -> AgentResult<()> {
+        if self.agent_id != agent_id || self.nonce != nonce {
+            return Err(AgentError::SignatureVerificationFailed(
+                "Rotation response does not match this request (replayed?)".to_string(),
+            ));
+        }
+        if self.version <= latest_version {
+            return Err(AgentError::RotationFailed(format!(
+                "Rotation response version {} is not newer than version {}",
+                self.version, latest_version
+            )));
+        }
+        Ok(())
+    }
 }
 
 impl RotationManager {
@@ -50,19 +103,31 @@ impl RotationManager {
         audit: Arc<AuditLogger>,
         crypto: Arc<CryptoManager>,
     ) -> anyhow::Result<Self> {
+        // Client HTTPS : mTLS avec le certificat agent + pinning du certificat serveur
+        let tls_config = crate::tls::build_client_config(&config.server)?;
         let client = Client::builder()
             .timeout(Duration::from_secs(config.server.timeout_seconds.unwrap_or(30)))
+            .use_preconfigured_tls(tls_config)
             .build()?;
 
+        let server_public_key = config
+            .server
+            .public_key
+            .as_deref()
+            .map(crypto::load_public_key_pem)
+            .transpose()?;
+
         Ok(Self {
             config,
             secret_manager,
             audit,
             client,
             crypto,
+            server_public_key,
             rotation_in_progress: Arc::new(tokio::sync::Mutex::new(false)),
             max_retries: 3,
             base_retry_delay_seconds: 1,
+            metrics: None,
         })
     }
 
@@ -78,6 +143,12 @@ impl RotationManager {
         self
     }
 
+    /// Publie durée et résultat des rotations
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
     /// Vérifie si une rotation est nécessaire
     pub async fn check_rotation_needed(&self) -> bool {
         if let Some(active_version) = self.secret_manager.active_version() {
@@ -112,6 +183,7 @@ impl RotationManager {
         }
 
         let start_time = std::time::Instant::now();
+        let old_version = self.secret_manager.active_version().unwrap_or(0);
 
         // Libérer le lock en cas d'erreur
         let result = self.do_rotate(force).await;
@@ -124,13 +196,18 @@ impl RotationManager {
         match &result {
             Ok(_) => {
                 let duration = start_time.elapsed().as_millis() as u64;
-                let old_version = self.secret_manager.active_version().unwrap_or(0);
                 let new_version = self.secret_manager.active_version().unwrap_or(0);
                 self.audit.rotation_succeeded(old_version, new_version, duration).await;
+                if let Some(metrics) = &self.metrics {
+                    metrics.record_rotation_success(start_time.elapsed().as_secs_f64());
+                }
                 info!("Rotation completed in {}ms", duration);
             }
             Err(e) => {
                 self.audit.rotation_failed("rotation_error", &e.to_string()).await;
+                if let Some(metrics) = &self.metrics {
+                    metrics.record_rotation_failure();
+                }
                 error!("Rotation failed: {}", e);
             }
         }
@@ -176,13 +253,22 @@ impl RotationManager {
         // 3. Envoyer requête au serveur
         let response = self.send_rotation_request(&request).await?;
 
-        // 4. Vérifier signature réponse
-        // Note: Nécessite clé publique serveur dans config
-        // Pour l'instant, on assume que la signature est valide si présente
-        if !response.signature.is_empty() {
-            // TODO: Charger clé publique serveur et vérifier signature
-            debug!("Server signature present (verification not yet implemented)");
+        // 4. Vérifier signature réponse et son lien avec la requête (anti-rejeu)
+        let latest_version = self
+            .secret_manager
+            .list_versions()
+            .into_iter()
+            .max()
+            .unwrap_or(0)
+            .max(current_version);
+        let verified = self
+            .verify_response_signature(&response)
+            .and_then(|_| response.check_request_binding(&request.agent_id, &request.nonce, latest_version));
+        if let Err(e) = verified {
+            self.audit.rotation_signature_invalid(response.version, &e.to_string()).await;
+            return Err(e);
         }
+        debug!("Server signature verified for version {}", response.version);
 
         // 5. Déchiffrer nouveau secret avec RSA-OAEP
         let new_secret_encrypted = general_purpose::STANDARD
@@ -287,6 +373,33 @@ impl RotationManager {
         }))
     }
 
+    /// Vérifie la signature RSA-PSS du serveur sur la réponse de rotation
+    fn verify_response_signature(&self, response: &RotateSecretResponse) -> AgentResult<()> {
+        let public_key = self.server_public_key.as_ref().ok_or_else(|| {
+            AgentError::SignatureVerificationFailed(
+                "Server public key not configured (server.public_key)".to_string(),
+            )
+        })?;
+
+        if response.signature.is_empty() {
+            return Err(AgentError::SignatureVerificationFailed(
+                "Missing server signature".to_string(),
+            ));
+        }
+
+        let signature = general_purpose::STANDARD
+            .decode(&response.signature)
+            .map_err(|e| AgentError::SignatureVerificationFailed(format!("Invalid signature encoding: {}", e)))?;
+
+        if !crypto::verify_pss_with_key(public_key, &response.canonical_bytes(), &signature)? {
+            return Err(AgentError::SignatureVerificationFailed(
+                "Server signature does not match response".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
     fn generate_nonce(&self) -> Vec<u8> {
         let mut nonce = vec![0u8; 16];
         rand::thread_rng().fill(&mut nonce[..]);
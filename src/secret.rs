@@ -1,45 +1,116 @@
-use crate::tpm::TpmManager;
-use crate::types::{AgentError, AgentResult, Secret, SecretMetadata, SecretState};
+use crate::license::ValidationStats;
+use crate::state::StateStore;
+use crate::store::SealedStore;
+use crate::tpm::{TpmManager, NV_SLOT_MAX, NV_STATE_SLOT};
+use crate::types::{AgentError, AgentResult, LicenseStatus, Secret, SecretMetadata, SecretState, TamperStatus};
 use chrono::{DateTime, Utc};
+use rand::RngCore;
 use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
-use tracing::{debug, error, info, warn};
+use tracing::{error, info, warn};
+use zeroize::Zeroizing;
+
+/// Version réservée au scellement de la clé d'intégrité du fichier d'état
+///
+/// Sert uniquement à la dérivation de la clé de données ; la clé scellée est
+/// stockée dans l'emplacement NV [`NV_STATE_SLOT`] ou dans `<état>.key`.
+const STATE_KEY_VERSION: u64 = u64::MAX;
 
 /// Gestionnaire de secrets
 pub struct SecretManager {
     tpm: Arc<TpmManager>,
+    store: SealedStore,
     secrets: Arc<Mutex<HashMap<u64, SecretMetadata>>>,
     active_version: Arc<Mutex<Option<u64>>>,
-    state_path: PathBuf,
+    state: StateStore,
+    /// Sérialise l'écriture des secrets et le rechiffrement (`rekey`)
+    write_lock: tokio::sync::Mutex<()>,
+    /// Statistiques de validation, sauvegardées avec l'état
+    validation_stats: Arc<ValidationStats>,
+    /// Altération du fichier d'état détectée : aucun secret n'est servi jusqu'au `reset`
+    tamper: Mutex<Option<TamperStatus>>,
+    /// Clé du MAC de l'état non descellable : état laissé intact sur le disque, non chargé
+    state_key_failure: Mutex<Option<String>>,
 }
 
 impl SecretManager {
-    pub fn new(tpm: Arc<TpmManager>, state_path: PathBuf) -> Self {
+    /// `store_path` : répertoire des secrets scellés, utilisé quand le TPM est indisponible
+    pub fn new(tpm: Arc<TpmManager>, state_path: PathBuf, store_path: PathBuf) -> Self {
         Self {
             tpm,
+            store: SealedStore::new(store_path),
             secrets: Arc::new(Mutex::new(HashMap::new())),
             active_version: Arc::new(Mutex::new(None)),
-            state_path,
+            state: StateStore::new(state_path),
+            write_lock: tokio::sync::Mutex::new(()),
+            validation_stats: Arc::new(ValidationStats::default()),
+            tamper: Mutex::new(None),
+            state_key_failure: Mutex::new(None),
         }
     }
 
-    /// Charge l'état depuis le disque
+    /// Charge l'état depuis le disque (ou sa sauvegarde si le fichier est corrompu)
+    ///
+    /// Le MAC de l'état est vérifié avec la clé scellée par le TPM (ou la KEK).
+    /// Un MAC invalide déclenche la réponse à l'altération (voir
+    /// [`Self::tamper_status`]) au lieu d'une erreur. Une clé qui ne peut pas
+    /// être descellée (PCR modifiés, erreur TPM) n'est pas une altération :
+    /// l'état n'est pas chargé, ni réécrit, et aucun secret n'est servi (voir
+    /// [`Self::state_key_failure`]) jusqu'au retour de l'état de démarrage scellé.
+    ///
+    /// Une fois l'état authentifié (marqueur du fichier de KEK), une clé
+    /// manquante est aussi une altération : supprimer la clé ne permet pas de
+    /// faire accepter un état d'un schéma antérieur, sans MAC.
     pub async fn load_state(&self) -> AgentResult<()> {
-        if !self.state_path.exists() {
-            info!("State file does not exist, starting fresh");
-            return Ok(());
+        let kek = self.tpm.kek();
+        match self.read_sealed_state_key().await? {
+            Some(sealed) => match self.unseal_state_key(&sealed) {
+                Ok(key) => {
+                    self.state.set_mac_key(key);
+                    kek.mark_state_authenticated()?;
+                }
+                Err(e) => {
+                    error!("State not loaded: {}", e);
+                    *self.state_key_failure.lock().unwrap() = Some(e.to_string());
+                    return Ok(());
+                }
+            },
+            None if kek.state_authenticated() => {
+                return self
+                    .enter_tamper_response("State integrity key is missing".to_string(), true)
+                    .await;
+            }
+            // Premier démarrage ou état antérieur au MAC : migration, clé créée à la première écriture
+            None => {}
         }
 
-        let content = tokio::fs::read_to_string(&self.state_path)
-            .await
-            .map_err(|e| AgentError::InternalError(format!("Failed to read state: {}", e)))?;
-
-        let state: StateFile = serde_json::from_str(&content)
-            .map_err(|e| AgentError::InternalError(format!("Failed to parse state: {}", e)))?;
+        let state = match self.state.load::<StateFile>().await {
+            Ok(Some(state)) => state,
+            Ok(None) => {
+                info!("State file does not exist, starting fresh");
+                return Ok(());
+            }
+            Err(AgentError::SignatureVerificationFailed(reason)) => {
+                return self.enter_tamper_response(reason, false).await;
+            }
+            Err(e) => return Err(e),
+        };
 
         *self.secrets.lock().unwrap() = state.secrets;
         *self.active_version.lock().unwrap() = state.active_version;
+        if let Some(stats) = &state.validation_stats {
+            self.validation_stats.restore(stats);
+        }
+        if let Some(tamper) = &state.tamper {
+            error!("Tamper response active since {}: {}", tamper.detected_at.to_rfc3339(), tamper.reason);
+        }
+        *self.tamper.lock().unwrap() = state.tamper;
+
+        // État migré depuis un schéma non authentifié : MAC immédiat
+        if !self.state.has_mac_key() {
+            self.save_state().await?;
+        }
 
         let secrets_len = self.secrets.lock().unwrap().len();
         let active_ver = *self.active_version.lock().unwrap();
@@ -49,44 +120,123 @@ impl SecretManager {
         Ok(())
     }
 
-    /// Sauvegarde l'état sur le disque
+    /// Sauvegarde l'état sur le disque (écriture atomique)
     pub async fn save_state(&self) -> AgentResult<()> {
+        // L'état sur disque n'a pas été chargé : il ne doit pas être remplacé
+        if let Some(failure) = self.state_key_failure() {
+            return Err(AgentError::TpmError(failure));
+        }
+
         let secrets = self.secrets.lock().unwrap().clone();
         let active_version = *self.active_version.lock().unwrap();
         let state = StateFile {
             secrets,
             active_version,
+            validation_stats: Some(self.validation_stats.snapshot()),
+            tamper: self.tamper.lock().unwrap().clone(),
             last_updated: Utc::now(),
         };
 
-        let content = serde_json::to_string_pretty(&state)
-            .map_err(|e| AgentError::InternalError(format!("Failed to serialize state: {}", e)))?;
+        // Écriture avant `load_state` (outils, tests) : l'état reste authentifié
+        if !self.state.has_mac_key() {
+            self.load_state_key().await?;
+        }
+        self.state.save(&state).await
+    }
+
+    /// Réponse à l'altération en cours, `None` en fonctionnement normal
+    pub fn tamper_status(&self) -> Option<TamperStatus> {
+        self.tamper.lock().unwrap().clone()
+    }
+
+    /// Échec du descellement de la clé du MAC de l'état au démarrage, `None` en fonctionnement normal
+    pub fn state_key_failure(&self) -> Option<String> {
+        self.state_key_failure.lock().unwrap().clone()
+    }
 
-        // Créer répertoire si nécessaire
-        if let Some(parent) = self.state_path.parent() {
-            tokio::fs::create_dir_all(parent)
-                .await
-                .map_err(|e| AgentError::InternalError(format!("Failed to create state dir: {}", e)))?;
+    /// Réponse à une altération : état écarté pour analyse, secrets refusés
+    ///
+    /// L'état altéré n'est pas chargé. Un nouvel état, qui enregistre
+    /// l'altération, est écrit pour que la réponse survive au redémarrage ;
+    /// seul un `reset` y met fin.
+    async fn enter_tamper_response(&self, reason: String, key_unusable: bool) -> AgentResult<()> {
+        error!("State file tampering detected: {}", reason);
+
+        let quarantined = self.state.quarantine(key_unusable).await?;
+        if key_unusable {
+            self.state.set_mac_key(self.create_state_key().await?);
         }
 
-        tokio::fs::write(&self.state_path, content)
-            .await
-            .map_err(|e| AgentError::InternalError(format!("Failed to write state: {}", e)))?;
+        self.secrets.lock().unwrap().clear();
+        *self.active_version.lock().unwrap() = None;
+        *self.tamper.lock().unwrap() = Some(TamperStatus {
+            detected_at: Utc::now(),
+            reason,
+            quarantined: quarantined.iter().map(|p| p.display().to_string()).collect(),
+        });
 
-        debug!("State saved to {}", self.state_path.display());
+        self.save_state().await
+    }
+
+    /// Descelle la clé du MAC de l'état, ou la crée au premier démarrage
+    async fn load_state_key(&self) -> AgentResult<()> {
+        let key = match self.read_sealed_state_key().await? {
+            Some(sealed) => self.unseal_state_key(&sealed)?,
+            None => self.create_state_key().await?,
+        };
+        self.state.set_mac_key(key);
         Ok(())
     }
 
+    /// Un échec est une `TpmError` : politique PCR non satisfaite, TPM ou KEK indisponible
+    fn unseal_state_key(&self, sealed: &[u8]) -> AgentResult<Zeroizing<Vec<u8>>> {
+        self.tpm.decrypt(STATE_KEY_VERSION, sealed).map(Zeroizing::new).map_err(|e| {
+            AgentError::TpmError(format!("State integrity key cannot be unsealed: {}", e))
+        })
+    }
+
+    async fn create_state_key(&self) -> AgentResult<Zeroizing<Vec<u8>>> {
+        let mut key = Zeroizing::new(vec![0u8; 32]);
+        rand::thread_rng().fill_bytes(&mut key);
+        self.seal_state_key(&key).await?;
+        info!("State integrity key created");
+        Ok(key)
+    }
+
+    async fn seal_state_key(&self, key: &[u8]) -> AgentResult<()> {
+        let sealed = self.tpm.encrypt(STATE_KEY_VERSION, key)
+            .map_err(|e| AgentError::TpmError(format!("Failed to seal state key: {}", e)))?;
+        if self.tpm.is_available() {
+            self.tpm.nv_write(NV_STATE_SLOT, &sealed)
+                .map_err(|e| AgentError::TpmError(format!("Failed to store state key in TPM: {}", e)))?;
+        } else {
+            self.state.write_sealed_key(&sealed).await?;
+        }
+        // Dès qu'une clé existe, son absence sera traitée comme une altération
+        self.tpm.kek().mark_state_authenticated()
+    }
+
+    /// Clé du MAC scellée : emplacement NV réservé avec le TPM, fichier `<état>.key` sinon
+    async fn read_sealed_state_key(&self) -> AgentResult<Option<Vec<u8>>> {
+        if self.tpm.is_available() {
+            return self.tpm.nv_read(NV_STATE_SLOT)
+                .map_err(|e| AgentError::TpmError(format!("Failed to read state key from TPM: {}", e)));
+        }
+        self.state.read_sealed_key().await
+    }
+
     /// Stocke un nouveau secret
     pub async fn store_secret(&self, secret: Secret, version: u64) -> AgentResult<()> {
-        // Chiffrer le secret avec TPM
-        let encrypted = self.tpm.encrypt(&secret.data)
-            .map_err(|e| AgentError::TpmError(format!("Failed to encrypt secret: {}", e)))?;
+        {
+            let _guard = self.write_lock.lock().await;
 
-        // Stocker dans TPM NV Index (ou fallback)
-        let nv_index = Self::nv_index_for_version(version);
-        self.tpm.nv_write(nv_index, &encrypted)
-            .map_err(|e| AgentError::TpmError(format!("Failed to store secret in TPM: {}", e)))?;
+            // Chiffrer le secret avec TPM
+            let encrypted = self.tpm.encrypt(version, &secret.data)
+                .map_err(|e| AgentError::TpmError(format!("Failed to encrypt secret: {}", e)))?;
+
+            // Stocker dans TPM NV Index (ou stockage fichier scellé)
+            self.write_sealed(version, &encrypted).await?;
+        }
 
         // Mettre à jour métadonnées
         let mut metadata = secret.metadata.clone();
@@ -114,11 +264,21 @@ impl SecretManager {
 
     /// Récupère un secret par version
     pub async fn get_secret(&self, version: u64) -> AgentResult<Secret> {
+        if let Some(failure) = self.state_key_failure() {
+            return Err(AgentError::TpmError(failure));
+        }
+        if let Some(tamper) = self.tamper.lock().unwrap().as_ref() {
+            return Err(AgentError::SecretInvalid(format!(
+                "State tamper response active since {}",
+                tamper.detected_at.to_rfc3339()
+            )));
+        }
+
         // Vérifier que le secret existe
         let metadata = {
             let secrets = self.secrets.lock().unwrap();
             secrets.get(&version).cloned()
-        }.ok_or_else(|| AgentError::SecretNotFound(version))?;
+        }.ok_or(AgentError::SecretNotFound(version))?;
 
         // Vérifier état
         match metadata.state {
@@ -144,17 +304,15 @@ impl SecretManager {
             }
         }
 
-        // Lire depuis TPM
-        let nv_index = Self::nv_index_for_version(version);
-        let encrypted = self.tpm.nv_read(nv_index)
-            .map_err(|e| AgentError::TpmError(format!("Failed to read secret from TPM: {}", e)))?;
+        // Lire depuis TPM (ou stockage fichier scellé)
+        let encrypted = self.read_sealed(version).await?;
 
         if encrypted.is_empty() {
             return Err(AgentError::SecretNotFound(version));
         }
 
         // Déchiffrer
-        let data = self.tpm.decrypt(&encrypted)
+        let data = self.tpm.decrypt(version, &encrypted)
             .map_err(|e| AgentError::TpmError(format!("Failed to decrypt secret: {}", e)))?;
 
         Ok(Secret {
@@ -202,7 +360,7 @@ impl SecretManager {
         let mut metadata = {
             let mut secrets = self.secrets.lock().unwrap();
             secrets.get_mut(&version)
-                .ok_or_else(|| AgentError::SecretNotFound(version))?
+                .ok_or(AgentError::SecretNotFound(version))?
                 .clone()
         };
 
@@ -225,7 +383,7 @@ impl SecretManager {
         let mut metadata = {
             let mut secrets = self.secrets.lock().unwrap();
             secrets.get_mut(&version)
-                .ok_or_else(|| AgentError::SecretNotFound(version))?
+                .ok_or(AgentError::SecretNotFound(version))?
                 .clone()
         };
 
@@ -246,6 +404,12 @@ impl SecretManager {
         }
 
         self.save_state().await?;
+
+        // Le secret n'est plus utilisable : supprimer sa copie scellée
+        if let Err(e) = self.remove_sealed(version).await {
+            warn!("Failed to remove sealed secret {}: {}", version, e);
+        }
+
         info!("Secret {} invalidated", version);
 
         Ok(())
@@ -294,6 +458,124 @@ impl SecretManager {
         Ok(cleaned)
     }
 
+    /// Supprime tous les secrets et réinitialise l'état
+    pub async fn reset(&self) -> AgentResult<usize> {
+        let versions: Vec<u64> = {
+            let mut secrets = self.secrets.lock().unwrap();
+            secrets.drain().map(|(version, _)| version).collect()
+        };
+        let removed = versions.len();
+        *self.active_version.lock().unwrap() = None;
+        *self.tamper.lock().unwrap() = None;
+
+        // Clé du MAC non descellable : l'état qu'elle protégeait est abandonné avec elle
+        if self.state_key_failure.lock().unwrap().take().is_some() {
+            self.state.set_mac_key(self.create_state_key().await?);
+        }
+        self.save_state().await?;
+
+        if self.tpm.is_available() {
+            for version in versions {
+                self.remove_sealed(version).await?;
+            }
+        } else {
+            for version in self.store.list_versions().await? {
+                self.store.remove(version).await?;
+            }
+        }
+        warn!("Secret state reset ({} secrets removed)", removed);
+
+        Ok(removed)
+    }
+
+    /// Rechiffre tous les secrets stockés sous une nouvelle KEK
+    ///
+    /// L'ancienne KEK n'est supprimée qu'une fois tous les secrets rechiffrés :
+    /// une interruption laisse les deux clés utilisables et un nouveau `rekey`
+    /// reprend l'opération.
+    pub async fn rekey(&self) -> AgentResult<usize> {
+        let _guard = self.write_lock.lock().await;
+
+        let kek = self.tpm.kek();
+        let kek_id = kek.begin_rekey()?;
+        let rewrapped = self.reencrypt_all().await?;
+
+        let retired = kek.retire_previous()?;
+        info!(
+            "Rekey complete: {} secrets rewrapped under KEK {} ({} keys retired)",
+            rewrapped,
+            hex::encode(kek_id),
+            retired
+        );
+
+        Ok(rewrapped)
+    }
+
+    /// Rescelle tous les secrets dans le TPM
+    ///
+    /// `suspend_pcr_binding` : avant une mise à jour firmware planifiée, rescelle
+    /// sans politique PCR. Sinon, lie les secrets aux valeurs courantes des PCR
+    /// (à lancer après le redémarrage sur le nouveau firmware).
+    pub async fn reseal(&self, suspend_pcr_binding: bool) -> AgentResult<usize> {
+        if !self.tpm.is_available() {
+            return Err(AgentError::TpmError("TPM not available, nothing to reseal".to_string()));
+        }
+
+        let _guard = self.write_lock.lock().await;
+
+        self.tpm.set_pcr_binding_suspended(suspend_pcr_binding);
+        let resealed = self.reencrypt_all().await?;
+
+        info!(
+            "Reseal complete: {} secrets resealed (PCR binding {})",
+            resealed,
+            if suspend_pcr_binding { "suspended" } else { "active" }
+        );
+
+        Ok(resealed)
+    }
+
+    /// Déchiffre puis rechiffre chaque secret stocké (appelant détenteur de `write_lock`)
+    async fn reencrypt_all(&self) -> AgentResult<usize> {
+        let versions: Vec<u64> = {
+            let secrets = self.secrets.lock().unwrap();
+            secrets
+                .iter()
+                .filter(|(_, metadata)| metadata.state != SecretState::Invalide)
+                .map(|(version, _)| *version)
+                .collect()
+        };
+
+        let mut reencrypted = 0;
+        for version in versions {
+            let encrypted = match self.read_sealed(version).await {
+                Ok(encrypted) if !encrypted.is_empty() => encrypted,
+                Ok(_) | Err(AgentError::SecretNotFound(_)) => {
+                    warn!("Sealed secret {} not found, skipping", version);
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+
+            let data = zeroize::Zeroizing::new(
+                self.tpm.decrypt(version, &encrypted)
+                    .map_err(|e| AgentError::TpmError(format!("Failed to decrypt secret {}: {}", version, e)))?,
+            );
+            let encrypted = self.tpm.encrypt(version, &data)
+                .map_err(|e| AgentError::TpmError(format!("Failed to encrypt secret {}: {}", version, e)))?;
+            self.write_sealed(version, &encrypted).await?;
+            reencrypted += 1;
+        }
+
+        // Clé du MAC de l'état, scellée sous la même KEK / politique PCR que les secrets
+        if let Some(sealed) = self.read_sealed_state_key().await? {
+            let key = self.unseal_state_key(&sealed)?;
+            self.seal_state_key(&key).await?;
+        }
+
+        Ok(reencrypted)
+    }
+
     fn find_new_active_version(&self) -> Option<u64> {
         let secrets = self.secrets.lock().unwrap();
         secrets
@@ -303,10 +585,46 @@ impl SecretManager {
             .max()
     }
 
-    fn nv_index_for_version(version: u64) -> u32 {
-        // NV Index de base + version
-        // Note: TPM 2.0 a des contraintes sur les NV Index, ajuster selon besoin
-        0x01000000 + (version as u32 & 0x00FFFFFF)
+    async fn write_sealed(&self, version: u64, sealed: &[u8]) -> AgentResult<()> {
+        if self.tpm.is_available() {
+            let slot = Self::nv_slot_for_version(version)?;
+            return self.tpm.nv_write(slot, sealed)
+                .map_err(|e| AgentError::TpmError(format!("Failed to store secret in TPM: {}", e)));
+        }
+
+        self.store.write(version, sealed).await
+    }
+
+    async fn read_sealed(&self, version: u64) -> AgentResult<Vec<u8>> {
+        if self.tpm.is_available() {
+            let slot = Self::nv_slot_for_version(version)?;
+            // Index non défini ou vide : secret absent, comme un blob manquant hors TPM
+            return self.tpm.nv_read(slot)
+                .map_err(|e| AgentError::TpmError(format!("Failed to read secret from TPM: {}", e)))?
+                .filter(|data| !data.is_empty())
+                .ok_or(AgentError::SecretNotFound(version));
+        }
+
+        self.store.read(version).await
+    }
+
+    async fn remove_sealed(&self, version: u64) -> AgentResult<()> {
+        if self.tpm.is_available() {
+            return self.tpm.nv_remove(Self::nv_slot_for_version(version)?);
+        }
+
+        self.store.remove(version).await
+    }
+
+    /// Emplacement NV d'un secret : sa version, refusée si elle sort de la plage de l'agent
+    fn nv_slot_for_version(version: u64) -> AgentResult<u32> {
+        u32::try_from(version)
+            .ok()
+            .filter(|slot| (1..=NV_SLOT_MAX).contains(slot))
+            .ok_or_else(|| AgentError::TpmError(format!(
+                "Secret version {} does not fit in the agent NV range (1..={})",
+                version, NV_SLOT_MAX
+            )))
     }
 
     /// Obtient les métadonnées d'un secret (sans le secret lui-même)
@@ -320,6 +638,11 @@ impl SecretManager {
         *self.active_version.lock().unwrap()
     }
 
+    /// Compteurs de validation partagés avec le `LicenseValidator`
+    pub fn validation_stats(&self) -> Arc<ValidationStats> {
+        Arc::clone(&self.validation_stats)
+    }
+
     /// Liste toutes les versions de secrets
     pub fn list_versions(&self) -> Vec<u64> {
         let secrets = self.secrets.lock().unwrap();
@@ -331,5 +654,9 @@ impl SecretManager {
 struct StateFile {
     secrets: HashMap<u64, SecretMetadata>,
     active_version: Option<u64>,
+    #[serde(default)]
+    validation_stats: Option<LicenseStatus>,
+    #[serde(default)]
+    tamper: Option<TamperStatus>,
     last_updated: DateTime<Utc>,
 }
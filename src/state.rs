@@ -0,0 +1,384 @@
+use crate::crypto::{constant_time_compare, sha256};
+use crate::types::{AgentError, AgentResult};
+use base64::{engine::general_purpose, Engine as _};
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{json, Value};
+use sha2::Sha256;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use tokio::io::AsyncWriteExt;
+use tracing::{debug, error, info, warn};
+use zeroize::Zeroizing;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Version courante du schéma du fichier d'état
+pub const STATE_SCHEMA_VERSION: u32 = 3;
+
+/// Premier schéma dont le contenu est authentifié par un MAC
+const FIRST_MAC_SCHEMA: u32 = 3;
+
+/// Préfixe du message authentifié par le MAC de l'état
+const STATE_MAC_CONTEXT: &[u8] = b"license-agent state v3\0";
+
+/// Migration du contenu de l'état vers la version suivante du schéma
+type Migration = fn(Value) -> Result<Value, String>;
+
+/// Migrations indexées par version source (`MIGRATIONS[0]` : v1 -> v2)
+const MIGRATIONS: &[Migration] = &[migrate_v1_to_v2, migrate_v2_to_v3];
+
+/// Fichier d'état persistant, résistant aux coupures de courant
+///
+/// Format (JSON) :
+///
+/// ```text
+/// { "schema_version": 3, "checksum": "<SHA-256 hex>", "mac": "<HMAC-SHA256 Base64>", "state": { ... } }
+/// ```
+///
+/// La somme de contrôle et le MAC portent sur l'encodage canonique (clés
+/// triées) de `schema_version` et `state`. Une somme invalide signale une
+/// écriture interrompue ; un MAC invalide, une modification délibérée
+/// ([`AgentError::SignatureVerificationFailed`]).
+///
+/// Les écritures sont atomiques (fichier temporaire, fsync, rename) ; la
+/// version précédente est conservée dans `<fichier>.bak` et relue si le
+/// fichier principal est absent ou illisible. La clé du MAC, scellée par
+/// l'appelant, est stockée dans `<fichier>.key`.
+pub struct StateStore {
+    path: PathBuf,
+    backup_path: PathBuf,
+    key_path: PathBuf,
+    mac_key: Mutex<Option<Zeroizing<Vec<u8>>>>,
+    /// Faux si le fichier principal était corrompu : il ne doit pas remplacer la sauvegarde
+    primary_valid: AtomicBool,
+    /// Sérialise les écritures (fichier temporaire unique)
+    write_lock: tokio::sync::Mutex<()>,
+}
+
+impl StateStore {
+    pub fn new(path: PathBuf) -> Self {
+        let backup_path = append_extension(&path, "bak");
+        let key_path = append_extension(&path, "key");
+        Self {
+            path,
+            backup_path,
+            key_path,
+            mac_key: Mutex::new(None),
+            primary_valid: AtomicBool::new(true),
+            write_lock: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn backup_path(&self) -> &Path {
+        &self.backup_path
+    }
+
+    pub fn key_path(&self) -> &Path {
+        &self.key_path
+    }
+
+    /// Clé du MAC : les écritures suivantes sont authentifiées et les lectures vérifiées
+    pub fn set_mac_key(&self, key: Zeroizing<Vec<u8>>) {
+        *self.mac_key.lock().unwrap() = Some(key);
+    }
+
+    pub fn has_mac_key(&self) -> bool {
+        self.mac_key.lock().unwrap().is_some()
+    }
+
+    /// Charge l'état, migré vers le schéma courant
+    ///
+    /// `None` si aucun fichier n'existe (premier démarrage). Si le fichier
+    /// principal est illisible, la sauvegarde `.bak` est utilisée ; un MAC
+    /// invalide, dans l'un ou l'autre, est renvoyé sans repli.
+    pub async fn load<T: DeserializeOwned>(&self) -> AgentResult<Option<T>> {
+        let primary = match self.read(&self.path).await {
+            Ok(Some(state)) => return Ok(Some(state)),
+            Ok(None) => None,
+            Err(e @ (AgentError::ConfigError(_) | AgentError::SignatureVerificationFailed(_))) => return Err(e),
+            Err(e) => {
+                error!("State file {} is unusable: {}", self.path.display(), e);
+                self.primary_valid.store(false, Ordering::SeqCst);
+                Some(e)
+            }
+        };
+
+        match self.read(&self.backup_path).await {
+            Ok(Some(state)) => {
+                warn!("State restored from backup {}", self.backup_path.display());
+                Ok(Some(state))
+            }
+            Ok(None) => match primary {
+                Some(e) => Err(e),
+                None => Ok(None),
+            },
+            Err(e @ AgentError::SignatureVerificationFailed(_)) => Err(e),
+            Err(backup_error) => {
+                error!("State backup {} is unusable: {}", self.backup_path.display(), backup_error);
+                Err(primary.unwrap_or(backup_error))
+            }
+        }
+    }
+
+    /// Écrit l'état et conserve la version précédente en `.bak`
+    pub async fn save<T: Serialize>(&self, state: &T) -> AgentResult<()> {
+        let content = {
+            let key = self.mac_key.lock().unwrap();
+            encode_state(state, key.as_deref().map(Vec::as_slice))?
+        };
+        let _guard = self.write_lock.lock().await;
+
+        let tmp_path = self.write_tmp(&self.path, &content).await?;
+
+        // Une coupure entre les deux rename laisse la sauvegarde, relue au démarrage
+        if self.primary_valid.load(Ordering::SeqCst) {
+            match tokio::fs::rename(&self.path, &self.backup_path).await {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(state_error("rotate", &self.backup_path, e)),
+            }
+        }
+        tokio::fs::rename(&tmp_path, &self.path)
+            .await
+            .map_err(|e| state_error("rename", &self.path, e))?;
+        self.primary_valid.store(true, Ordering::SeqCst);
+        self.sync_dir().await;
+
+        debug!("State saved to {}", self.path.display());
+        Ok(())
+    }
+
+    /// Clé du MAC scellée, `None` avant la première écriture
+    pub async fn read_sealed_key(&self) -> AgentResult<Option<Vec<u8>>> {
+        match tokio::fs::read(&self.key_path).await {
+            Ok(sealed) => Ok(Some(sealed)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(state_error("read", &self.key_path, e)),
+        }
+    }
+
+    /// Remplace la clé du MAC scellée (écriture atomique)
+    pub async fn write_sealed_key(&self, sealed: &[u8]) -> AgentResult<()> {
+        let _guard = self.write_lock.lock().await;
+        let tmp_path = self.write_tmp(&self.key_path, sealed).await?;
+        tokio::fs::rename(&tmp_path, &self.key_path)
+            .await
+            .map_err(|e| state_error("rename", &self.key_path, e))?;
+        self.sync_dir().await;
+        Ok(())
+    }
+
+    /// Met de côté les fichiers altérés (`<fichier>.tampered-<horodatage>`) pour analyse
+    ///
+    /// `include_key` : la clé scellée est aussi écartée (illisible ou suspecte).
+    pub async fn quarantine(&self, include_key: bool) -> AgentResult<Vec<PathBuf>> {
+        let _guard = self.write_lock.lock().await;
+        let suffix = format!("tampered-{}", Utc::now().format("%Y%m%dT%H%M%SZ"));
+
+        let mut paths = vec![&self.path, &self.backup_path];
+        if include_key {
+            paths.push(&self.key_path);
+        }
+
+        let mut quarantined = Vec::new();
+        for path in paths {
+            let target = append_extension(path, &suffix);
+            match tokio::fs::rename(path, &target).await {
+                Ok(()) => quarantined.push(target),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(state_error("quarantine", path, e)),
+            }
+        }
+        // Plus de fichier principal : la prochaine écriture n'a rien à sauvegarder
+        self.primary_valid.store(true, Ordering::SeqCst);
+        self.sync_dir().await;
+
+        Ok(quarantined)
+    }
+
+    async fn read<T: DeserializeOwned>(&self, path: &Path) -> AgentResult<Option<T>> {
+        let content = match tokio::fs::read(path).await {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(state_error("read", path, e)),
+        };
+        let key = self.mac_key.lock().unwrap().clone();
+        decode_state(&content, key.as_deref().map(Vec::as_slice)).map(Some)
+    }
+
+    /// Écrit `content` dans `<path>.tmp` (0600) et le synchronise sur disque
+    async fn write_tmp(&self, path: &Path, content: &[u8]) -> AgentResult<PathBuf> {
+        tokio::fs::create_dir_all(self.dir())
+            .await
+            .map_err(|e| state_error("create", self.dir(), e))?;
+
+        let tmp_path = append_extension(path, "tmp");
+        let mut options = tokio::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        options.mode(0o600);
+
+        let mut file = options
+            .open(&tmp_path)
+            .await
+            .map_err(|e| state_error("create", &tmp_path, e))?;
+        file.write_all(content)
+            .await
+            .map_err(|e| state_error("write", &tmp_path, e))?;
+        file.sync_all()
+            .await
+            .map_err(|e| state_error("sync", &tmp_path, e))?;
+
+        Ok(tmp_path)
+    }
+
+    fn dir(&self) -> &Path {
+        self.path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."))
+    }
+
+    /// Synchronise le répertoire pour rendre les rename durables
+    async fn sync_dir(&self) {
+        let dir = self.dir();
+        match tokio::fs::File::open(dir).await {
+            Ok(dir_file) => {
+                if let Err(e) = dir_file.sync_all().await {
+                    warn!("Failed to sync {}: {}", dir.display(), e);
+                }
+            }
+            Err(e) => warn!("Failed to open {} for sync: {}", dir.display(), e),
+        }
+    }
+}
+
+/// Encode l'état au schéma courant, avec sa somme de contrôle et, si une clé est fournie, son MAC
+pub fn encode_state<T: Serialize>(state: &T, mac_key: Option<&[u8]>) -> AgentResult<Vec<u8>> {
+    let state = serde_json::to_value(state)
+        .map_err(|e| AgentError::InternalError(format!("Failed to serialize state: {}", e)))?;
+    let canonical = canonical_state(STATE_SCHEMA_VERSION, &state)?;
+
+    let mut file = json!({
+        "schema_version": STATE_SCHEMA_VERSION,
+        "checksum": hex::encode(sha256(&canonical)),
+        "state": state,
+    });
+    if let Some(key) = mac_key {
+        file["mac"] = json!(general_purpose::STANDARD.encode(state_mac(key, &canonical)?.finalize().into_bytes()));
+    }
+    serde_json::to_vec_pretty(&file).map_err(|e| AgentError::InternalError(format!("Failed to serialize state: {}", e)))
+}
+
+/// Vérifie, migre et décode un fichier d'état
+///
+/// Avec `mac_key`, un MAC absent ou invalide est une `SignatureVerificationFailed`.
+/// Sans clé, seuls les schémas antérieurs au MAC sont acceptés (migration). Un
+/// schéma plus récent que celui de l'agent est une `ConfigError` : la
+/// sauvegarde, plus ancienne, ne doit pas le remplacer silencieusement.
+pub fn decode_state<T: DeserializeOwned>(content: &[u8], mac_key: Option<&[u8]>) -> AgentResult<T> {
+    let corrupted = |reason: String| AgentError::InternalError(format!("State file is corrupted: {}", reason));
+    let tampered = |reason: &str| AgentError::SignatureVerificationFailed(format!("State file {}", reason));
+
+    let file: Value = serde_json::from_slice(content).map_err(|e| corrupted(e.to_string()))?;
+    let (schema_version, mut state) = match file.get("schema_version") {
+        // Schéma 1 : contenu à la racine, sans somme de contrôle
+        None if mac_key.is_some() => return Err(tampered("is not authenticated")),
+        None => (1, file),
+        Some(version) => {
+            let version = version
+                .as_u64()
+                .and_then(|v| u32::try_from(v).ok())
+                .filter(|&v| v >= 2)
+                .ok_or_else(|| corrupted("invalid schema_version".to_string()))?;
+            if version > STATE_SCHEMA_VERSION {
+                return Err(AgentError::ConfigError(format!(
+                    "State schema version {} is newer than supported version {}",
+                    version, STATE_SCHEMA_VERSION
+                )));
+            }
+
+            let mut file = file;
+            let state = file.get_mut("state").map(Value::take).ok_or_else(|| corrupted("missing state".to_string()))?;
+            let checksum = file.get("checksum").and_then(Value::as_str).ok_or_else(|| corrupted("missing checksum".to_string()))?;
+            let canonical = canonical_state(version, &state)?;
+            if !constant_time_compare(hex::encode(sha256(&canonical)).as_bytes(), checksum.as_bytes()) {
+                return Err(corrupted("checksum mismatch".to_string()));
+            }
+
+            match mac_key {
+                Some(key) => verify_state_mac(key, &canonical, file.get("mac"))?,
+                None if version >= FIRST_MAC_SCHEMA => return Err(tampered("is authenticated but its key is missing")),
+                None => {}
+            }
+            (version, state)
+        }
+    };
+
+    for version in schema_version..STATE_SCHEMA_VERSION {
+        let migration = MIGRATIONS[(version - 1) as usize];
+        state = migration(state).map_err(|e| corrupted(format!("migration from schema {} failed: {}", version, e)))?;
+        info!("State migrated from schema {} to {}", version, version + 1);
+    }
+
+    serde_json::from_value(state).map_err(|e| corrupted(e.to_string()))
+}
+
+fn verify_state_mac(key: &[u8], canonical: &[u8], presented: Option<&Value>) -> AgentResult<()> {
+    let tampered = |reason: &str| AgentError::SignatureVerificationFailed(format!("State file {}", reason));
+
+    let presented = match presented {
+        Some(mac) => mac.as_str().ok_or_else(|| tampered("MAC is not a string"))?,
+        None => return Err(tampered("MAC is missing")),
+    };
+    let presented = general_purpose::STANDARD
+        .decode(presented)
+        .map_err(|_| tampered("MAC encoding is invalid"))?;
+    state_mac(key, canonical)?
+        .verify_slice(&presented)
+        .map_err(|_| tampered("MAC mismatch"))
+}
+
+fn state_mac(key: &[u8], canonical: &[u8]) -> AgentResult<HmacSha256> {
+    let mut mac = HmacSha256::new_from_slice(key)
+        .map_err(|e| AgentError::CryptoError(format!("Invalid state key: {}", e)))?;
+    mac.update(STATE_MAC_CONTEXT);
+    mac.update(canonical);
+    Ok(mac)
+}
+
+/// Encodage canonique de la version et du contenu, base de la somme et du MAC
+fn canonical_state(schema_version: u32, state: &Value) -> AgentResult<Vec<u8>> {
+    // `serde_json::Value` trie les clés des objets : encodage indépendant de la mise en forme
+    serde_json::to_vec(&json!({ "schema_version": schema_version, "state": state }))
+        .map_err(|e| AgentError::InternalError(format!("Failed to encode state: {}", e)))
+}
+
+/// Schéma 2 : le contenu est inchangé, seule l'enveloppe (version, somme) est ajoutée
+fn migrate_v1_to_v2(state: Value) -> Result<Value, String> {
+    if !state.is_object() {
+        return Err("state is not an object".to_string());
+    }
+    Ok(state)
+}
+
+/// Schéma 3 : contenu inchangé, authentifié par le MAC à la prochaine écriture
+fn migrate_v2_to_v3(state: Value) -> Result<Value, String> {
+    Ok(state)
+}
+
+fn append_extension(path: &Path, extension: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".");
+    name.push(extension);
+    PathBuf::from(name)
+}
+
+fn state_error(operation: &str, path: &Path, error: std::io::Error) -> AgentError {
+    AgentError::InternalError(format!("Failed to {} state file {}: {}", operation, path.display(), error))
+}
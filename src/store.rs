@@ -0,0 +1,223 @@
+use crate::crypto::{constant_time_compare, sha256};
+use crate::types::{AgentError, AgentResult};
+use std::path::{Path, PathBuf};
+use tokio::io::AsyncWriteExt;
+use tracing::{debug, warn};
+
+const BLOB_MAGIC: &[u8; 4] = b"LSAS";
+const BLOB_FORMAT_VERSION: u8 = 1;
+const HEADER_LEN: usize = 4 + 1 + 8 + 4;
+const CHECKSUM_LEN: usize = 32;
+
+/// Stockage fichier des secrets scellés (utilisé sans TPM)
+///
+/// Un fichier par version de secret, contenant le secret déjà chiffré par
+/// `TpmManager`. Format d'un blob :
+///
+/// ```text
+/// magic "LSAS" (4) | format (1) | version (8, BE) | longueur (4, BE) | données | SHA-256 (32)
+/// ```
+///
+/// Les écritures sont atomiques (fichier temporaire + fsync + rename) et les
+/// fichiers sont créés en 0600.
+pub struct SealedStore {
+    dir: PathBuf,
+}
+
+impl SealedStore {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Écrit le blob d'une version de secret
+    pub async fn write(&self, version: u64, sealed: &[u8]) -> AgentResult<()> {
+        self.ensure_dir().await?;
+
+        let blob = encode_blob(version, sealed)?;
+        let path = self.blob_path(version);
+        let tmp_path = self.dir.join(format!(".{}.tmp", Self::blob_name(version)));
+
+        let mut options = tokio::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        options.mode(0o600);
+
+        let mut file = options
+            .open(&tmp_path)
+            .await
+            .map_err(|e| store_error("create", &tmp_path, e))?;
+        file.write_all(&blob)
+            .await
+            .map_err(|e| store_error("write", &tmp_path, e))?;
+        file.sync_all()
+            .await
+            .map_err(|e| store_error("sync", &tmp_path, e))?;
+        drop(file);
+
+        tokio::fs::rename(&tmp_path, &path)
+            .await
+            .map_err(|e| store_error("rename", &path, e))?;
+        self.sync_dir().await;
+
+        debug!("Sealed secret version {} written to {}", version, path.display());
+        Ok(())
+    }
+
+    /// Lit et vérifie le blob d'une version de secret
+    pub async fn read(&self, version: u64) -> AgentResult<Vec<u8>> {
+        let path = self.blob_path(version);
+        let blob = match tokio::fs::read(&path).await {
+            Ok(blob) => blob,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Err(AgentError::SecretNotFound(version));
+            }
+            Err(e) => return Err(store_error("read", &path, e)),
+        };
+
+        decode_blob(version, &blob)
+    }
+
+    /// Supprime le blob d'une version de secret (sans erreur s'il est absent)
+    pub async fn remove(&self, version: u64) -> AgentResult<()> {
+        let path = self.blob_path(version);
+        match tokio::fs::remove_file(&path).await {
+            Ok(()) => {
+                self.sync_dir().await;
+                debug!("Sealed secret version {} removed", version);
+                Ok(())
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(store_error("remove", &path, e)),
+        }
+    }
+
+    /// Liste les versions présentes dans le stockage
+    pub async fn list_versions(&self) -> AgentResult<Vec<u64>> {
+        let mut entries = match tokio::fs::read_dir(&self.dir).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(store_error("list", &self.dir, e)),
+        };
+
+        let mut versions = Vec::new();
+        while let Some(entry) = entries
+            .next_entry()
+            .await
+            .map_err(|e| store_error("list", &self.dir, e))?
+        {
+            let name = entry.file_name();
+            let version = name
+                .to_str()
+                .and_then(|n| n.strip_prefix("secret-"))
+                .and_then(|n| n.strip_suffix(".sealed"))
+                .and_then(|v| u64::from_str_radix(v, 16).ok());
+            if let Some(version) = version {
+                versions.push(version);
+            }
+        }
+
+        versions.sort_unstable();
+        Ok(versions)
+    }
+
+    fn blob_name(version: u64) -> String {
+        format!("secret-{:016x}.sealed", version)
+    }
+
+    fn blob_path(&self, version: u64) -> PathBuf {
+        self.dir.join(Self::blob_name(version))
+    }
+
+    async fn ensure_dir(&self) -> AgentResult<()> {
+        tokio::fs::create_dir_all(&self.dir)
+            .await
+            .map_err(|e| store_error("create", &self.dir, e))?;
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            tokio::fs::set_permissions(&self.dir, std::fs::Permissions::from_mode(0o700))
+                .await
+                .map_err(|e| store_error("chmod", &self.dir, e))?;
+        }
+
+        Ok(())
+    }
+
+    /// Synchronise le répertoire pour rendre le rename durable
+    async fn sync_dir(&self) {
+        match tokio::fs::File::open(&self.dir).await {
+            Ok(dir) => {
+                if let Err(e) = dir.sync_all().await {
+                    warn!("Failed to sync {}: {}", self.dir.display(), e);
+                }
+            }
+            Err(e) => warn!("Failed to open {} for sync: {}", self.dir.display(), e),
+        }
+    }
+}
+
+fn encode_blob(version: u64, sealed: &[u8]) -> AgentResult<Vec<u8>> {
+    let len = u32::try_from(sealed.len())
+        .map_err(|_| AgentError::InternalError("Sealed secret too large".to_string()))?;
+
+    let mut blob = Vec::with_capacity(HEADER_LEN + sealed.len() + CHECKSUM_LEN);
+    blob.extend_from_slice(BLOB_MAGIC);
+    blob.push(BLOB_FORMAT_VERSION);
+    blob.extend_from_slice(&version.to_be_bytes());
+    blob.extend_from_slice(&len.to_be_bytes());
+    blob.extend_from_slice(sealed);
+    let checksum = sha256(&blob);
+    blob.extend_from_slice(&checksum);
+    Ok(blob)
+}
+
+fn decode_blob(version: u64, blob: &[u8]) -> AgentResult<Vec<u8>> {
+    let corrupted = |reason: &str| {
+        AgentError::SecretInvalid(format!("Sealed secret {} is corrupted: {}", version, reason))
+    };
+
+    if blob.len() < HEADER_LEN + CHECKSUM_LEN {
+        return Err(corrupted("truncated blob"));
+    }
+
+    let (content, checksum) = blob.split_at(blob.len() - CHECKSUM_LEN);
+    if !constant_time_compare(&sha256(content), checksum) {
+        return Err(corrupted("checksum mismatch"));
+    }
+
+    if &content[0..4] != BLOB_MAGIC {
+        return Err(corrupted("bad magic"));
+    }
+    if content[4] != BLOB_FORMAT_VERSION {
+        return Err(corrupted(&format!("unsupported format {}", content[4])));
+    }
+
+    let mut version_bytes = [0u8; 8];
+    version_bytes.copy_from_slice(&content[5..13]);
+    if u64::from_be_bytes(version_bytes) != version {
+        return Err(corrupted("version mismatch"));
+    }
+
+    let mut len_bytes = [0u8; 4];
+    len_bytes.copy_from_slice(&content[13..17]);
+    let len = u32::from_be_bytes(len_bytes) as usize;
+    if content.len() - HEADER_LEN != len {
+        return Err(corrupted("length mismatch"));
+    }
+
+    Ok(content[HEADER_LEN..].to_vec())
+}
+
+fn store_error(action: &str, path: &Path, error: std::io::Error) -> AgentError {
+    AgentError::InternalError(format!(
+        "Secret store: failed to {} {}: {}",
+        action,
+        path.display(),
+        error
+    ))
+}
@@ -0,0 +1,199 @@
+use crate::config::ServerConfig;
+use crate::crypto::{constant_time_compare, sha256};
+use anyhow::{Context, Result};
+use base64::{engine::general_purpose, Engine as _};
+use rustls::client::{ServerCertVerified, ServerCertVerifier, WebPkiVerifier};
+use rustls::server::AllowAnyAnonymousOrAuthenticatedClient;
+use rustls::{Certificate, ClientConfig, OwnedTrustAnchor, PrivateKey, RootCertStore, ServerName};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::SystemTime;
+use tracing::{debug, warn};
+
+/// Empreinte SHA-256 d'un SubjectPublicKeyInfo
+pub type SpkiPin = [u8; 32];
+
+/// Vérificateur de certificat serveur avec pinning SPKI
+///
+/// - Sans pin : vérification WebPKI classique (chaîne + nom d'hôte).
+/// - Avec pin et bundle CA : vérification WebPKI puis contrôle du pin.
+/// - Avec pin sans bundle CA : le pin seul authentifie le serveur
+///   (certificats auto-signés).
+pub struct PinnedCertVerifier {
+    pins: Vec<SpkiPin>,
+    webpki: Option<WebPkiVerifier>,
+}
+
+impl PinnedCertVerifier {
+    pub fn new(pins: Vec<SpkiPin>, webpki: Option<WebPkiVerifier>) -> Self {
+        Self { pins, webpki }
+    }
+}
+
+impl ServerCertVerifier for PinnedCertVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+        server_name: &ServerName,
+        scts: &mut dyn Iterator<Item = &[u8]>,
+        ocsp_response: &[u8],
+        now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        if let Some(webpki) = &self.webpki {
+            webpki.verify_server_cert(end_entity, intermediates, server_name, scts, ocsp_response, now)?;
+        }
+
+        if !self.pins.is_empty() {
+            let pin = spki_sha256(&end_entity.0)
+                .map_err(|e| rustls::Error::General(format!("Failed to parse server certificate: {}", e)))?;
+
+            if !self.pins.iter().any(|p| constant_time_compare(p, &pin)) {
+                warn!("Server certificate pin mismatch (got sha256/{})", general_purpose::STANDARD.encode(pin));
+                return Err(rustls::Error::General("Server certificate pin mismatch".to_string()));
+            }
+            debug!("Server certificate pin verified");
+        }
+
+        Ok(ServerCertVerified::assertion())
+    }
+}
+
+/// Construit la configuration TLS du client de rotation
+///
+/// Présente le certificat client configuré (mTLS, obligatoire), applique le pin
+/// `cert_pin` et utilise `ca_bundle` comme racines de confiance si défini.
+pub fn build_client_config(server: &ServerConfig) -> Result<ClientConfig> {
+    let pins = parse_cert_pins(&server.cert_pin)?;
+
+    let roots = match &server.ca_bundle {
+        Some(path) => load_root_store(path)?,
+        None => webpki_root_store(),
+    };
+
+    // Avec un pin et sans bundle CA, le pin remplace la validation de chaîne
+    let webpki = if pins.is_empty() || server.ca_bundle.is_some() {
+        Some(WebPkiVerifier::new(roots, None))
+    } else {
+        None
+    };
+
+    let builder = ClientConfig::builder()
+        .with_safe_defaults()
+        .with_custom_certificate_verifier(Arc::new(PinnedCertVerifier::new(pins, webpki)));
+
+    // Le serveur exige le mTLS : un certificat ou une clé illisible est une erreur de configuration
+    let certs = load_certificates(&server.client_cert).context("Client certificate unavailable")?;
+    let key = load_private_key(&server.client_key).context("Client key unavailable")?;
+    builder
+        .with_client_auth_cert(certs, key)
+        .context("Invalid client certificate or key")
+}
+
+/// Construit la configuration TLS de l'API HTTP
+///
+/// Avec `client_ca`, les certificats clients signés par cette CA sont vérifiés
+/// (mTLS). Un client sans certificat reste accepté et doit alors présenter un
+/// jeton bearer.
+pub fn build_api_server_config(cert: &Path, key: &Path, client_ca: Option<&Path>) -> Result<rustls::ServerConfig> {
+    let builder = rustls::ServerConfig::builder().with_safe_defaults();
+    let builder = match client_ca {
+        Some(path) => builder.with_client_cert_verifier(
+            AllowAnyAnonymousOrAuthenticatedClient::new(load_root_store(path)?).boxed(),
+        ),
+        None => builder.with_no_client_auth(),
+    };
+
+    builder
+        .with_single_cert(load_certificates(cert)?, load_private_key(key)?)
+        .context("Invalid API certificate or key")
+}
+
+/// Parse `cert_pin` : liste séparée par des virgules d'empreintes SHA-256 SPKI,
+/// en Base64 (préfixe `sha256/` optionnel) ou en hexadécimal
+pub fn parse_cert_pins(value: &str) -> Result<Vec<SpkiPin>> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .map(|p| {
+            let encoded = p.strip_prefix("sha256/").unwrap_or(p);
+            let bytes = if encoded.len() == 64 && encoded.chars().all(|c| c.is_ascii_hexdigit()) {
+                hex::decode(encoded)?
+            } else {
+                general_purpose::STANDARD
+                    .decode(encoded)
+                    .with_context(|| format!("Invalid certificate pin: {}", p))?
+            };
+            SpkiPin::try_from(bytes.as_slice())
+                .map_err(|_| anyhow::anyhow!("Certificate pin must be a SHA-256 digest: {}", p))
+        })
+        .collect()
+}
+
+/// Calcule l'empreinte SHA-256 du SubjectPublicKeyInfo d'un certificat DER
+pub fn spki_sha256(cert_der: &[u8]) -> Result<SpkiPin> {
+    use x509_cert::der::{Decode, Encode};
+
+    let cert = x509_cert::Certificate::from_der(cert_der)?;
+    let spki = cert.tbs_certificate.subject_public_key_info.to_der()?;
+
+    let mut pin = [0u8; 32];
+    pin.copy_from_slice(&sha256(&spki));
+    Ok(pin)
+}
+
+/// Charge une chaîne de certificats PEM
+pub fn load_certificates(path: &Path) -> Result<Vec<Certificate>> {
+    let file = std::fs::File::open(path)
+        .with_context(|| format!("Failed to open certificate {}", path.display()))?;
+    let certs = rustls_pemfile::certs(&mut std::io::BufReader::new(file))
+        .with_context(|| format!("Failed to parse certificate {}", path.display()))?;
+
+    if certs.is_empty() {
+        anyhow::bail!("No certificate found in {}", path.display());
+    }
+
+    Ok(certs.into_iter().map(Certificate).collect())
+}
+
+/// Charge une clé privée PEM (PKCS#1, PKCS#8 ou SEC1)
+pub fn load_private_key(path: &Path) -> Result<PrivateKey> {
+    use rustls_pemfile::Item;
+
+    let file = std::fs::File::open(path)
+        .with_context(|| format!("Failed to open private key {}", path.display()))?;
+    let items = rustls_pemfile::read_all(&mut std::io::BufReader::new(file))
+        .with_context(|| format!("Failed to parse private key {}", path.display()))?;
+
+    items
+        .into_iter()
+        .find_map(|item| match item {
+            Item::RSAKey(key) | Item::PKCS8Key(key) | Item::ECKey(key) => Some(PrivateKey(key)),
+            _ => None,
+        })
+        .ok_or_else(|| anyhow::anyhow!("No private key found in {}", path.display()))
+}
+
+fn load_root_store(path: &Path) -> Result<RootCertStore> {
+    let mut roots = RootCertStore::empty();
+    let certs = load_certificates(path)?;
+    let (added, ignored) = roots.add_parsable_certificates(&certs.iter().map(|c| c.0.clone()).collect::<Vec<_>>());
+
+    if added == 0 {
+        anyhow::bail!("No usable CA certificate in {}", path.display());
+    }
+    if ignored > 0 {
+        warn!("{} certificates ignored in CA bundle {}", ignored, path.display());
+    }
+
+    Ok(roots)
+}
+
+fn webpki_root_store() -> RootCertStore {
+    let mut roots = RootCertStore::empty();
+    roots.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|ta| {
+        OwnedTrustAnchor::from_subject_spki_name_constraints(ta.subject, ta.spki, ta.name_constraints)
+    }));
+    roots
+}
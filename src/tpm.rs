@@ -1,28 +1,108 @@
+use crate::config::PcrPolicyConfig;
+use crate::kek::{KeyHierarchy, KekId};
 use crate::types::AgentError;
 use anyhow::Result;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
 #[cfg(feature = "tpm")]
-use std::sync::Arc;
+use tss_esapi::{
+    abstraction::nv,
+    attributes::{NvIndexAttributesBuilder, ObjectAttributesBuilder},
+    constants::SessionType,
+    handles::{
+        KeyHandle, NvIndexHandle, NvIndexTpmHandle, ObjectHandle, PersistentTpmHandle, SessionHandle,
+        TpmHandle,
+    },
+    interface_types::{
+        algorithm::{HashingAlgorithm, PublicAlgorithm},
+        dynamic_handles::Persistent,
+        key_bits::RsaKeyBits,
+        resource_handles::{Hierarchy, NvAuth, Provision},
+        session_handles::PolicySession,
+    },
+    structures::{
+        Digest, KeyedHashScheme, MaxNvBuffer, NvPublicBuilder, PcrSelectionList, PcrSelectionListBuilder,
+        PcrSlot, Private, Public, PublicBuilder, PublicKeyedHashParameters, RsaExponent, SensitiveData,
+        SymmetricDefinition, SymmetricDefinitionObject,
+    },
+    traits::{Marshall, UnMarshall},
+    utils::create_restricted_decryption_rsa_public,
+    Context, TctiNameConf,
+};
+use tracing::{debug, info, warn};
+
+/// Handle persistant de la clé de stockage (hiérarchie propriétaire)
+pub const STORAGE_KEY_HANDLE: u32 = 0x8100_0100;
+
+/// Plage des NV Index de l'agent
+///
+/// Sous-plage de la seconde zone réservée au propriétaire par le registre des
+/// handles TCG (0x01800000-0x01BFFFFF). La zone 0x01400000-0x017FFFFF revient
+/// aux fabricants de plateforme, 0x01C00000 et au-delà aux certificats EK.
+pub const NV_INDEX_BASE: u32 = 0x0180_0000;
+pub const NV_INDEX_LAST: u32 = 0x018F_FFFF;
+
+/// Emplacement NV de la clé du MAC de l'état ; les secrets occupent 1..=`NV_SLOT_MAX`
+///
+/// Chaque emplacement dispose de deux index consécutifs, écrits en alternance :
+/// la donnée précédente reste lisible tant que la nouvelle n'est pas complète.
+pub const NV_STATE_SLOT: u32 = 0;
+pub const NV_SLOT_MAX: u32 = (NV_INDEX_LAST - NV_INDEX_BASE) / 2;
+
+/// En-tête d'un enregistrement NV : numéro de génération (u64) et SHA-256
 #[cfg(feature = "tpm")]
-use tss_esapi::{Context, TctiNameConf};
-use tracing::{info, warn};
+const NV_RECORD_HEADER: usize = 8 + 32;
+
+/// Taille des écritures/lectures NV (inférieure à TPM2_PT_NV_BUFFER_MAX usuel)
+#[cfg(feature = "tpm")]
+const NV_CHUNK_SIZE: usize = 512;
+
+/// Occupation des NV Index de l'agent
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NvUsage {
+    pub indices: usize,
+    pub bytes: usize,
+}
 
 /// Gestionnaire TPM
 pub struct TpmManager {
     #[cfg(feature = "tpm")]
-    context: Option<Arc<Context>>,
+    context: Option<Mutex<TpmContext>>,
     #[cfg(not(feature = "tpm"))]
     context: Option<()>,
+    kek: Arc<KeyHierarchy>,
+    /// Politique PCR appliquée aux nouveaux scellements
+    pcr_policy: Option<PcrPolicyConfig>,
+    /// Scellement sans politique PCR pendant une mise à jour firmware planifiée
+    pcr_binding_suspended: AtomicBool,
+    /// Dernier échec de la politique PCR au descellement
+    pcr_policy_failure: Mutex<Option<String>>,
+}
+
+/// Contexte ESYS et clé de stockage chargée
+///
+/// `Context` n'est pas `Send` (pointeur brut ESYS). Il n'est jamais utilisé
+/// qu'à travers le `Mutex` de `TpmManager`, donc par un seul thread à la fois.
+#[cfg(feature = "tpm")]
+struct TpmContext {
+    context: Context,
+    storage_key: KeyHandle,
 }
 
+#[cfg(feature = "tpm")]
+unsafe impl Send for TpmContext {}
+
 impl TpmManager {
-    pub fn new(enabled: bool) -> Result<Self> {
+    /// `kek` : hiérarchie de clés utilisée pour le chiffrement logiciel.
+    /// `pcr_policy` : PCR auxquels les secrets scellés dans le TPM sont liés.
+    pub fn new(enabled: bool, kek: Arc<KeyHierarchy>, pcr_policy: Option<PcrPolicyConfig>) -> Result<Self> {
         let context = if enabled {
             #[cfg(feature = "tpm")]
             {
                 match Self::create_context() {
                     Ok(ctx) => {
                         info!("TPM context created successfully");
-                        Some(Arc::new(ctx))
+                        Some(Mutex::new(ctx))
                     }
                     Err(e) => {
                         warn!("Failed to create TPM context: {}. Falling back to software encryption.", e);
@@ -40,20 +120,23 @@ impl TpmManager {
             None
         };
 
-        Ok(Self { context })
+        Ok(Self {
+            context,
+            kek,
+            pcr_policy,
+            pcr_binding_suspended: AtomicBool::new(false),
+            pcr_policy_failure: Mutex::new(None),
+        })
     }
 
     #[cfg(feature = "tpm")]
-    fn create_context() -> Result<Context> {
+    fn create_context() -> Result<TpmContext> {
         let tcti = TctiNameConf::from_environment_variable()
-            .unwrap_or_else(|_| TctiNameConf::Mssim {
-                host: "localhost".to_string(),
-                port: 2321,
-            });
+            .unwrap_or_else(|_| TctiNameConf::Mssim(Default::default()));
 
         let mut context = Context::new(tcti)?;
-        context.initialize()?;
-        Ok(context)
+        let storage_key = Self::load_or_create_storage_key(&mut context)?;
+        Ok(TpmContext { context, storage_key })
     }
 
     #[cfg(not(feature = "tpm"))]
@@ -62,144 +145,349 @@ impl TpmManager {
         anyhow::bail!("TPM support not compiled (feature 'tpm' not enabled)");
     }
 
+    /// Charge la clé de stockage persistante, ou la crée sous la hiérarchie propriétaire
+    #[cfg(feature = "tpm")]
+    fn load_or_create_storage_key(context: &mut Context) -> Result<KeyHandle> {
+        let persistent = PersistentTpmHandle::new(STORAGE_KEY_HANDLE)?;
+
+        if let Ok(handle) = context.tr_from_tpm_public(TpmHandle::Persistent(persistent)) {
+            debug!("Using persistent storage key {:#010x}", STORAGE_KEY_HANDLE);
+            return Ok(handle.into());
+        }
+
+        let template = create_restricted_decryption_rsa_public(
+            SymmetricDefinitionObject::AES_128_CFB,
+            RsaKeyBits::Rsa2048,
+            RsaExponent::default(),
+        )?;
+
+        let primary = context.execute_with_nullauth_session(|ctx| {
+            ctx.create_primary(Hierarchy::Owner, template, None, None, None, None)
+        })?;
+
+        let persisted = context.execute_with_nullauth_session(|ctx| {
+            ctx.evict_control(
+                Provision::Owner,
+                primary.key_handle.into(),
+                Persistent::Persistent(persistent),
+            )
+        })?;
+        context.flush_context(primary.key_handle.into())?;
+
+        info!("Persistent storage key created at {:#010x}", STORAGE_KEY_HANDLE);
+        Ok(persisted.into())
+    }
+
     pub fn is_available(&self) -> bool {
         self.context.is_some()
     }
 
-    /// Chiffre des données avec TPM
-    pub fn encrypt(&self, data: &[u8]) -> Result<Vec<u8>, AgentError> {
+    /// Hiérarchie de clés du chiffrement logiciel
+    pub fn kek(&self) -> &KeyHierarchy {
+        &self.kek
+    }
+
+    /// Suspend (ou rétablit) la liaison PCR des prochains scellements
+    ///
+    /// Avant une mise à jour firmware planifiée, les secrets sont rescellés sans
+    /// politique PCR ; après redémarrage, un nouveau rescellement les lie aux
+    /// nouvelles valeurs des PCR.
+    pub fn set_pcr_binding_suspended(&self, suspended: bool) {
+        self.pcr_binding_suspended.store(suspended, Ordering::SeqCst);
+        if !suspended {
+            *self.pcr_policy_failure.lock().unwrap() = None;
+        }
+    }
+
+    /// Politique PCR configurée
+    pub fn pcr_policy(&self) -> Option<&PcrPolicyConfig> {
+        self.pcr_policy.as_ref()
+    }
+
+    /// Politique PCR effective des prochains scellements
+    #[cfg(feature = "tpm")]
+    fn active_pcr_policy(&self) -> Option<&PcrPolicyConfig> {
+        if self.pcr_binding_suspended.load(Ordering::SeqCst) {
+            return None;
+        }
+        self.pcr_policy.as_ref()
+    }
+
+    /// Chiffre une version de secret avec TPM
+    pub fn encrypt(&self, version: u64, data: &[u8]) -> Result<Vec<u8>, AgentError> {
         #[cfg(feature = "tpm")]
         {
             if let Some(ctx) = &self.context {
-                return self.encrypt_with_tpm(ctx, data);
+                return self.encrypt_with_tpm(ctx, version, data);
             }
         }
-        // Fallback: chiffrement logiciel (moins sécurisé)
-        warn!("Using software encryption fallback (TPM not available)");
-        self.encrypt_software(data)
+        // Fallback: chiffrement logiciel sous la KEK
+        debug!("Using software encryption fallback (TPM not available)");
+        self.encrypt_software(version, data)
     }
 
-    /// Déchiffre des données avec TPM
-    pub fn decrypt(&self, encrypted: &[u8]) -> Result<Vec<u8>, AgentError> {
+    /// Déchiffre une version de secret avec TPM
+    pub fn decrypt(&self, version: u64, encrypted: &[u8]) -> Result<Vec<u8>, AgentError> {
         #[cfg(feature = "tpm")]
         {
             if let Some(ctx) = &self.context {
-                return self.decrypt_with_tpm(ctx, encrypted);
+                return self.decrypt_with_tpm(ctx, version, encrypted);
             }
         }
-        self.decrypt_software(encrypted)
+        self.decrypt_software(version, encrypted)
     }
 
+    /// Scelle une clé de données aléatoire sous la clé de stockage et chiffre
+    /// le secret avec elle
+    ///
+    /// Format : len public (2) | public | len private (2) | private | nonce (12) | données chiffrées
     #[cfg(feature = "tpm")]
-    fn encrypt_with_tpm(&self, _ctx: &Context, data: &[u8]) -> Result<Vec<u8>, AgentError> {
-        // TODO: Implémenter chiffrement avec clé TPM persistante
-        // Pour l'instant, fallback logiciel
-        self.encrypt_software(data)
+    fn encrypt_with_tpm(&self, ctx: &Mutex<TpmContext>, version: u64, data: &[u8]) -> Result<Vec<u8>, AgentError> {
+        use rand::RngCore;
+
+        let mut data_key = zeroize::Zeroizing::new(vec![0u8; 32]);
+        rand::thread_rng().fill_bytes(&mut data_key);
+
+        let (public, private) = {
+            let mut guard = ctx.lock().unwrap();
+            let TpmContext { context, storage_key } = &mut *guard;
+            let storage_key = *storage_key;
+
+            // Politique PCR calculée sur les valeurs courantes des PCR
+            let auth_policy = match self.active_pcr_policy() {
+                Some(policy) => Some(pcr_policy_digest(context, policy)?),
+                None => None,
+            };
+
+            let sensitive = SensitiveData::try_from(data_key.to_vec()).map_err(tpm_error("prepare sealed data"))?;
+            let template = sealed_object_template(auth_policy).map_err(tpm_error("build sealed object template"))?;
+            let created = context
+                .execute_with_nullauth_session(|ctx| {
+                    ctx.create(storage_key, template, None, Some(sensitive), None, None)
+                })
+                .map_err(tpm_error("seal data key"))?;
+            (created.out_public, created.out_private)
+        };
+
+        let public = public.marshall().map_err(tpm_error("marshall sealed object"))?;
+        let (nonce, ciphertext) = aead_encrypt(&data_key, version, data)?;
+
+        let mut result = Vec::with_capacity(4 + public.len() + private.value().len() + nonce.len() + ciphertext.len());
+        append_sized(&mut result, &public)?;
+        append_sized(&mut result, private.value())?;
+        result.extend_from_slice(&nonce);
+        result.extend_from_slice(&ciphertext);
+        Ok(result)
     }
 
     #[cfg(not(feature = "tpm"))]
     #[allow(dead_code)]
-    fn encrypt_with_tpm(&self, _ctx: &(), _data: &[u8]) -> Result<Vec<u8>, AgentError> {
-        self.encrypt_software(_data)
+    fn encrypt_with_tpm(&self, _ctx: &(), version: u64, data: &[u8]) -> Result<Vec<u8>, AgentError> {
+        self.encrypt_software(version, data)
     }
 
     #[cfg(feature = "tpm")]
-    fn decrypt_with_tpm(&self, _ctx: &Context, encrypted: &[u8]) -> Result<Vec<u8>, AgentError> {
-        // TODO: Implémenter déchiffrement avec clé TPM persistante
-        self.decrypt_software(encrypted)
+    fn decrypt_with_tpm(&self, ctx: &Mutex<TpmContext>, version: u64, encrypted: &[u8]) -> Result<Vec<u8>, AgentError> {
+        let invalid = || AgentError::CryptoError("Invalid sealed data".to_string());
+
+        let (public, rest) = split_sized(encrypted).ok_or_else(invalid)?;
+        let (private, rest) = split_sized(rest).ok_or_else(invalid)?;
+        if rest.len() < 12 {
+            return Err(invalid());
+        }
+
+        let public = Public::unmarshall(public).map_err(tpm_error("unmarshall sealed object"))?;
+        let private = Private::try_from(private.to_vec()).map_err(tpm_error("unmarshall sealed object"))?;
+
+        let data_key = {
+            let mut guard = ctx.lock().unwrap();
+            let TpmContext { context, storage_key } = &mut *guard;
+            let storage_key = *storage_key;
+
+            let sealed_policy = sealed_auth_policy(&public);
+            let object = context
+                .execute_with_nullauth_session(|ctx| ctx.load(storage_key, private, public))
+                .map_err(tpm_error("load sealed object"))?;
+            let unsealed = match sealed_policy {
+                Some(sealed_policy) => self.unseal_with_pcr_policy(context, object, version, &sealed_policy),
+                None => context
+                    .execute_with_nullauth_session(|ctx| ctx.unseal(object.into()))
+                    .map_err(tpm_error("unseal data key")),
+            };
+            if let Err(e) = context.flush_context(object.into()) {
+                warn!("Failed to flush sealed object: {}", e);
+            }
+            zeroize::Zeroizing::new(unsealed?.value().to_vec())
+        };
+
+        aead_decrypt(&data_key, version, &rest[..12], &rest[12..])
+    }
+
+    /// Descelle un objet lié à une politique PCR
+    ///
+    /// Si les PCR ont changé depuis le scellement, l'échec est enregistré et
+    /// exposé dans `TpmStatus`.
+    #[cfg(feature = "tpm")]
+    fn unseal_with_pcr_policy(
+        &self,
+        context: &mut Context,
+        object: KeyHandle,
+        version: u64,
+        sealed_policy: &Digest,
+    ) -> Result<SensitiveData, AgentError> {
+        let policy = self.pcr_policy.as_ref().ok_or_else(|| {
+            AgentError::TpmError(format!(
+                "Secret {} is sealed to a PCR policy but tpm.pcr_policy is not configured",
+                version
+            ))
+        })?;
+
+        let session = start_policy_session(context, SessionType::Policy)?;
+        let result = policy_pcr(context, session, policy)
+            .and_then(|_| {
+                let current = context.policy_get_digest(session).map_err(tpm_error("read policy digest"))?;
+                if current.value() != sealed_policy.value() {
+                    return Err(AgentError::TpmError(format!(
+                        "PCR policy check failed for secret {}: boot state differs from sealed state (PCRs {})",
+                        version, policy
+                    )));
+                }
+                context
+                    .execute_with_session(Some(session.into()), |ctx| ctx.unseal(object.into()))
+                    .map_err(|e| AgentError::TpmError(format!(
+                        "PCR policy check failed for secret {} (PCRs {}): {}",
+                        version, policy, e
+                    )))
+            });
+        flush_session(context, session);
+
+        if let Err(e) = &result {
+            warn!("{}", e);
+            *self.pcr_policy_failure.lock().unwrap() = Some(format!("{} at {}", e, chrono::Utc::now().to_rfc3339()));
+        }
+        result
     }
 
     #[cfg(not(feature = "tpm"))]
     #[allow(dead_code)]
-    fn decrypt_with_tpm(&self, _ctx: &(), encrypted: &[u8]) -> Result<Vec<u8>, AgentError> {
-        self.decrypt_software(encrypted)
+    fn decrypt_with_tpm(&self, _ctx: &(), version: u64, encrypted: &[u8]) -> Result<Vec<u8>, AgentError> {
+        self.decrypt_software(version, encrypted)
     }
 
-    fn encrypt_software(&self, data: &[u8]) -> Result<Vec<u8>, AgentError> {
-        use aes_gcm::{
-            aead::{Aead, KeyInit},
-            Aes256Gcm, Nonce,
-        };
-        use rand::Rng;
-
-        // Génération clé depuis variable d'environnement ou fichier
-        // NOTE: Ceci est un fallback temporaire, doit être remplacé par TPM
-        let key = self.get_fallback_key()?;
-        let cipher = Aes256Gcm::new(&key);
-        
-        let mut nonce_bytes = [0u8; 12];
-        rand::thread_rng().fill(&mut nonce_bytes);
-        let nonce = Nonce::from_slice(&nonce_bytes);
+    /// Format : id KEK (4) | nonce (12) | données chiffrées (AAD = version)
+    fn encrypt_software(&self, version: u64, data: &[u8]) -> Result<Vec<u8>, AgentError> {
+        let (kek_id, key) = self.kek.current_data_key(version)?;
+        let (nonce_bytes, ciphertext) = aead_encrypt(key.as_ref(), version, data)?;
 
-        let ciphertext = cipher
-            .encrypt(nonce, data)
-            .map_err(|e| AgentError::CryptoError(format!("Encryption failed: {}", e)))?;
-
-        // Préfixer avec nonce
-        let mut result = nonce_bytes.to_vec();
+        // Préfixer avec id KEK et nonce
+        let mut result = kek_id.to_vec();
+        result.extend_from_slice(&nonce_bytes);
         result.extend_from_slice(&ciphertext);
         Ok(result)
     }
 
-    fn decrypt_software(&self, encrypted: &[u8]) -> Result<Vec<u8>, AgentError> {
-        use aes_gcm::{
-            aead::{Aead, KeyInit},
-            Aes256Gcm, Nonce,
-        };
-
-        if encrypted.len() < 12 {
+    fn decrypt_software(&self, version: u64, encrypted: &[u8]) -> Result<Vec<u8>, AgentError> {
+        let header_len = std::mem::size_of::<KekId>() + 12;
+        if encrypted.len() < header_len {
             return Err(AgentError::CryptoError("Invalid encrypted data".to_string()));
         }
 
-        let key = self.get_fallback_key()?;
-        let cipher = Aes256Gcm::new(&key);
+        let (kek_id, rest) = encrypted.split_at(std::mem::size_of::<KekId>());
+        let kek_id = KekId::try_from(kek_id)
+            .map_err(|_| AgentError::CryptoError("Invalid encrypted data".to_string()))?;
+        let key = self.kek.data_key(&kek_id, version)?;
+        aead_decrypt(key.as_ref(), version, &rest[0..12], &rest[12..])
+    }
 
-        let nonce = Nonce::from_slice(&encrypted[0..12]);
-        let ciphertext = &encrypted[12..];
+    /// Écrit dans un emplacement NV (voir [`NV_STATE_SLOT`])
+    ///
+    /// La donnée est écrite dans l'index inoccupé de l'emplacement, avec une
+    /// génération supérieure ; l'ancienne n'est supprimée qu'ensuite. Une
+    /// coupure pendant l'écriture laisse donc la donnée précédente intacte.
+    #[cfg_attr(not(feature = "tpm"), allow(unused_variables))]
+    pub fn nv_write(&self, slot: u32, data: &[u8]) -> Result<(), AgentError> {
+        #[cfg(feature = "tpm")]
+        {
+            if let Some(ctx) = &self.context {
+                let indices = nv_slot_indices(slot)?;
+                let mut guard = ctx.lock().unwrap();
+                let context = &mut guard.context;
 
-        let plaintext = cipher
-            .decrypt(nonce, ciphertext)
-            .map_err(|e| AgentError::CryptoError(format!("Decryption failed: {}", e)))?;
+                let current = read_nv_records(context, indices)?;
+                let generation = current.as_ref().map_or(0, |(_, generation, _)| *generation) + 1;
+                let (target, previous) = match current {
+                    Some((index, _, _)) if index == indices[0] => (indices[1], Some(indices[0])),
+                    Some((index, _, _)) => (indices[0], Some(index)),
+                    None => (indices[0], None),
+                };
+
+                // Index cible : ancienne génération ou écriture interrompue
+                undefine_nv_index(context, target)?;
+                write_nv_index(context, target, &encode_nv_record(generation, data))?;
+                if let Some(previous) = previous {
+                    undefine_nv_index(context, previous)?;
+                }
 
-        Ok(plaintext)
+                debug!("NV write to slot {} (index {:#010x}, {} bytes)", slot, target, data.len());
+                return Ok(());
+            }
+        }
+        Err(AgentError::TpmError("TPM not available".to_string()))
     }
 
-    fn get_fallback_key(&self) -> Result<aes_gcm::Key<aes_gcm::Aes256Gcm>, AgentError> {
-        use sha2::{Digest, Sha256};
+    /// Lit un emplacement NV, `None` s'il n'a jamais été écrit
+    #[cfg_attr(not(feature = "tpm"), allow(unused_variables))]
+    pub fn nv_read(&self, slot: u32) -> Result<Option<Vec<u8>>, AgentError> {
+        #[cfg(feature = "tpm")]
+        {
+            if let Some(ctx) = &self.context {
+                let indices = nv_slot_indices(slot)?;
+                let mut guard = ctx.lock().unwrap();
+                let record = read_nv_records(&mut guard.context, indices)?;
 
-        // Dérivation clé depuis fichier ou variable d'environnement
-        // WARNING: Ceci est un fallback temporaire
-        let seed = std::env::var("LICENSE_AGENT_FALLBACK_KEY")
-            .unwrap_or_else(|_| "CHANGE_THIS_IN_PRODUCTION".to_string());
-        
-        let mut hasher = Sha256::new();
-        hasher.update(seed.as_bytes());
-        let key_bytes = hasher.finalize();
-        
-        Ok(aes_gcm::Key::<aes_gcm::Aes256Gcm>::from_slice(&key_bytes).clone())
+                if let Some((index, _, data)) = &record {
+                    debug!("NV read from slot {} (index {:#010x}, {} bytes)", slot, index, data.len());
+                }
+                return Ok(record.map(|(_, _, data)| data));
+            }
+        }
+        Err(AgentError::TpmError("TPM not available".to_string()))
     }
 
-    /// Écrit dans un NV Index TPM
-    pub fn nv_write(&self, _index: u32, _data: &[u8]) -> Result<(), AgentError> {
+    /// Supprime un emplacement NV (sans erreur s'il est absent)
+    pub fn nv_remove(&self, slot: u32) -> Result<(), AgentError> {
         #[cfg(feature = "tpm")]
         {
-            if self.context.is_some() {
-                // TODO: Implémenter écriture NV Index complète avec tss-esapi
-                debug!("NV write to index {} ({} bytes)", _index, _data.len());
+            if let Some(ctx) = &self.context {
+                let indices = nv_slot_indices(slot)?;
+                let mut guard = ctx.lock().unwrap();
+                for index in indices {
+                    undefine_nv_index(&mut guard.context, index)?;
+                }
+                debug!("NV slot {} removed", slot);
                 return Ok(());
             }
         }
-        Err(AgentError::TpmError("TPM not available".to_string()))
+        Err(AgentError::TpmError(format!("TPM not available (NV slot {})", slot)))
     }
 
-    /// Lit depuis un NV Index TPM
-    pub fn nv_read(&self, _index: u32) -> Result<Vec<u8>, AgentError> {
+    /// Occupation des NV Index de l'agent
+    pub fn nv_usage(&self) -> Result<NvUsage, AgentError> {
         #[cfg(feature = "tpm")]
         {
-            if self.context.is_some() {
-                // TODO: Implémenter lecture NV Index complète avec tss-esapi
-                debug!("NV read from index {}", _index);
-                return Ok(vec![]);
+            if let Some(ctx) = &self.context {
+                let mut guard = ctx.lock().unwrap();
+                let indices = nv::list(&mut guard.context).map_err(tpm_error("list NV indices"))?;
+
+                return Ok(indices
+                    .iter()
+                    .filter(|(public, _)| {
+                        (NV_INDEX_BASE..=NV_INDEX_LAST).contains(&u32::from(public.nv_index()))
+                    })
+                    .fold(NvUsage::default(), |usage, (public, _)| NvUsage {
+                        indices: usage.indices + 1,
+                        bytes: usage.bytes + public.data_size(),
+                    }));
             }
         }
         Err(AgentError::TpmError("TPM not available".to_string()))
@@ -211,41 +499,436 @@ impl TpmManager {
         
         #[cfg(feature = "tpm")]
         {
-            if self.context.is_some() {
-                // TODO: Récupérer informations TPM réelles
-                return TpmStatus {
-                    available: true,
-                    version: Some("2.0".to_string()),
-                    manufacturer: None,
-                    firmware_version: None,
-                    keys_loaded: 0,
-                    nv_space_used: None,
+            if let Some(ctx) = &self.context {
+                let usage = match self.nv_usage() {
+                    Ok(usage) => Some(usage),
+                    Err(e) => {
+                        warn!("Failed to read NV usage: {}", e);
+                        None
+                    }
                 };
+
+                let mut status = {
+                    let mut guard = ctx.lock().unwrap();
+                    query_status(&mut guard.context)
+                };
+                status.nv_space_used = usage.map(|u| u.bytes as f64);
+                status.nv_agent_indices = usage.map(|u| u.indices);
+                status.pcr_policy = self.pcr_policy.as_ref().map(ToString::to_string);
+                status.pcr_binding_suspended = self.pcr_binding_suspended.load(Ordering::SeqCst);
+                status.pcr_policy_failure = self.pcr_policy_failure.lock().unwrap().clone();
+                return status;
             }
         }
         
-        TpmStatus {
-            available: false,
-            version: None,
-            manufacturer: None,
-            firmware_version: None,
-            keys_loaded: 0,
-            nv_space_used: None,
+        TpmStatus::default()
+    }
+}
+
+/// Interroge les propriétés TPM2 (TPM2_GetCapability)
+///
+/// Une propriété non lisible est rapportée à `None` sans faire échouer l'ensemble.
+#[cfg(feature = "tpm")]
+fn query_status(context: &mut Context) -> crate::types::TpmStatus {
+    use crate::types::{TpmLockoutStatus, TpmStatus};
+    use tss_esapi::constants::PropertyTag;
+
+    /// TPMA_PERMANENT.inLockout
+    const PERMANENT_IN_LOCKOUT: u32 = 1 << 9;
+
+    let keys_loaded = transient_object_count(context).unwrap_or_else(|e| {
+        debug!("{}", e);
+        0
+    });
+
+    let mut property = |tag: PropertyTag| match context.get_tpm_property(tag) {
+        Ok(value) => value,
+        Err(e) => {
+            debug!("Failed to read TPM property {:?}: {}", tag, e);
+            None
         }
+    };
+
+    let family = property(PropertyTag::FamilyIndicator).map(|v| property_string(v).trim().to_string());
+    let revision = property(PropertyTag::Revision);
+    let version = match (family, revision) {
+        (Some(family), Some(revision)) => Some(format!("{} rev {}.{:02}", family, revision / 100, revision % 100)),
+        (Some(family), None) => Some(family),
+        _ => None,
+    };
+
+    let vendor: String = [
+        PropertyTag::VendorString1,
+        PropertyTag::VendorString2,
+        PropertyTag::VendorString3,
+        PropertyTag::VendorString4,
+    ]
+    .into_iter()
+    .filter_map(&mut property)
+    .map(property_string)
+    .collect::<String>()
+    .trim()
+    .to_string();
+
+    let firmware_version = property(PropertyTag::FirmwareVersion1).map(|v1| {
+        let v2 = property(PropertyTag::FirmwareVersion2).unwrap_or(0);
+        format!("{}.{}.{}.{}", v1 >> 16, v1 & 0xffff, v2 >> 16, v2 & 0xffff)
+    });
+
+    let lockout = match (
+        property(PropertyTag::Permanent),
+        property(PropertyTag::LockoutCounter),
+        property(PropertyTag::MaxAuthFail),
+    ) {
+        (Some(permanent), Some(failed_tries), Some(max_tries)) => Some(TpmLockoutStatus {
+            in_lockout: permanent & PERMANENT_IN_LOCKOUT != 0,
+            failed_tries,
+            max_tries,
+            recovery_interval_seconds: property(PropertyTag::LockoutInterval).unwrap_or(0),
+            lockout_recovery_seconds: property(PropertyTag::LockoutRecovery).unwrap_or(0),
+        }),
+        _ => None,
+    };
+
+    TpmStatus {
+        available: true,
+        version,
+        manufacturer: property(PropertyTag::Manufacturer).map(|v| property_string(v).trim().to_string()),
+        vendor: (!vendor.is_empty()).then_some(vendor),
+        firmware_version,
+        keys_loaded,
+        persistent_handles: property(PropertyTag::HrPersistent),
+        persistent_handles_available: property(PropertyTag::HrPersistentAvail),
+        nv_indices_defined: property(PropertyTag::HrNvIndex),
+        nv_index_max_size: property(PropertyTag::NvIndexMax),
+        lockout,
+        ..Default::default()
     }
 }
 
-impl Drop for TpmManager {
-    fn drop(&mut self) {
-        #[cfg(feature = "tpm")]
-        {
-            if let Some(ctx) = self.context.take() {
-                if let Ok(ctx) = Arc::try_unwrap(ctx) {
-                    if let Err(e) = ctx.teardown() {
-                        error!("Failed to teardown TPM context: {}", e);
-                    }
-                }
+/// Objets transitoires chargés : handles de la plage TRANSIENT_FIRST
+///
+/// TPM_PT_HR_LOADED compte les sessions chargées, pas les objets.
+#[cfg(feature = "tpm")]
+fn transient_object_count(context: &mut Context) -> Result<usize, AgentError> {
+    use tss_esapi::constants::{tss::TPM2_TRANSIENT_FIRST, CapabilityType};
+    use tss_esapi::structures::{CapabilityData, HandleList};
+
+    let mut count = 0;
+    let mut next = TPM2_TRANSIENT_FIRST;
+    loop {
+        let (data, more) = context
+            .get_capability(CapabilityType::Handles, next, HandleList::MAX_SIZE as u32)
+            .map_err(tpm_error("list transient handles"))?;
+        let CapabilityData::Handles(handles) = data else {
+            return Err(AgentError::TpmError("Unexpected capability data for handles".to_string()));
+        };
+        let handles = handles.into_inner();
+        count += handles.len();
+        match handles.last() {
+            Some(last) if more => next = u32::from(*last) + 1,
+            _ => return Ok(count),
+        }
+    }
+}
+
+/// Décode une propriété TPM contenant jusqu'à 4 caractères ASCII
+#[cfg(feature = "tpm")]
+fn property_string(value: u32) -> String {
+    value
+        .to_be_bytes()
+        .iter()
+        .filter(|b| b.is_ascii_graphic() || **b == b' ')
+        .map(|b| *b as char)
+        .collect()
+}
+
+/// Chiffre avec AES-256-GCM (AAD = version), retourne (nonce, données chiffrées)
+fn aead_encrypt(key: &[u8], version: u64, data: &[u8]) -> Result<([u8; 12], Vec<u8>), AgentError> {
+    use aes_gcm::{
+        aead::{Aead, KeyInit, Payload},
+        Aes256Gcm, Key, Nonce,
+    };
+    use rand::Rng;
+
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
+
+    let mut nonce_bytes = [0u8; 12];
+    rand::thread_rng().fill(&mut nonce_bytes);
+
+    let aad = version.to_be_bytes();
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce_bytes), Payload { msg: data, aad: &aad })
+        .map_err(|e| AgentError::CryptoError(format!("Encryption failed: {}", e)))?;
+
+    Ok((nonce_bytes, ciphertext))
+}
+
+fn aead_decrypt(key: &[u8], version: u64, nonce: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, AgentError> {
+    use aes_gcm::{
+        aead::{Aead, KeyInit, Payload},
+        Aes256Gcm, Key, Nonce,
+    };
+
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
+    let aad = version.to_be_bytes();
+
+    cipher
+        .decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad: &aad })
+        .map_err(|e| AgentError::CryptoError(format!("Decryption failed: {}", e)))
+}
+
+/// Gabarit d'un objet scellé (keyed hash) sous la clé de stockage
+///
+/// Avec une politique, l'objet n'est descellable que via une session de
+/// politique satisfaisant `auth_policy` (pas d'autorisation par mot de passe).
+#[cfg(feature = "tpm")]
+fn sealed_object_template(auth_policy: Option<Digest>) -> tss_esapi::Result<Public> {
+    let attributes = ObjectAttributesBuilder::new()
+        .with_fixed_tpm(true)
+        .with_fixed_parent(true)
+        .with_user_with_auth(auth_policy.is_none())
+        .build()?;
+
+    PublicBuilder::new()
+        .with_public_algorithm(PublicAlgorithm::KeyedHash)
+        .with_name_hashing_algorithm(HashingAlgorithm::Sha256)
+        .with_object_attributes(attributes)
+        .with_auth_policy(auth_policy.unwrap_or_default())
+        .with_keyed_hash_parameters(PublicKeyedHashParameters::new(KeyedHashScheme::Null))
+        .with_keyed_hash_unique_identifier(Digest::default())
+        .build()
+}
+
+/// Politique d'un objet scellé (`None` si l'objet n'est pas lié aux PCR)
+#[cfg(feature = "tpm")]
+fn sealed_auth_policy(public: &Public) -> Option<Digest> {
+    match public {
+        Public::KeyedHash { auth_policy, .. } if !auth_policy.value().is_empty() => Some(auth_policy.clone()),
+        _ => None,
+    }
+}
+
+/// Calcule le digest de politique PCR sur les valeurs courantes des PCR
+#[cfg(feature = "tpm")]
+fn pcr_policy_digest(context: &mut Context, policy: &PcrPolicyConfig) -> Result<Digest, AgentError> {
+    let session = start_policy_session(context, SessionType::Trial)?;
+    let digest = policy_pcr(context, session, policy)
+        .and_then(|_| context.policy_get_digest(session).map_err(tpm_error("read policy digest")));
+    flush_session(context, session);
+    digest
+}
+
+#[cfg(feature = "tpm")]
+fn start_policy_session(context: &mut Context, session_type: SessionType) -> Result<PolicySession, AgentError> {
+    let session = context
+        .start_auth_session(
+            None,
+            None,
+            None,
+            session_type,
+            SymmetricDefinition::AES_128_CFB,
+            HashingAlgorithm::Sha256,
+        )
+        .map_err(tpm_error("start policy session"))?
+        .ok_or_else(|| AgentError::TpmError("Failed to start policy session".to_string()))?;
+
+    PolicySession::try_from(session).map_err(tpm_error("start policy session"))
+}
+
+#[cfg(feature = "tpm")]
+fn policy_pcr(context: &mut Context, session: PolicySession, policy: &PcrPolicyConfig) -> Result<(), AgentError> {
+    // Digest vide : le TPM utilise les valeurs courantes des PCR sélectionnés
+    context
+        .policy_pcr(session, Digest::default(), pcr_selection(policy)?)
+        .map_err(tpm_error("apply PCR policy"))
+}
+
+#[cfg(feature = "tpm")]
+fn pcr_selection(policy: &PcrPolicyConfig) -> Result<PcrSelectionList, AgentError> {
+    let bank = match policy.bank.as_str() {
+        "sha1" => HashingAlgorithm::Sha1,
+        "sha256" => HashingAlgorithm::Sha256,
+        "sha384" => HashingAlgorithm::Sha384,
+        other => return Err(AgentError::ConfigError(format!("Unsupported PCR bank: {}", other))),
+    };
+
+    let slots = policy
+        .pcrs
+        .iter()
+        .map(|pcr| PcrSlot::try_from(1u32 << pcr).map_err(tpm_error("select PCR")))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    PcrSelectionListBuilder::new()
+        .with_selection(bank, &slots)
+        .build()
+        .map_err(tpm_error("select PCR"))
+}
+
+#[cfg(feature = "tpm")]
+fn flush_session(context: &mut Context, session: PolicySession) {
+    let handle = SessionHandle::from(tss_esapi::interface_types::session_handles::AuthSession::from(session));
+    if let Err(e) = context.flush_context(handle.into()) {
+        debug!("Failed to flush policy session: {}", e);
+    }
+}
+
+#[cfg(feature = "tpm")]
+fn nv_index_handle(context: &mut Context, index: u32) -> Result<Option<NvIndexHandle>, AgentError> {
+    let tpm_handle = NvIndexTpmHandle::new(index).map_err(tpm_error("build NV index"))?;
+    match context.tr_from_tpm_public(TpmHandle::NvIndex(tpm_handle)) {
+        Ok(handle) => Ok(Some(handle.into())),
+        Err(_) => Ok(None),
+    }
+}
+
+#[cfg(feature = "tpm")]
+fn close_handle(context: &mut Context, mut handle: ObjectHandle) {
+    if let Err(e) = context.tr_close(&mut handle) {
+        debug!("Failed to close TPM handle: {}", e);
+    }
+}
+
+/// Les deux NV Index d'un emplacement
+#[cfg(feature = "tpm")]
+fn nv_slot_indices(slot: u32) -> Result<[u32; 2], AgentError> {
+    if slot > NV_SLOT_MAX {
+        return Err(AgentError::TpmError(format!("NV slot {} outside agent range", slot)));
+    }
+    let index = NV_INDEX_BASE + 2 * slot;
+    Ok([index, index + 1])
+}
+
+#[cfg(feature = "tpm")]
+fn encode_nv_record(generation: u64, data: &[u8]) -> Vec<u8> {
+    use sha2::{Digest as _, Sha256};
+
+    let mut record = Vec::with_capacity(NV_RECORD_HEADER + data.len());
+    record.extend_from_slice(&generation.to_be_bytes());
+    record.extend_from_slice(&Sha256::new().chain_update(generation.to_be_bytes()).chain_update(data).finalize());
+    record.extend_from_slice(data);
+    record
+}
+
+/// Génération et données d'un enregistrement, `None` s'il est incomplet
+#[cfg(feature = "tpm")]
+fn decode_nv_record(record: &[u8]) -> Option<(u64, Vec<u8>)> {
+    use sha2::{Digest as _, Sha256};
+
+    if record.len() < NV_RECORD_HEADER {
+        return None;
+    }
+    let (header, data) = record.split_at(NV_RECORD_HEADER);
+    let generation = u64::from_be_bytes(header[..8].try_into().ok()?);
+    let digest = Sha256::new().chain_update(&header[..8]).chain_update(data).finalize();
+    (digest.as_slice() == &header[8..]).then(|| (generation, data.to_vec()))
+}
+
+/// Enregistrement le plus récent d'un emplacement : (index, génération, données)
+#[cfg(feature = "tpm")]
+fn read_nv_records(context: &mut Context, indices: [u32; 2]) -> Result<Option<(u32, u64, Vec<u8>)>, AgentError> {
+    let mut latest: Option<(u32, u64, Vec<u8>)> = None;
+    for index in indices {
+        let Some(handle) = nv_index_handle(context, index)? else {
+            continue;
+        };
+        // Index défini mais jamais écrit (coupure) : ignoré
+        let record = read_nv_index(context, handle);
+        close_handle(context, handle.into());
+        match record.ok().as_deref().and_then(decode_nv_record) {
+            Some((generation, data)) if latest.as_ref().is_none_or(|(_, g, _)| generation > *g) => {
+                latest = Some((index, generation, data));
             }
+            Some(_) => {}
+            None => warn!("NV index {:#010x} holds an incomplete record, ignored", index),
         }
     }
+    Ok(latest)
+}
+
+#[cfg(feature = "tpm")]
+fn read_nv_index(context: &mut Context, handle: NvIndexHandle) -> Result<Vec<u8>, AgentError> {
+    let (nv_public, _) = context
+        .nv_read_public(handle)
+        .map_err(tpm_error("read NV public area"))?;
+    let size = nv_public.data_size();
+
+    let mut data = Vec::with_capacity(size);
+    while data.len() < size {
+        let chunk = (size - data.len()).min(NV_CHUNK_SIZE) as u16;
+        let offset = data.len() as u16;
+        let buffer = context
+            .execute_with_nullauth_session(|ctx| ctx.nv_read(NvAuth::Owner, handle, chunk, offset))
+            .map_err(tpm_error("read NV index"))?;
+        data.extend_from_slice(buffer.value());
+    }
+    Ok(data)
+}
+
+/// Définit l'index à la taille des données et les écrit
+#[cfg(feature = "tpm")]
+fn write_nv_index(context: &mut Context, index: u32, data: &[u8]) -> Result<(), AgentError> {
+    let attributes = NvIndexAttributesBuilder::new()
+        .with_owner_write(true)
+        .with_owner_read(true)
+        .with_no_da(true)
+        .build()
+        .map_err(tpm_error("build NV attributes"))?;
+    let nv_public = NvPublicBuilder::new()
+        .with_nv_index(NvIndexTpmHandle::new(index).map_err(tpm_error("build NV index"))?)
+        .with_index_name_algorithm(HashingAlgorithm::Sha256)
+        .with_index_attributes(attributes)
+        .with_data_area_size(data.len())
+        .build()
+        .map_err(tpm_error("build NV index"))?;
+
+    let handle = context
+        .execute_with_nullauth_session(|ctx| ctx.nv_define_space(Provision::Owner, None, nv_public))
+        .map_err(|e| AgentError::TpmError(format!(
+            "Failed to define NV index {:#010x} ({} bytes, NV space exhausted?): {}",
+            index,
+            data.len(),
+            e
+        )))?;
+
+    let written = data.chunks(NV_CHUNK_SIZE).enumerate().try_for_each(|(i, chunk)| {
+        let buffer = MaxNvBuffer::try_from(chunk.to_vec()).map_err(tpm_error("prepare NV data"))?;
+        let offset = (i * NV_CHUNK_SIZE) as u16;
+        context
+            .execute_with_nullauth_session(|ctx| ctx.nv_write(NvAuth::Owner, handle, buffer, offset))
+            .map_err(tpm_error("write NV index"))
+    });
+    close_handle(context, handle.into());
+    written
+}
+
+#[cfg(feature = "tpm")]
+fn undefine_nv_index(context: &mut Context, index: u32) -> Result<(), AgentError> {
+    if let Some(handle) = nv_index_handle(context, index)? {
+        context
+            .execute_with_nullauth_session(|ctx| ctx.nv_undefine_space(Provision::Owner, handle))
+            .map_err(tpm_error("undefine NV index"))?;
+    }
+    Ok(())
+}
+
+#[cfg(feature = "tpm")]
+fn append_sized(buffer: &mut Vec<u8>, data: &[u8]) -> Result<(), AgentError> {
+    let len = u16::try_from(data.len())
+        .map_err(|_| AgentError::CryptoError("Sealed object too large".to_string()))?;
+    buffer.extend_from_slice(&len.to_be_bytes());
+    buffer.extend_from_slice(data);
+    Ok(())
+}
+
+#[cfg(feature = "tpm")]
+fn split_sized(data: &[u8]) -> Option<(&[u8], &[u8])> {
+    let len = u16::from_be_bytes([*data.first()?, *data.get(1)?]) as usize;
+    let rest = &data[2..];
+    (rest.len() >= len).then(|| rest.split_at(len))
+}
+
+#[cfg(feature = "tpm")]
+fn tpm_error(action: &'static str) -> impl Fn(tss_esapi::Error) -> AgentError {
+    move |e| AgentError::TpmError(format!("Failed to {}: {}", action, e))
 }
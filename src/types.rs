@@ -1,6 +1,6 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use zeroize::ZeroizeOnDrop;
 
 /// État d'un secret
@@ -67,21 +67,35 @@ pub struct ValidationResult {
     pub error: Option<String>,
 }
 
+impl ValidationResult {
+    /// Licence valide et incluant la fonctionnalité `feature`
+    pub fn has_feature(&self, feature: &str) -> bool {
+        self.valid && self.features.iter().any(|f| f == feature)
+    }
+}
+
 /// Requête de validation
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct ValidateLicenseRequest {
     pub license_token: Vec<u8>,
+    /// Nonce aléatoire : refusé s'il est réutilisé, repris dans le MAC de la réponse
     pub nonce: [u8; 16],
+    /// Application enregistrée : la réponse est authentifiée avec sa clé
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub app_id: Option<String>,
 }
 
 /// Réponse de validation
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct ValidateLicenseResponse {
     pub result: ValidationResult,
+    /// HMAC-SHA256 (Base64) de la réponse, si `app_id` est renseigné
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mac: Option<String>,
 }
 
 /// État du système
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct SystemStatus {
     pub active_secret: Option<SecretInfo>,
     pub grace_secrets: Vec<SecretInfo>,
@@ -89,10 +103,13 @@ pub struct SystemStatus {
     pub license_status: LicenseStatus,
     pub degraded_mode: DegradedModeStatus,
     pub next_rotation: Option<DateTime<Utc>>,
+    /// Altération du fichier d'état détectée : secrets refusés jusqu'au `reset`
+    #[serde(default)]
+    pub tamper_response: Option<TamperStatus>,
 }
 
 /// Informations sur un secret (sans le secret lui-même)
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SecretInfo {
     pub version: u64,
     pub state: SecretState,
@@ -103,28 +120,116 @@ pub struct SecretInfo {
 }
 
 /// État TPM
-#[derive(Debug, Serialize)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct TpmStatus {
     pub available: bool,
+    /// Famille et révision de la spécification (ex: `2.0 rev 1.59`)
     pub version: Option<String>,
+    /// Identifiant fabricant (TPM_PT_MANUFACTURER, ex: `IFX`)
     pub manufacturer: Option<String>,
+    /// Chaîne fournisseur (TPM_PT_VENDOR_STRING_1..4)
+    #[serde(default)]
+    pub vendor: Option<String>,
     pub firmware_version: Option<String>,
+    /// Objets transitoires chargés
     pub keys_loaded: usize,
+    /// Handles persistants définis et encore disponibles
+    #[serde(default)]
+    pub persistent_handles: Option<u32>,
+    #[serde(default)]
+    pub persistent_handles_available: Option<u32>,
+    /// Octets occupés par les NV Index de l'agent
     pub nv_space_used: Option<f64>,
+    /// NV Index utilisés par l'agent
+    #[serde(default)]
+    pub nv_agent_indices: Option<usize>,
+    /// NV Index définis sur le TPM (tous propriétaires)
+    #[serde(default)]
+    pub nv_indices_defined: Option<u32>,
+    /// Taille maximale d'un NV Index (le TPM n'expose pas d'espace libre global)
+    #[serde(default)]
+    pub nv_index_max_size: Option<u32>,
+    /// Protection contre les attaques par dictionnaire
+    #[serde(default)]
+    pub lockout: Option<TpmLockoutStatus>,
+    /// Politique PCR des secrets scellés (ex: `sha256:0,2,4,7`)
+    pub pcr_policy: Option<String>,
+    /// Liaison PCR suspendue (fenêtre de mise à jour firmware)
+    #[serde(default)]
+    pub pcr_binding_suspended: bool,
+    /// Dernier échec de la politique PCR au descellement
+    pub pcr_policy_failure: Option<String>,
+}
+
+/// État du verrouillage (dictionary attack) du TPM
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TpmLockoutStatus {
+    pub in_lockout: bool,
+    /// Échecs d'autorisation comptabilisés
+    pub failed_tries: u32,
+    /// Échecs tolérés avant verrouillage
+    pub max_tries: u32,
+    /// Délai de décrément du compteur d'échecs (secondes)
+    pub recovery_interval_seconds: u32,
+    /// Durée de verrouillage de l'autorisation lockout (secondes)
+    pub lockout_recovery_seconds: u32,
 }
 
 /// État de la licence
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct LicenseStatus {
     pub last_validation: Option<DateTime<Utc>>,
     pub total_validations: u64,
     pub successful_validations: u64,
     pub failed_validations: u64,
     pub last_error: Option<String>,
+    /// Échecs de validation par motif
+    #[serde(default)]
+    pub failures_by_reason: BTreeMap<ValidationFailureReason, u64>,
+}
+
+/// Motif d'échec d'une validation de licence
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ValidationFailureReason {
+    /// Licence expirée
+    Expired,
+    /// Version de secret inconnue ou expirée
+    UnknownVersion,
+    /// Échec du déchiffrement (secret ou en-tête ne correspondant pas)
+    DecryptFailure,
+    /// Token illisible (Base64, format, JSON)
+    MalformedToken,
+    /// Format de token valide mais désactivé par la configuration (v1)
+    DisabledFormat,
+    /// Autre erreur (stockage, TPM)
+    Other,
+}
+
+impl ValidationFailureReason {
+    pub const ALL: [ValidationFailureReason; 6] = [
+        ValidationFailureReason::Expired,
+        ValidationFailureReason::UnknownVersion,
+        ValidationFailureReason::DecryptFailure,
+        ValidationFailureReason::MalformedToken,
+        ValidationFailureReason::DisabledFormat,
+        ValidationFailureReason::Other,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ValidationFailureReason::Expired => "expired",
+            ValidationFailureReason::UnknownVersion => "unknown_version",
+            ValidationFailureReason::DecryptFailure => "decrypt_failure",
+            ValidationFailureReason::MalformedToken => "malformed_token",
+            ValidationFailureReason::DisabledFormat => "disabled_format",
+            ValidationFailureReason::Other => "other",
+        }
+    }
 }
 
 /// État du mode dégradé
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct DegradedModeStatus {
     pub active: bool,
     pub activated_at: Option<DateTime<Utc>>,
@@ -133,6 +238,16 @@ pub struct DegradedModeStatus {
     pub remaining_seconds: Option<i64>,
 }
 
+/// Réponse à une altération du fichier d'état (MAC invalide ou clé d'intégrité illisible)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TamperStatus {
+    pub detected_at: DateTime<Utc>,
+    pub reason: String,
+    /// Fichiers altérés conservés pour analyse
+    #[serde(default)]
+    pub quarantined: Vec<String>,
+}
+
 /// Erreurs du système
 #[derive(Debug, thiserror::Error)]
 pub enum AgentError {
@@ -166,6 +281,9 @@ pub enum AgentError {
     #[error("Cryptographic error: {0}")]
     CryptoError(String),
     
+    #[error("Signature verification failed: {0}")]
+    SignatureVerificationFailed(String),
+    
     #[error("Internal error: {0}")]
     InternalError(String),
 }
@@ -0,0 +1,198 @@
+//! Agent en processus pour les tests d'intégration
+//!
+//! Démarre le `CoreEngine` et l'`IpcServer` de l'agent (TPM désactivé) dans un
+//! répertoire temporaire, avec un secret actif et une application enregistrée.
+//! Les tests de l'agent et des clients (Rust, C, Python) passent ainsi par le
+//! vrai protocole, les vrais rôles, limites de débit et codes d'erreur.
+
+use base64::{engine::general_purpose, Engine as _};
+use chrono::{DateTime, Utc};
+use license_secret_agent::app_auth::AppKeyStore;
+use license_secret_agent::config::{Config, ManagementConfig, RolesConfig};
+use license_secret_agent::core::CoreEngine;
+use license_secret_agent::kek::KeyHierarchy;
+use license_secret_agent::license::encrypt_license_v2;
+use license_secret_agent::roles::Role;
+use license_secret_agent::secret::SecretManager;
+use license_secret_agent::tpm::TpmManager;
+use license_secret_agent::types::{RotationSource, Secret, SecretMetadata, SecretState};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Application enregistrée auprès de l'agent (réponses authentifiées)
+pub const APP_ID: &str = "billing";
+
+/// Version du secret actif de l'agent
+pub const SECRET_VERSION: u64 = 1;
+
+const SECRET: [u8; 32] = [0x5a; 32];
+
+/// Fichier de `tests/fixtures` (certificats et clés de test)
+pub fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("../tests/fixtures").join(name)
+}
+
+/// Configuration d'agent minimale (TPM désactivé, hors production) suivie de `extra`
+pub fn write_agent_config(path: &Path, extra: &str) {
+    std::fs::write(
+        path,
+        format!(
+            r#"
+[server]
+url = "https://license-server.example.com"
+cert_pin = ""
+client_cert = "{cert}"
+client_key = "{key}"
+
+[agent]
+id = "dev-001"
+production_mode = false
+
+[tpm]
+enabled = false
+
+[management]
+allowed_uids = [1000]
+
+[degraded_mode]
+enabled = true
+grace_period_days = 7
+retry_interval_seconds = 300
+auto_deactivate_on_reconnect = true
+alert_thresholds_hours = [24]
+
+{extra}
+"#,
+            cert = fixture("client.crt").display(),
+            key = fixture("client.key").display(),
+        ),
+    )
+    .unwrap();
+}
+
+/// Attribue `role` à l'UID du processus de test (`[management.roles]`)
+pub fn bind_current_uid(management: &mut ManagementConfig, role: Role) {
+    let uid = unsafe { libc::getuid() };
+    let roles = management.roles.get_or_insert_with(RolesConfig::default);
+    let binding = match role {
+        Role::App => &mut roles.app,
+        Role::Operator => &mut roles.operator,
+        Role::Admin => &mut roles.admin,
+    };
+    binding.uids.push(uid);
+}
+
+/// Agent servant l'IPC sur un socket temporaire, arrêté et supprimé au `drop`
+pub struct TestAgent {
+    dir: PathBuf,
+    socket: PathBuf,
+    app_key: Vec<u8>,
+    server: tokio::task::JoinHandle<()>,
+    /// Runtime de l'agent quand le test n'en a pas (tests bloquants)
+    runtime: Option<tokio::runtime::Runtime>,
+}
+
+impl TestAgent {
+    /// Démarre l'agent sur le runtime courant, configuré par `management`
+    pub async fn start(management: impl FnOnce(&mut ManagementConfig)) -> Self {
+        let dir = std::env::temp_dir().join(format!("lsa-agent-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let config_path = dir.join("config.toml");
+        write_agent_config(
+            &config_path,
+            &format!(
+                "[paths]\nstate_dir = \"{dir}/state\"\naudit_log = \"{dir}/audit.log\"\nruntime_dir = \"{dir}/run\"",
+                dir = dir.display()
+            ),
+        );
+        let mut config = Config::load_from_path(&config_path).unwrap();
+        management(&mut config.management);
+
+        // Secret actif et application enregistrée, comme après une rotation et un `register-app`
+        {
+            let kek = KeyHierarchy::from_config(&config).unwrap();
+            let tpm = Arc::new(TpmManager::new(false, Arc::new(kek), None).unwrap());
+            SecretManager::new(tpm, config.state_path(), config.secret_store_path())
+                .store_secret(secret(), SECRET_VERSION)
+                .await
+                .unwrap();
+        }
+        let app_key = AppKeyStore::new(config.app_keys_path()).register(APP_ID).await.unwrap().to_vec();
+
+        // Serveur IPC du démon, sans les tâches périodiques ni l'API HTTP de `CoreEngine::start`
+        let socket = config.ipc_socket_path();
+        let engine = Arc::new(CoreEngine::new(config).await.unwrap());
+        let server = engine.ipc_server().await.unwrap();
+        let server = tokio::spawn(async move {
+            let _ = server.run().await;
+        });
+
+        Self { dir, socket, app_key, server, runtime: None }
+    }
+
+    /// Démarre l'agent sur son propre runtime, pour les tests sans runtime tokio
+    pub fn start_blocking(management: impl FnOnce(&mut ManagementConfig)) -> Self {
+        let runtime = tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap();
+        let mut agent = runtime.block_on(Self::start(management));
+        agent.runtime = Some(runtime);
+        agent
+    }
+
+    /// Répertoire temporaire de l'agent (configuration, état, journal d'audit)
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn socket(&self) -> &Path {
+        &self.socket
+    }
+
+    /// Clé de l'application [`APP_ID`]
+    pub fn app_key(&self) -> &[u8] {
+        &self.app_key
+    }
+
+    /// Token de licence (Base64) chiffré avec le secret actif de l'agent
+    pub fn license(&self, features: &[&str], expires_at: DateTime<Utc>) -> Vec<u8> {
+        let license = serde_json::to_vec(&serde_json::json!({
+            "license_id": "lic-test",
+            "customer_id": "ACME",
+            "features": features,
+            "metadata": { "customer": "ACME" },
+            "expires_at": expires_at.to_rfc3339(),
+            "issued_at": Utc::now().to_rfc3339(),
+        }))
+        .unwrap();
+        let token = encrypt_license_v2(&SECRET, SECRET_VERSION, &license).unwrap();
+        general_purpose::STANDARD.encode(token).into_bytes()
+    }
+}
+
+impl Drop for TestAgent {
+    fn drop(&mut self) {
+        self.server.abort();
+        if let Some(runtime) = self.runtime.take() {
+            runtime.shutdown_background();
+        }
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+fn secret() -> Secret {
+    let now = Utc::now();
+    Secret {
+        data: SECRET.to_vec(),
+        metadata: SecretMetadata {
+            version: SECRET_VERSION,
+            state: SecretState::Actif,
+            valid_from: now,
+            valid_until: now + chrono::Duration::days(1),
+            grace_until: None,
+            created_at: now,
+            last_used_at: None,
+            rotation_source: RotationSource::Manual,
+            invalidation_reason: None,
+        },
+    }
+}
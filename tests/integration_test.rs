@@ -2,6 +2,7 @@
 
 #[cfg(test)]
 mod tests {
+    use license_agent_testing::{bind_current_uid, fixture, write_agent_config, TestAgent};
     use license_secret_agent::crypto::CryptoManager;
     use license_secret_agent::types::*;
 
@@ -37,4 +38,1420 @@ mod tests {
         assert!(!constant_time_compare(b"test", b"test2"));
         assert!(!constant_time_compare(b"test", b""));
     }
+
+    #[test]
+    fn test_protocol_request_envelope() {
+        use license_secret_agent::protocol::{IpcRequest, RequestEnvelope, PROTOCOL_VERSION};
+
+        // Format envoyé par les clients existants (sans champ version)
+        let json = serde_json::json!({
+            "command": "validate",
+            "data": { "license_token": [1, 2, 3], "nonce": vec![0u8; 16] }
+        });
+        let envelope: RequestEnvelope = serde_json::from_value(json).unwrap();
+        assert_eq!(envelope.version, PROTOCOL_VERSION);
+        assert_eq!(envelope.id, None);
+        match envelope.request {
+            IpcRequest::Validate(request) => assert_eq!(request.license_token, vec![1, 2, 3]),
+            other => panic!("unexpected request: {:?}", other),
+        }
+
+        let json = serde_json::json!({
+            "version": 1,
+            "id": 42,
+            "command": "invalidate",
+            "data": { "version": 4, "reason": null }
+        });
+        let envelope: RequestEnvelope = serde_json::from_value(json).unwrap();
+        assert_eq!(envelope.id, Some(42));
+        assert!(matches!(envelope.request, IpcRequest::Invalidate { version: 4, reason: None }));
+
+        let json = serde_json::json!({ "command": "unknown", "data": {} });
+        assert!(serde_json::from_value::<RequestEnvelope>(json).is_err());
+    }
+
+    #[test]
+    fn test_protocol_error_response() {
+        use license_secret_agent::protocol::{ErrorCode, ErrorPayload, ResponseEnvelope};
+
+        let error = AgentError::SecretExpired(3);
+        let envelope = ResponseEnvelope::error(Some(7), ErrorPayload::from(&error));
+        let json = serde_json::to_value(&envelope).unwrap();
+
+        assert_eq!(json["id"], 7);
+        assert_eq!(json["error"]["code"], "SECRET_EXPIRED");
+        assert!(json.get("data").is_none());
+
+        let parsed: ResponseEnvelope = serde_json::from_value(json).unwrap();
+        let error = parsed.into_result().unwrap_err();
+        assert_eq!(error.code, ErrorCode::SecretExpired);
+    }
+
+    #[test]
+    fn test_rotation_response_signature() {
+        use base64::{engine::general_purpose, Engine as _};
+        use license_secret_agent::crypto::verify_pss_with_key;
+        use license_secret_agent::rotation::RotateSecretResponse;
+
+        let (private_key, public_key) = CryptoManager::generate_keys().unwrap();
+        let server = CryptoManager::new(private_key, public_key);
+
+        let now = chrono::Utc::now();
+        let mut response = RotateSecretResponse {
+            agent_id: "agent-1".to_string(),
+            nonce: "00112233".to_string(),
+            new_secret_encrypted: "c2VjcmV0".to_string(),
+            version: 2,
+            valid_from: now,
+            valid_until: now + chrono::Duration::days(90),
+            grace_until: now + chrono::Duration::days(97),
+            signature: String::new(),
+        };
+        let signature = server.sign_pss(&response.canonical_bytes()).unwrap();
+        response.signature = general_purpose::STANDARD.encode(&signature);
+
+        // La forme canonique survit à un aller-retour JSON
+        let json = serde_json::to_string(&response).unwrap();
+        let mut received: RotateSecretResponse = serde_json::from_str(&json).unwrap();
+        assert_eq!(received.canonical_bytes(), response.canonical_bytes());
+        assert!(verify_pss_with_key(server.public_key(), &received.canonical_bytes(), &signature).unwrap());
+
+        // Toute modification invalide la signature
+        received.grace_until += chrono::Duration::days(365);
+        assert!(!verify_pss_with_key(server.public_key(), &received.canonical_bytes(), &signature).unwrap());
+        let mut replayed = response.clone();
+        replayed.nonce = "44556677".to_string();
+        assert!(!verify_pss_with_key(server.public_key(), &replayed.canonical_bytes(), &signature).unwrap());
+
+        // Réponse liée à la requête : agent, nonce et version strictement croissante
+        assert!(response.check_request_binding("agent-1", "00112233", 1).is_ok());
+        assert!(response.check_request_binding("agent-2", "00112233", 1).is_err());
+        assert!(response.check_request_binding("agent-1", "44556677", 1).is_err());
+        assert!(response.check_request_binding("agent-1", "00112233", 2).is_err());
+        assert!(response.check_request_binding("agent-1", "00112233", 3).is_err());
+    }
+
+    /// Handshake TLS en mémoire entre le client de rotation et un serveur mTLS
+    fn tls_handshake(cert_pin: &str) -> Result<bool, rustls::Error> {
+        use license_secret_agent::config::ServerConfig;
+        use license_secret_agent::tls::{build_client_config, load_certificates, load_private_key};
+        use rustls::server::AllowAnyAuthenticatedClient;
+        use std::sync::Arc;
+
+        let agent_server_config = ServerConfig {
+            url: "https://localhost:8443".to_string(),
+            cert_pin: cert_pin.to_string(),
+            client_cert: fixture("client.crt"),
+            client_key: fixture("client.key"),
+            public_key: None,
+            ca_bundle: None,
+            timeout_seconds: Some(5),
+        };
+        let client_config = build_client_config(&agent_server_config).unwrap();
+
+        let mut client_roots = rustls::RootCertStore::empty();
+        client_roots.add(&load_certificates(&fixture("client.crt")).unwrap()[0]).unwrap();
+        let server_config = rustls::ServerConfig::builder()
+            .with_safe_defaults()
+            .with_client_cert_verifier(Arc::new(AllowAnyAuthenticatedClient::new(client_roots)))
+            .with_single_cert(
+                load_certificates(&fixture("server.crt")).unwrap(),
+                load_private_key(&fixture("server.key")).unwrap(),
+            )
+            .unwrap();
+
+        let mut client = rustls::ClientConnection::new(
+            Arc::new(client_config),
+            "localhost".try_into().unwrap(),
+        )
+        .unwrap();
+        let mut server = rustls::ServerConnection::new(Arc::new(server_config)).unwrap();
+
+        while client.is_handshaking() || server.is_handshaking() {
+            let mut buf = Vec::new();
+            client.write_tls(&mut buf).unwrap();
+            server.read_tls(&mut buf.as_slice()).unwrap();
+            server.process_new_packets()?;
+
+            let mut buf = Vec::new();
+            server.write_tls(&mut buf).unwrap();
+            client.read_tls(&mut buf.as_slice()).unwrap();
+            client.process_new_packets()?;
+        }
+
+        Ok(server.peer_certificates().is_some())
+    }
+
+    #[test]
+    fn test_tls_pinning_and_client_auth() {
+        use license_secret_agent::tls::{load_certificates, parse_cert_pins, spki_sha256};
+
+        let server_cert = &load_certificates(&fixture("server.crt")).unwrap()[0];
+        let pin = spki_sha256(&server_cert.0).unwrap();
+        assert_eq!(parse_cert_pins("sha256/St3/D8wuEJx5VmyNFsyhmR4yq0yZ8KkFWip2zG2q6Gw=").unwrap(), vec![pin]);
+        assert_eq!(parse_cert_pins(&hex::encode(pin)).unwrap(), vec![pin]);
+        assert!(parse_cert_pins("sha256/dG9vIHNob3J0").is_err());
+
+        // Pin correct : handshake réussi et certificat client présenté
+        let client_authenticated = tls_handshake("sha256/St3/D8wuEJx5VmyNFsyhmR4yq0yZ8KkFWip2zG2q6Gw=").unwrap();
+        assert!(client_authenticated);
+
+        // Pin incorrect : connexion refusée
+        let wrong_pin = format!("sha256/{}", "A".repeat(43) + "=");
+        assert!(tls_handshake(&wrong_pin).is_err());
+
+        // Certificat ou clé client manquant ou illisible : pas de repli sans mTLS
+        use license_secret_agent::config::ServerConfig;
+        use license_secret_agent::tls::build_client_config;
+        let server = |client_cert: &str, client_key: &str| ServerConfig {
+            url: "https://localhost:8443".to_string(),
+            cert_pin: String::new(),
+            client_cert: fixture(client_cert),
+            client_key: fixture(client_key),
+            public_key: None,
+            ca_bundle: None,
+            timeout_seconds: Some(5),
+        };
+        assert!(build_client_config(&server("client.crt", "client.key")).is_ok());
+        assert!(build_client_config(&server("client.crt", "missing.key")).is_err());
+        assert!(build_client_config(&server("missing.crt", "client.key")).is_err());
+        assert!(build_client_config(&server("client.key", "client.key")).is_err());
+    }
+
+    #[test]
+    fn test_management_api_authentication() {
+        use license_secret_agent::api::{ApiAuth, ClientIdentity};
+        use license_secret_agent::config::ManagementConfig;
+        use license_secret_agent::crypto::sha256;
+        use license_secret_agent::roles::Role;
+        use license_secret_agent::tls::{build_api_server_config, load_certificates, load_private_key};
+        use std::sync::Arc;
+
+        let management: ManagementConfig = toml::from_str(&format!(
+            "allowed_uids = []\napi_port = 8443\napi_token_sha256 = [\"{}\"]\napi_client_ca = \"{}\"\n\
+             [api_roles.admin]\ntoken_sha256 = [\"{}\"]\ncertificate_sha256 = [\"{}\"]\n",
+            hex::encode(sha256(b"fleet-token")),
+            fixture("client.crt").display(),
+            hex::encode(sha256(b"admin-token")),
+            "cd".repeat(32),
+        ))
+        .unwrap();
+        let auth = ApiAuth::from_config(&management).unwrap();
+        assert!(auth.is_enabled());
+
+        let headers = |value: &str| {
+            let mut headers = axum::http::HeaderMap::new();
+            headers.insert(axum::http::header::AUTHORIZATION, value.parse().unwrap());
+            headers
+        };
+        let anonymous = ClientIdentity::default();
+        let fleet = auth.authenticate(&headers("Bearer fleet-token"), &anonymous).unwrap();
+        assert!(fleet.name.starts_with("token:"));
+        assert_eq!(fleet.role, Role::Operator);
+        assert_eq!(auth.authenticate(&headers("Bearer admin-token"), &anonymous).unwrap().role, Role::Admin);
+        assert!(auth.authenticate(&headers("Bearer wrong-token"), &anonymous).is_none());
+        assert!(auth.authenticate(&axum::http::HeaderMap::new(), &anonymous).is_none());
+
+        // Certificat vérifié : rôle de `api_roles`, aucun s'il n'y est pas listé
+        let client = ClientIdentity { certificate_sha256: Some("ab".repeat(32)) };
+        assert!(auth.authenticate(&axum::http::HeaderMap::new(), &client).is_none());
+        assert_eq!(auth.authenticate(&headers("Bearer fleet-token"), &client).unwrap().role, Role::Operator);
+        let client = ClientIdentity { certificate_sha256: Some("cd".repeat(32)) };
+        let principal = auth.authenticate(&axum::http::HeaderMap::new(), &client).unwrap();
+        assert!(principal.name.starts_with("cert:"));
+        assert_eq!(principal.role, Role::Admin);
+
+        // Handshake mTLS contre la configuration de l'API : certificat client vérifié
+        let server_config = build_api_server_config(
+            &fixture("server.crt"),
+            &fixture("server.key"),
+            Some(&fixture("client.crt")),
+        )
+        .unwrap();
+        let mut roots = rustls::RootCertStore::empty();
+        roots.add(&load_certificates(&fixture("server.crt")).unwrap()[0]).unwrap();
+        let client_config = rustls::ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(roots)
+            .with_client_auth_cert(
+                load_certificates(&fixture("client.crt")).unwrap(),
+                load_private_key(&fixture("client.key")).unwrap(),
+            )
+            .unwrap();
+
+        let mut client = rustls::ClientConnection::new(Arc::new(client_config), "localhost".try_into().unwrap()).unwrap();
+        let mut server = rustls::ServerConnection::new(Arc::new(server_config)).unwrap();
+        while client.is_handshaking() || server.is_handshaking() {
+            let mut buf = Vec::new();
+            client.write_tls(&mut buf).unwrap();
+            server.read_tls(&mut buf.as_slice()).unwrap();
+            server.process_new_packets().unwrap();
+
+            let mut buf = Vec::new();
+            server.write_tls(&mut buf).unwrap();
+            client.read_tls(&mut buf.as_slice()).unwrap();
+            client.process_new_packets().unwrap();
+        }
+        assert!(server.peer_certificates().is_some());
+    }
+
+    /// Envoie une requête HTTP brute et renvoie le code de statut (au plus 5 secondes)
+    async fn http_status(addr: std::net::SocketAddr, request: &str) -> u16 {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = Vec::new();
+        tokio::time::timeout(std::time::Duration::from_secs(5), stream.read_to_end(&mut response))
+            .await
+            .expect("no HTTP response")
+            .unwrap();
+        String::from_utf8_lossy(&response).split(' ').nth(1).unwrap().parse().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_management_api_roles_and_timeout() {
+        use license_secret_agent::api::ApiServer;
+        use license_secret_agent::config::{ApiRoleBinding, ApiRolesConfig, Config};
+        use license_secret_agent::core::CoreEngine;
+        use license_secret_agent::crypto::sha256;
+        use std::sync::Arc;
+
+        let dir = temp_dir("api");
+        let config_path = dir.join("config.toml");
+        write_agent_config(
+            &config_path,
+            &format!("[paths]\nstate_dir = \"{dir}/state\"\naudit_log = \"{dir}/audit.log\"", dir = dir.display()),
+        );
+        let mut config = Config::load_from_path(&config_path).unwrap();
+        config.management.api_token_sha256 = vec![hex::encode(sha256(b"operator-token"))];
+        config.management.api_roles = Some(ApiRolesConfig {
+            admin: ApiRoleBinding { token_sha256: vec![hex::encode(sha256(b"admin-token"))], ..Default::default() },
+            ..Default::default()
+        });
+        config.management.api_request_timeout_seconds = Some(1);
+        config.management.api_handshake_timeout_seconds = Some(1);
+        config.management.api_max_connections = Some(1);
+
+        let core = Arc::new(CoreEngine::new(config.clone()).await.unwrap());
+        let server = ApiServer::bind(&config, "127.0.0.1:0".parse().unwrap(), core).await.unwrap();
+        let addr = server.local_addr().unwrap();
+        let shutdown = Arc::new(tokio::sync::Notify::new());
+        tokio::spawn(server.run(Arc::clone(&shutdown)));
+
+        let request = |method: &str, path: &str, token: &str| {
+            format!("{method} {path} HTTP/1.1\r\nHost: localhost\r\nAuthorization: Bearer {token}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+        };
+
+        // Jeton `operator` : commandes d'exploitation uniquement
+        assert_eq!(http_status(addr, &request("GET", "/api/v1/status", "operator-token")).await, 200);
+        assert_eq!(http_status(addr, &request("POST", "/api/v1/secrets/7/invalidate", "operator-token")).await, 403);
+        assert_eq!(http_status(addr, &request("GET", "/api/v1/degraded-mode", "operator-token")).await, 403);
+        assert_eq!(http_status(addr, &request("GET", "/api/v1/status", "wrong-token")).await, 401);
+
+        // Jeton `admin` : la commande s'exécute (secret inconnu)
+        assert_eq!(http_status(addr, &request("POST", "/api/v1/secrets/7/invalidate", "admin-token")).await, 404);
+
+        // Corps annoncé mais jamais envoyé : réponse 503 après le délai
+        let started = std::time::Instant::now();
+        let stalled = "POST /api/v1/rotate HTTP/1.1\r\nHost: localhost\r\nAuthorization: Bearer admin-token\r\n\
+                       Content-Type: application/json\r\nContent-Length: 64\r\nConnection: close\r\n\r\n{";
+        assert_eq!(http_status(addr, stalled).await, 503);
+        assert!(started.elapsed() >= std::time::Duration::from_millis(900));
+
+        // En-têtes jamais terminés : la connexion occupe la seule place jusqu'au délai de lecture
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        let mut slow = tokio::net::TcpStream::connect(addr).await.unwrap();
+        slow.write_all(b"GET /healthz HTTP/1.1\r\nHost: local").await.unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        let started = std::time::Instant::now();
+        assert_eq!(http_status(addr, &request("GET", "/api/v1/status", "operator-token")).await, 200);
+        assert!(started.elapsed() >= std::time::Duration::from_millis(700));
+        let mut response = Vec::new();
+        slow.read_to_end(&mut response).await.unwrap();
+        assert!(!String::from_utf8_lossy(&response).contains(" 200 "));
+
+        shutdown.notify_waiters();
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_ipc_rate_limiter_budgets() {
+        use license_secret_agent::metrics::create_metrics;
+        use license_secret_agent::ratelimit::{IpcRateLimiter, RequestClass};
+
+        let (_registry, metrics) = create_metrics().unwrap();
+        let limiter = IpcRateLimiter::new(Some(2), Some(1)).with_metrics(std::sync::Arc::clone(&metrics));
+
+        // Budget de validation épuisé pour le processus 10
+        assert!(limiter.check(RequestClass::Validation, 1000, 10, "validate").await.is_ok());
+        assert!(limiter.check(RequestClass::Validation, 1000, 10, "validate").await.is_ok());
+        let retry_after = limiter.check(RequestClass::Validation, 1000, 10, "validate").await.unwrap_err();
+        assert!(retry_after > std::time::Duration::ZERO);
+
+        // Budget d'administration séparé, autres processus non affectés
+        assert!(limiter.check(RequestClass::Admin, 1000, 10, "status").await.is_ok());
+        assert!(limiter.check(RequestClass::Admin, 1000, 10, "status").await.is_err());
+        assert!(limiter.check(RequestClass::Validation, 1000, 11, "validate").await.is_ok());
+
+        // Budget par UID : 4 fois celui d'un processus
+        for pid in 12..17 {
+            assert!(limiter.check(RequestClass::Validation, 1000, pid, "validate").await.is_ok());
+        }
+        assert!(limiter.check(RequestClass::Validation, 1000, 20, "validate").await.is_err());
+        assert!(limiter.check(RequestClass::Validation, 1001, 21, "validate").await.is_ok());
+
+        assert_eq!(metrics.ipc_rate_limited.with_label_values(&["validation"]).get(), 2.0);
+        assert_eq!(metrics.ipc_rate_limited.with_label_values(&["admin"]).get(), 1.0);
+
+        // Refus pour l'UID : le budget du processus n'est pas entamé
+        let limiter = IpcRateLimiter::new(Some(1), None);
+        for pid in 1..=4 {
+            assert!(limiter.check(RequestClass::Validation, 1000, pid, "validate").await.is_ok());
+        }
+        for _ in 0..3 {
+            let retry_after = limiter.check(RequestClass::Validation, 1000, 5, "validate").await.unwrap_err();
+            assert!(retry_after <= std::time::Duration::from_secs(15), "{:?}", retry_after);
+        }
+
+        // Sans limite configurée, aucune requête n'est refusée
+        let unlimited = IpcRateLimiter::new(None, None);
+        for _ in 0..100 {
+            assert!(unlimited.check(RequestClass::Validation, 1000, 10, "validate").await.is_ok());
+        }
+    }
+
+    #[test]
+    fn test_admin_authentication() {
+        use base64::{engine::general_purpose, Engine as _};
+        use license_secret_agent::admin::{AdminAuthenticator, Challenge};
+        use license_secret_agent::config::ManagementConfig;
+        use license_secret_agent::crypto::sha256;
+        use license_secret_agent::protocol::{admin_challenge_message, AdminCredential};
+        use license_secret_agent::roles::{Peer, Role, RoleResolver};
+        use license_secret_agent::tls::load_certificates;
+
+        let management: ManagementConfig = toml::from_str(&format!(
+            "allowed_uids = [1000]\nadmin_uids = [0]\nadmin_token_sha256 = [\"{}\"]\nadmin_certificates = [\"{}\"]\n",
+            hex::encode(sha256(b"admin-token")),
+            fixture("client.crt").display(),
+        ))
+        .unwrap();
+        let auth = AdminAuthenticator::from_config(&management).unwrap();
+
+        // Sans section `[management.roles]` : `admin_uids` donne le rôle admin
+        let roles = RoleResolver::from_config(&management);
+        assert_eq!(roles.resolve(&Peer::from_credentials(0, 0, 1)), Some(Role::Admin));
+        assert_eq!(roles.resolve(&Peer::from_credentials(1000, 1000, 1)), Some(Role::App));
+        assert_eq!(roles.resolve(&Peer::from_credentials(1001, 1001, 1)), None);
+
+        // Jeton d'administration
+        let token = |token: &str| AdminCredential::Token { token: token.to_string() };
+        assert!(auth.authorize(Some(&token("admin-token")), None, "reset").unwrap().starts_with("token:"));
+        assert!(auth.authorize(Some(&token("wrong-token")), None, "reset").is_err());
+        assert!(auth.authorize(None, None, "reset").is_err());
+
+        // Signature du challenge de la connexion avec la clé de l'administrateur
+        let fingerprint = hex::encode(sha256(&load_certificates(&fixture("client.crt")).unwrap()[0].0));
+        let signer = CryptoManager::from_pem_files(fixture("client.key").to_str().unwrap(), None).unwrap();
+        let challenge = Challenge::new();
+        let signed = |challenge: &Challenge, command: &str| AdminCredential::Signature {
+            certificate_sha256: fingerprint.clone(),
+            challenge: hex::encode(challenge.nonce()),
+            signature: general_purpose::STANDARD
+                .encode(signer.sign_pss(&admin_challenge_message(challenge.nonce(), command)).unwrap()),
+        };
+
+        let credential = signed(&challenge, "reset");
+        assert!(auth.authorize(Some(&credential), Some(&challenge), "reset").unwrap().starts_with("cert:"));
+        // Signature liée à la commande et au challenge émis
+        assert!(auth.authorize(Some(&credential), Some(&challenge), "rotate").is_err());
+        assert!(auth.authorize(Some(&credential), None, "reset").is_err());
+        assert!(auth.authorize(Some(&credential), Some(&Challenge::new()), "reset").is_err());
+    }
+
+    #[test]
+    fn test_role_based_authorization() {
+        use license_secret_agent::config::ManagementConfig;
+        use license_secret_agent::protocol::IpcRequest;
+        use license_secret_agent::roles::{Peer, Role, RoleResolver};
+
+        let exe = std::env::current_exe().unwrap();
+        let management: ManagementConfig = toml::from_str(&format!(
+            "allowed_uids = [1000]\n\
+             [roles.app]\nexecutables = [\"{}\"]\n\
+             [roles.operator]\ngids = [990]\n\
+             [roles.admin]\nuids = [0]\n",
+            exe.display(),
+        ))
+        .unwrap();
+        let roles = RoleResolver::from_config(&management);
+
+        // Rôle par UID, par GID (principal ou supplémentaire) et par exécutable
+        let pid = std::process::id();
+        assert_eq!(roles.resolve(&Peer::from_credentials(0, 0, u32::MAX)), Some(Role::Admin));
+        assert_eq!(roles.resolve(&Peer::from_credentials(2000, 990, u32::MAX)), Some(Role::Operator));
+        assert_eq!(roles.resolve(&Peer::from_credentials(2000, 2000, pid)), Some(Role::App));
+        // Le rôle le plus élevé l'emporte
+        assert_eq!(roles.resolve(&Peer::from_credentials(0, 990, pid)), Some(Role::Admin));
+        // La section `roles` remplace `allowed_uids`
+        assert_eq!(roles.resolve(&Peer::from_credentials(1000, 1000, u32::MAX)), None);
+
+        // Processus de la connexion identifié par sa date de démarrage
+        assert!(Peer::from_credentials(2000, 2000, pid).is_same_process());
+        assert!(!Peer::from_credentials(2000, 2000, u32::MAX).is_same_process());
+
+        let request = |json: serde_json::Value| serde_json::from_value::<IpcRequest>(json).unwrap();
+        let validate = request(serde_json::json!({"command": "validate", "data": {"license_token": [], "nonce": vec![0u8; 16]}}));
+        let status = request(serde_json::json!({"command": "status", "data": {}}));
+        let rotate = request(serde_json::json!({"command": "rotate", "data": {"force": true}}));
+        let reset = request(serde_json::json!({"command": "reset", "data": {}}));
+        assert_eq!(validate.required_role(), Role::App);
+        assert_eq!(status.required_role(), Role::Operator);
+        assert_eq!(rotate.required_role(), Role::Operator);
+        assert_eq!(reset.required_role(), Role::Admin);
+        assert!(Role::Operator >= status.required_role());
+        assert!(Role::Operator < reset.required_role());
+    }
+
+    #[test]
+    fn test_executable_allowlist_manifests() {
+        use base64::{engine::general_purpose, Engine as _};
+        use license_secret_agent::config::ExecutableAllowlistConfig;
+        use license_secret_agent::crypto::sha256;
+        use license_secret_agent::executable::ExecutableVerifier;
+        use license_secret_agent::roles::process_start_time;
+
+        let dir = temp_dir("manifests");
+        let signer = CryptoManager::from_pem_files(fixture("client.key").to_str().unwrap(), None).unwrap();
+        std::fs::write(dir.join("signing.pem"), signer.export_public_key_pem().unwrap()).unwrap();
+        let manifests = dir.join("manifests.d");
+        std::fs::create_dir_all(&manifests).unwrap();
+
+        let digest = hex::encode(sha256(&std::fs::read(std::env::current_exe().unwrap()).unwrap()));
+        let pid = std::process::id();
+        let start_time = process_start_time(pid);
+        assert!(start_time.is_some());
+
+        // Empreinte listée dans la configuration
+        let verifier = ExecutableVerifier::from_config(&ExecutableAllowlistConfig {
+            sha256: vec![digest.clone()],
+            ..Default::default()
+        })
+        .unwrap();
+        let check = verifier.verify(pid, start_time);
+        assert!(check.is_allowed());
+        assert_eq!(check.source.as_deref(), Some("config"));
+        assert_eq!(check.sha256.as_deref(), Some(digest.as_str()));
+
+        // Vérification liée au processus de la connexion : PID réattribué refusé
+        let check = verifier.verify(pid, start_time.map(|t| t + 1));
+        assert!(!check.is_allowed());
+        assert!(check.error.unwrap().contains("replaced"));
+        assert!(!verifier.verify(pid, None).is_allowed());
+
+        let config = ExecutableAllowlistConfig {
+            sha256: vec![],
+            manifest_dir: Some(manifests.clone()),
+            manifest_public_key: Some(dir.join("signing.pem")),
+        };
+        let check = ExecutableVerifier::from_config(&config).unwrap().verify(pid, start_time);
+        assert!(!check.is_allowed());
+        assert!(check.error.is_some());
+
+        // Manifeste de mise à jour : ignoré si sa signature ne correspond pas
+        let manifest = serde_json::to_vec(&serde_json::json!({
+            "name": "app",
+            "version": "2.0",
+            "sha256": [digest],
+        }))
+        .unwrap();
+        let sign = |data: &[u8]| general_purpose::STANDARD.encode(signer.sign_pss(data).unwrap());
+        std::fs::write(manifests.join("app.json"), &manifest).unwrap();
+        std::fs::write(manifests.join("app.json.sig"), sign(b"other manifest")).unwrap();
+        assert!(!ExecutableVerifier::from_config(&config).unwrap().verify(pid, start_time).is_allowed());
+
+        std::fs::write(manifests.join("app.json.sig"), sign(&manifest)).unwrap();
+        let check = ExecutableVerifier::from_config(&config).unwrap().verify(pid, start_time);
+        assert!(check.is_allowed());
+        assert_eq!(check.source.as_deref(), Some("manifest:app@2.0"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_validation_response_mac_and_replay() {
+        use license_secret_agent::app_auth::{
+            compute_response_mac, verify_validation_response, AppKeyStore, NonceCache, NonceRejection,
+        };
+
+        let dir = temp_dir("apps");
+        let store = AppKeyStore::new(dir.join("apps"));
+        let key = store.register("billing").await.unwrap();
+        assert_eq!(key.len(), 32);
+        assert!(store.register("../billing").await.is_err());
+        assert!(store.key("unknown").await.is_err());
+        // Clé relue depuis le fichier après redémarrage
+        assert_eq!(*AppKeyStore::new(dir.join("apps")).key("billing").await.unwrap(), *key);
+
+        let mut metadata = std::collections::HashMap::new();
+        metadata.insert("customer".to_string(), "acme".to_string());
+        metadata.insert("seats".to_string(), "25".to_string());
+        let result = ValidationResult {
+            valid: true,
+            expires_at: Some(chrono::Utc::now()),
+            features: vec!["premium".to_string()],
+            metadata,
+            error: None,
+        };
+        let nonce = [7u8; 16];
+        let token = b"license-token".to_vec();
+        let mac = compute_response_mac(&key, &nonce, &token, &result).unwrap();
+        let response = ValidateLicenseResponse { result, mac: Some(mac) };
+
+        // Vérification après transport JSON (ordre des métadonnées indifférent)
+        let response: ValidateLicenseResponse =
+            serde_json::from_slice(&serde_json::to_vec(&response).unwrap()).unwrap();
+        assert!(verify_validation_response(&key, &nonce, &token, &response).is_ok());
+
+        // Réponse à une autre requête, falsifiée, non authentifiée ou d'une autre clé
+        assert!(verify_validation_response(&key, &[8u8; 16], &token, &response).is_err());
+        assert!(verify_validation_response(&key, &nonce, b"other-token", &response).is_err());
+        assert!(verify_validation_response(&[1u8; 32], &nonce, &token, &response).is_err());
+        let mut forged = serde_json::to_value(&response).unwrap();
+        forged["result"]["features"] = serde_json::json!(["premium", "enterprise"]);
+        let forged: ValidateLicenseResponse = serde_json::from_value(forged).unwrap();
+        assert!(verify_validation_response(&key, &nonce, &token, &forged).is_err());
+        let unsigned = ValidateLicenseResponse { mac: None, ..response };
+        assert!(verify_validation_response(&key, &nonce, &token, &unsigned).is_err());
+
+        // Cache anti-rejeu : un nonce par UID, oublié après expiration
+        let nonces = NonceCache::new(std::time::Duration::from_millis(50));
+        assert_eq!(nonces.check_and_insert(1000, &nonce), Ok(()));
+        assert_eq!(nonces.check_and_insert(1000, &nonce), Err(NonceRejection::Replayed));
+        assert_eq!(nonces.check_and_insert(1001, &nonce), Ok(()));
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        assert_eq!(nonces.check_and_insert(1000, &nonce), Ok(()));
+
+        // Limite par UID : nouveaux nonces refusés, aucun nonce non expiré oublié
+        let nonces = NonceCache::new(std::time::Duration::from_millis(200)).with_max_per_uid(2);
+        assert_eq!(nonces.check_and_insert(1000, &[1u8; 16]), Ok(()));
+        assert_eq!(nonces.check_and_insert(1000, &[2u8; 16]), Ok(()));
+        assert_eq!(nonces.check_and_insert(1000, &[3u8; 16]), Err(NonceRejection::CacheFull));
+        assert_eq!(nonces.check_and_insert(1000, &[1u8; 16]), Err(NonceRejection::Replayed));
+        assert_eq!(nonces.check_and_insert(1001, &[3u8; 16]), Ok(()));
+        tokio::time::sleep(std::time::Duration::from_millis(250)).await;
+        assert_eq!(nonces.check_and_insert(1000, &[3u8; 16]), Ok(()));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    fn temp_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("lsa-{}-{}", name, uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn test_secret(version: u64, byte: u8) -> Secret {
+        let now = chrono::Utc::now();
+        Secret {
+            data: vec![byte; 32],
+            metadata: SecretMetadata {
+                version,
+                state: SecretState::Actif,
+                valid_from: now,
+                valid_until: now + chrono::Duration::days(1),
+                grace_until: None,
+                created_at: now,
+                last_used_at: None,
+                rotation_source: RotationSource::Manual,
+                invalidation_reason: None,
+            },
+        }
+    }
+
+    fn software_secret_manager(dir: &std::path::Path) -> license_secret_agent::secret::SecretManager {
+        use license_secret_agent::kek::KeyHierarchy;
+        use license_secret_agent::secret::SecretManager;
+        use license_secret_agent::tpm::TpmManager;
+        use std::sync::Arc;
+
+        let kek = KeyHierarchy::open(dir.join("kek.json"), b"test-seed", b"machine-a").unwrap();
+        let tpm = Arc::new(TpmManager::new(false, Arc::new(kek), None).unwrap());
+        SecretManager::new(tpm, dir.join("state.json"), dir.join("secrets"))
+    }
+
+    #[tokio::test]
+    async fn test_secret_manager_sealed_store_without_tpm() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = temp_dir("store");
+        let manager = software_secret_manager(&dir);
+
+        manager.store_secret(test_secret(1, 7), 1).await.unwrap();
+        assert_eq!(manager.get_active_secret().await.unwrap().data, vec![7u8; 32]);
+
+        let blob_path = dir.join("secrets/secret-0000000000000001.sealed");
+        let mode = std::fs::metadata(&blob_path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        // Un blob altéré est détecté
+        let mut blob = std::fs::read(&blob_path).unwrap();
+        blob[20] ^= 0xff;
+        std::fs::write(&blob_path, &blob).unwrap();
+        assert!(matches!(manager.get_secret(1).await, Err(AgentError::SecretInvalid(_))));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_kek_rekey_and_machine_binding() {
+        use license_secret_agent::kek::KeyHierarchy;
+
+        let dir = temp_dir("kek");
+        let manager = software_secret_manager(&dir);
+        manager.store_secret(test_secret(1, 1), 1).await.unwrap();
+        manager.store_secret(test_secret(2, 2), 2).await.unwrap();
+
+        let kek_before = std::fs::read_to_string(dir.join("kek.json")).unwrap();
+        assert_eq!(manager.rekey().await.unwrap(), 2);
+        assert_ne!(std::fs::read_to_string(dir.join("kek.json")).unwrap(), kek_before);
+
+        // Les secrets restent lisibles après rechiffrement, y compris après redémarrage
+        let manager = software_secret_manager(&dir);
+        manager.load_state().await.unwrap();
+        assert_eq!(manager.get_secret(1).await.unwrap().data, vec![1u8; 32]);
+        assert_eq!(manager.get_secret(2).await.unwrap().data, vec![2u8; 32]);
+
+        // La KEK ne se déballe pas sur une autre machine ou avec une autre graine
+        assert!(KeyHierarchy::open(dir.join("kek.json"), b"test-seed", b"machine-b").is_err());
+        assert!(KeyHierarchy::open(dir.join("kek.json"), b"other-seed", b"machine-a").is_err());
+
+        // Changement de graine : la KEK est ré-enveloppée, les secrets restent lisibles
+        let kek = KeyHierarchy::open(dir.join("kek.json"), b"test-seed", b"machine-a").unwrap();
+        let (id, data_key) = kek.current_data_key(1).unwrap();
+        let kek = KeyHierarchy::open_with_previous_seed(dir.join("kek.json"), b"new-seed", b"test-seed", b"machine-a")
+            .unwrap();
+        assert_eq!(kek.current_data_key(1).unwrap(), (id, data_key));
+        assert!(KeyHierarchy::open(dir.join("kek.json"), b"test-seed", b"machine-a").is_err());
+        assert!(KeyHierarchy::open(dir.join("kek.json"), b"new-seed", b"machine-a").is_ok());
+        assert!(
+            KeyHierarchy::open_with_previous_seed(dir.join("kek.json"), b"other-seed", b"test-seed", b"machine-a")
+                .is_err()
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_kek_default_seed_detection() {
+        use license_secret_agent::config::Config;
+        use license_secret_agent::kek::{KeyHierarchy, DEFAULT_SEED};
+
+        let dir = temp_dir("kek-seed");
+        let config_path = dir.join("config.toml");
+        write_agent_config(
+            &config_path,
+            &format!(
+                "[paths]\nstate_dir = \"{dir}/state\"\naudit_log = \"{dir}/audit.log\"",
+                dir = dir.display()
+            ),
+        );
+        let mut config = Config::load_from_path(&config_path).unwrap();
+
+        // Sans `production_mode`, la graine par défaut n'est qu'un avertissement
+        assert!(!config.agent.production_mode);
+        let kek = KeyHierarchy::from_config(&config).unwrap();
+        assert!(kek.uses_default_seed());
+        let data_key = kek.current_data_key(1).unwrap();
+
+        // Graine par défaut recopiée dans le fichier de graine : détectée et refusée en production
+        let seed_path = dir.join("kek.seed");
+        std::fs::write(&seed_path, format!("{}\n", DEFAULT_SEED)).unwrap();
+        config.tpm.kek_seed_file = Some(seed_path.clone());
+        assert!(KeyHierarchy::from_config(&config).unwrap().uses_default_seed());
+        config.agent.production_mode = true;
+        let error = KeyHierarchy::from_config(&config).err().unwrap();
+        assert!(error.to_string().contains("default key seed"));
+
+        // Migration : nouvelle graine, KEK existante ré-enveloppée sans perte
+        std::fs::write(&seed_path, [0x42u8; 32]).unwrap();
+        let kek = KeyHierarchy::from_config(&config).unwrap();
+        assert!(!kek.uses_default_seed());
+        assert_eq!(kek.current_data_key(1).unwrap(), data_key);
+        assert_eq!(KeyHierarchy::from_config(&config).unwrap().current_data_key(1).unwrap(), data_key);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_license_token_v2_authenticated_header() {
+        use aes_gcm::{aead::Aead, Aes256Gcm, KeyInit, Nonce};
+        use base64::{engine::general_purpose, Engine as _};
+        use license_secret_agent::license::{encrypt_license_v2, LicenseValidator};
+        use license_secret_agent::types::ValidationFailureReason;
+        use std::sync::Arc;
+
+        let dir = temp_dir("license");
+        let manager = Arc::new(software_secret_manager(&dir));
+        manager.store_secret(test_secret(1, 7), 1).await.unwrap();
+        let secret = vec![7u8; 32];
+
+        let license = serde_json::to_vec(&serde_json::json!({
+            "license_id": "lic-1",
+            "customer_id": "cust-1",
+            "features": ["pos"],
+            "metadata": {},
+            "expires_at": (chrono::Utc::now() + chrono::Duration::days(1)).to_rfc3339(),
+            "issued_at": chrono::Utc::now().to_rfc3339(),
+        }))
+        .unwrap();
+
+        let validator = LicenseValidator::new(Arc::clone(&manager), false);
+        let token = encrypt_license_v2(&secret, 1, &license).unwrap();
+        assert_eq!(&token[..5], b"LSAT\x02");
+        let result = validator.validate(general_purpose::STANDARD.encode(&token).as_bytes()).await.unwrap();
+        assert!(result.valid);
+        assert_eq!(result.features, vec!["pos".to_string()]);
+
+        // En-tête altéré (key ID) : rejeté
+        let mut tampered = token.clone();
+        tampered[13] ^= 0x01;
+        assert!(validator.validate(general_purpose::STANDARD.encode(&tampered).as_bytes()).await.is_err());
+
+        // Token v1 : refusé par défaut, accepté derrière `accept_v1_tokens`
+        let nonce = [3u8; 12];
+        let ciphertext = Aes256Gcm::new_from_slice(&secret)
+            .unwrap()
+            .encrypt(Nonce::from_slice(&nonce), license.as_slice())
+            .unwrap();
+        let v1_token = general_purpose::STANDARD.encode([1u64.to_be_bytes().as_slice(), &nonce, &ciphertext].concat());
+        assert!(validator.validate(v1_token.as_bytes()).await.is_err());
+        let stats = validator.get_stats().await;
+        assert_eq!(stats.failures_by_reason.get(&ValidationFailureReason::DisabledFormat), Some(&1));
+        assert_eq!(stats.failures_by_reason.get(&ValidationFailureReason::MalformedToken), None);
+        let legacy_validator = LicenseValidator::new(Arc::clone(&manager), true);
+        assert!(legacy_validator.validate(v1_token.as_bytes()).await.unwrap().valid);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_validation_stats_persisted() {
+        use base64::{engine::general_purpose, Engine as _};
+        use license_secret_agent::license::{encrypt_license_v2, LicenseValidator};
+        use license_secret_agent::types::ValidationFailureReason;
+        use std::sync::Arc;
+
+        let dir = temp_dir("stats");
+        let manager = Arc::new(software_secret_manager(&dir));
+        manager.store_secret(test_secret(1, 7), 1).await.unwrap();
+        let validator = LicenseValidator::new(Arc::clone(&manager), false);
+
+        let license = |expires_at: chrono::DateTime<chrono::Utc>| {
+            serde_json::to_vec(&serde_json::json!({
+                "license_id": "lic-1",
+                "customer_id": "cust-1",
+                "features": [],
+                "metadata": {},
+                "expires_at": expires_at.to_rfc3339(),
+                "issued_at": chrono::Utc::now().to_rfc3339(),
+            }))
+            .unwrap()
+        };
+        let encode = |token: Vec<u8>| general_purpose::STANDARD.encode(token);
+
+        let valid = encode(encrypt_license_v2(&[7u8; 32], 1, &license(chrono::Utc::now() + chrono::Duration::days(1))).unwrap());
+        let expired = encode(encrypt_license_v2(&[7u8; 32], 1, &license(chrono::Utc::now() - chrono::Duration::days(1))).unwrap());
+        let unknown = encode(encrypt_license_v2(&[7u8; 32], 9, &license(chrono::Utc::now())).unwrap());
+
+        assert!(validator.validate(valid.as_bytes()).await.is_ok());
+        assert!(validator.validate(expired.as_bytes()).await.is_err());
+        assert!(validator.validate(unknown.as_bytes()).await.is_err());
+        assert!(validator.validate(b"not base64!").await.is_err());
+
+        let stats = validator.get_stats().await;
+        assert_eq!(stats.total_validations, 4);
+        assert_eq!(stats.successful_validations, 1);
+        assert_eq!(stats.failed_validations, 3);
+        assert!(stats.last_error.as_deref().unwrap().contains("base64"));
+        for reason in [
+            ValidationFailureReason::Expired,
+            ValidationFailureReason::UnknownVersion,
+            ValidationFailureReason::MalformedToken,
+        ] {
+            assert_eq!(stats.failures_by_reason.get(&reason), Some(&1));
+        }
+
+        // Les compteurs survivent à un redémarrage
+        manager.save_state().await.unwrap();
+        let reloaded = Arc::new(software_secret_manager(&dir));
+        reloaded.load_state().await.unwrap();
+        let restored = LicenseValidator::new(reloaded, false).get_stats().await;
+        assert_eq!(restored.total_validations, 4);
+        assert_eq!(restored.failures_by_reason, stats.failures_by_reason);
+        assert_eq!(restored.last_validation, stats.last_validation);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_validation_metrics_exported() {
+        use base64::{engine::general_purpose, Engine as _};
+        use license_secret_agent::license::{encrypt_license_v2, LicenseValidator};
+        use license_secret_agent::metrics::create_metrics;
+        use prometheus::Encoder;
+        use std::sync::Arc;
+
+        let dir = temp_dir("metrics");
+        let manager = Arc::new(software_secret_manager(&dir));
+        manager.store_secret(test_secret(1, 7), 1).await.unwrap();
+
+        let (registry, metrics) = create_metrics().unwrap();
+        let validator = LicenseValidator::new(Arc::clone(&manager), false).with_metrics(Arc::clone(&metrics));
+
+        let license = serde_json::to_vec(&serde_json::json!({
+            "license_id": "lic-1",
+            "customer_id": "cust-1",
+            "features": [],
+            "metadata": {},
+            "expires_at": (chrono::Utc::now() + chrono::Duration::days(1)).to_rfc3339(),
+            "issued_at": chrono::Utc::now().to_rfc3339(),
+        }))
+        .unwrap();
+        let token = general_purpose::STANDARD.encode(encrypt_license_v2(&[7u8; 32], 1, &license).unwrap());
+
+        assert!(validator.validate(token.as_bytes()).await.is_ok());
+        assert!(validator.validate(b"garbage").await.is_err());
+        metrics.update_degraded_mode(true, None);
+
+        let mut buffer = Vec::new();
+        prometheus::TextEncoder::new().encode(&registry.gather(), &mut buffer).unwrap();
+        let text = String::from_utf8(buffer).unwrap();
+        assert!(text.contains("license_agent_validations_total 2"));
+        assert!(text.contains("license_agent_validations_successful 1"));
+        assert!(text.contains("license_agent_validations_failed 1"));
+        assert!(text.contains("license_agent_validation_duration_seconds_count 2"));
+        assert!(text.contains("license_agent_degraded_mode_active 1"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_state_file_crash_safety() {
+        use license_secret_agent::state::{decode_state, STATE_SCHEMA_VERSION};
+
+        let dir = temp_dir("state");
+        let state_path = dir.join("state.json");
+        let backup_path = dir.join("state.json.bak");
+        let manager = software_secret_manager(&dir);
+        manager.store_secret(test_secret(1, 3), 1).await.unwrap();
+        manager.store_secret(test_secret(2, 4), 2).await.unwrap();
+
+        // Enveloppe versionnée, version précédente conservée, pas de fichier temporaire
+        let file: serde_json::Value = serde_json::from_slice(&std::fs::read(&state_path).unwrap()).unwrap();
+        assert_eq!(file["schema_version"], STATE_SCHEMA_VERSION);
+        assert_eq!(file["checksum"].as_str().unwrap().len(), 64);
+        assert!(backup_path.exists());
+        assert!(!dir.join("state.json.tmp").exists());
+        let backup = std::fs::read(&backup_path).unwrap();
+
+        let versions = |manager: &license_secret_agent::secret::SecretManager| {
+            let mut versions = manager.list_versions();
+            versions.sort_unstable();
+            versions
+        };
+
+        // Fichier tronqué par une coupure : reprise depuis la sauvegarde
+        let content = std::fs::read(&state_path).unwrap();
+        std::fs::write(&state_path, &content[..content.len() / 2]).unwrap();
+        let reloaded = software_secret_manager(&dir);
+        reloaded.load_state().await.unwrap();
+        assert_eq!(versions(&reloaded), vec![1]);
+
+        // Le fichier corrompu ne remplace pas la sauvegarde à l'écriture suivante
+        reloaded.save_state().await.unwrap();
+        assert_eq!(std::fs::read(&backup_path).unwrap(), backup);
+
+        // Contenu modifié sans recalculer la somme de contrôle
+        let mut tampered = file.clone();
+        tampered["state"]["active_version"] = serde_json::json!(7);
+        let tampered = serde_json::to_vec(&tampered).unwrap();
+        assert!(decode_state::<serde_json::Value>(&tampered, None).unwrap_err().to_string().contains("checksum"));
+
+        // Les deux copies illisibles : échec explicite au démarrage
+        std::fs::write(&state_path, &tampered).unwrap();
+        std::fs::write(&backup_path, b"{").unwrap();
+        assert!(software_secret_manager(&dir).load_state().await.is_err());
+
+        // Fichier principal absent (coupure entre les deux rename)
+        std::fs::remove_file(&state_path).unwrap();
+        std::fs::write(&backup_path, &content).unwrap();
+        let reloaded = software_secret_manager(&dir);
+        reloaded.load_state().await.unwrap();
+        assert_eq!(versions(&reloaded), vec![1, 2]);
+
+        // Schéma 1 (sans enveloppe) migré tant que l'état n'a jamais été authentifié
+        let legacy_dir = temp_dir("crash-safety-legacy");
+        std::fs::write(legacy_dir.join("state.json"), serde_json::to_vec(&file["state"]).unwrap()).unwrap();
+        let migrated = software_secret_manager(&legacy_dir);
+        migrated.load_state().await.unwrap();
+        assert_eq!(versions(&migrated), vec![1, 2]);
+        assert!(migrated.tamper_status().is_none());
+        assert!(legacy_dir.join("state.json.key").exists());
+        std::fs::remove_dir_all(&legacy_dir).unwrap();
+
+        // Schéma plus récent refusé
+
+        let mut newer = file.clone();
+        newer["schema_version"] = serde_json::json!(STATE_SCHEMA_VERSION + 1);
+        std::fs::write(&state_path, serde_json::to_vec(&newer).unwrap()).unwrap();
+        std::fs::write(&backup_path, &content).unwrap();
+        let error = software_secret_manager(&dir).load_state().await.unwrap_err();
+        assert!(matches!(error, AgentError::ConfigError(_)));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_state_file_tamper_response() {
+        use license_secret_agent::crypto::sha256;
+        use license_secret_agent::state::decode_state;
+
+        let dir = temp_dir("tamper");
+        let state_path = dir.join("state.json");
+        let manager = software_secret_manager(&dir);
+        manager.store_secret(test_secret(1, 3), 1).await.unwrap();
+        assert!(dir.join("state.json.key").exists());
+        assert!(manager.tamper_status().is_none());
+
+        // Somme de contrôle recalculée mais pas le MAC : état forgé
+        let mut file: serde_json::Value = serde_json::from_slice(&std::fs::read(&state_path).unwrap()).unwrap();
+        file["state"]["secrets"]["1"]["valid_until"] = serde_json::json!("2099-01-01T00:00:00Z");
+        let canonical = serde_json::to_vec(&serde_json::json!({
+            "schema_version": file["schema_version"],
+            "state": file["state"],
+        }))
+        .unwrap();
+        file["checksum"] = serde_json::json!(hex::encode(sha256(&canonical)));
+        let forged = serde_json::to_vec(&file).unwrap();
+        assert!(decode_state::<serde_json::Value>(&forged, None).unwrap_err().to_string().contains("key is missing"));
+        std::fs::write(&state_path, &forged).unwrap();
+
+        // Démarrage : fichiers mis de côté, aucun secret servi
+        let reloaded = software_secret_manager(&dir);
+        reloaded.load_state().await.unwrap();
+        let tamper = reloaded.tamper_status().unwrap();
+        assert!(tamper.reason.contains("MAC mismatch"));
+        assert!(!tamper.quarantined.is_empty());
+        assert!(tamper.quarantined.iter().all(|path| std::path::Path::new(path).exists()));
+        assert!(reloaded.list_versions().is_empty());
+        assert!(matches!(reloaded.get_secret(1).await, Err(AgentError::SecretInvalid(_))));
+
+        // La réponse survit au redémarrage, jusqu'au reset
+        let restarted = software_secret_manager(&dir);
+        restarted.load_state().await.unwrap();
+        assert_eq!(restarted.tamper_status().unwrap().detected_at, tamper.detected_at);
+        restarted.reset().await.unwrap();
+        restarted.store_secret(test_secret(2, 5), 2).await.unwrap();
+        let recovered = software_secret_manager(&dir);
+        recovered.load_state().await.unwrap();
+        assert!(recovered.tamper_status().is_none());
+        assert_eq!(recovered.get_secret(2).await.unwrap().data, vec![5u8; 32]);
+
+        // Clé non descellable (PCR, TPM) : pas d'altération, état ni chargé ni réécrit
+        let sealed_key = std::fs::read(dir.join("state.json.key")).unwrap();
+        let state_file = std::fs::read(&state_path).unwrap();
+        std::fs::write(dir.join("state.json.key"), b"not a sealed key").unwrap();
+        let unsealable = software_secret_manager(&dir);
+        unsealable.load_state().await.unwrap();
+        assert!(unsealable.tamper_status().is_none());
+        assert!(unsealable.state_key_failure().unwrap().contains("cannot be unsealed"));
+        assert!(matches!(unsealable.get_secret(2).await, Err(AgentError::TpmError(_))));
+        assert!(matches!(unsealable.save_state().await, Err(AgentError::TpmError(_))));
+        assert_eq!(std::fs::read(&state_path).unwrap(), state_file);
+        assert_eq!(std::fs::read(dir.join("state.json.key")).unwrap(), b"not a sealed key");
+
+        // Clé de nouveau lisible (état de démarrage restauré) : secrets intacts
+        std::fs::write(dir.join("state.json.key"), &sealed_key).unwrap();
+        let restored = software_secret_manager(&dir);
+        restored.load_state().await.unwrap();
+        assert!(restored.state_key_failure().is_none());
+        assert_eq!(restored.get_secret(2).await.unwrap().data, vec![5u8; 32]);
+
+        // Sinon seul le reset, qui abandonne l'état, remet l'agent en service
+        std::fs::write(dir.join("state.json.key"), b"not a sealed key").unwrap();
+        let unsealable = software_secret_manager(&dir);
+        unsealable.load_state().await.unwrap();
+        unsealable.reset().await.unwrap();
+        assert!(unsealable.state_key_failure().is_none());
+        let reset = software_secret_manager(&dir);
+        reset.load_state().await.unwrap();
+        assert!(reset.state_key_failure().is_none() && reset.tamper_status().is_none());
+        assert!(reset.list_versions().is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        // Clé supprimée et état de schéma 2 (sans MAC) : pas de migration après une première authentification
+        let dir = temp_dir("downgrade");
+        let state_path = dir.join("state.json");
+        software_secret_manager(&dir).store_secret(test_secret(1, 3), 1).await.unwrap();
+        std::fs::remove_file(dir.join("state.json.key")).unwrap();
+        let mut file: serde_json::Value = serde_json::from_slice(&std::fs::read(&state_path).unwrap()).unwrap();
+        file["state"]["secrets"]["1"]["valid_until"] = serde_json::json!("2099-01-01T00:00:00Z");
+        let canonical = serde_json::to_vec(&serde_json::json!({ "schema_version": 2, "state": file["state"] })).unwrap();
+        let downgraded = serde_json::json!({
+            "schema_version": 2,
+            "checksum": hex::encode(sha256(&canonical)),
+            "state": file["state"],
+        });
+        std::fs::write(&state_path, serde_json::to_vec(&downgraded).unwrap()).unwrap();
+        std::fs::remove_file(dir.join("state.json.bak")).ok();
+
+        let reloaded = software_secret_manager(&dir);
+        reloaded.load_state().await.unwrap();
+        let tamper = reloaded.tamper_status().unwrap();
+        assert!(tamper.reason.contains("key is missing"));
+        assert!(reloaded.list_versions().is_empty());
+
+        // Le marqueur ne peut pas être retiré du fichier de KEK sans le rendre illisible
+        let mut kek: serde_json::Value = serde_json::from_slice(&std::fs::read(dir.join("kek.json")).unwrap()).unwrap();
+        assert_eq!(kek["state_authenticated"], serde_json::json!(true));
+        kek.as_object_mut().unwrap().remove("state_authenticated");
+        std::fs::write(dir.join("kek.json"), serde_json::to_vec(&kek).unwrap()).unwrap();
+        assert!(license_secret_agent::kek::KeyHierarchy::open(dir.join("kek.json"), b"test-seed", b"machine-a").is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Envoie une requête IPC sur une connexion à l'agent
+    async fn send_ipc(
+        stream: &mut tokio::net::UnixStream,
+        id: Option<u64>,
+        request: license_secret_agent::protocol::IpcRequest,
+        auth: Option<license_secret_agent::protocol::AdminCredential>,
+    ) {
+        use license_secret_agent::protocol::{write_frame, RequestEnvelope, PROTOCOL_VERSION};
+
+        let envelope = RequestEnvelope { version: PROTOCOL_VERSION, id, request, auth };
+        write_frame(stream, &serde_json::to_vec(&envelope).unwrap()).await.unwrap();
+    }
+
+    async fn recv_ipc(stream: &mut tokio::net::UnixStream) -> license_secret_agent::protocol::ResponseEnvelope {
+        let frame = license_secret_agent::protocol::read_frame(stream).await.unwrap();
+        serde_json::from_slice(&frame).unwrap()
+    }
+
+    /// Attend la fermeture de la connexion par l'agent (au plus 5 secondes)
+    async fn assert_closed_by_agent(stream: &mut tokio::net::UnixStream) {
+        let mut buffer = [0u8; 4];
+        let read = tokio::time::timeout(
+            std::time::Duration::from_secs(5),
+            tokio::io::AsyncReadExt::read(stream, &mut buffer),
+        )
+        .await
+        .expect("connection still open");
+        assert_eq!(read.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_ipc_operator_commands_without_admin_credentials() {
+        use license_secret_agent::protocol::{ErrorCode, IpcRequest, IpcResponse};
+        use license_secret_agent::roles::Role;
+        use tokio::net::UnixStream;
+
+        // Rôle `operator` : commandes d'exploitation sans jeton ni signature
+        let agent = TestAgent::start(|management| bind_current_uid(management, Role::Operator)).await;
+        let mut stream = UnixStream::connect(agent.socket()).await.unwrap();
+        send_ipc(&mut stream, None, IpcRequest::Status {}, None).await;
+        assert!(matches!(recv_ipc(&mut stream).await.response, Some(IpcResponse::Status(_))));
+        send_ipc(&mut stream, None, IpcRequest::Reset {}, None).await;
+        assert_eq!(recv_ipc(&mut stream).await.error.unwrap().code, ErrorCode::Forbidden);
+        drop(agent);
+
+        // Rôle `admin` : les commandes d'administration exigent toujours des identifiants
+        let agent = TestAgent::start(|management| bind_current_uid(management, Role::Admin)).await;
+        let mut stream = UnixStream::connect(agent.socket()).await.unwrap();
+        send_ipc(&mut stream, None, IpcRequest::Status {}, None).await;
+        assert!(matches!(recv_ipc(&mut stream).await.response, Some(IpcResponse::Status(_))));
+        send_ipc(&mut stream, None, IpcRequest::Reset {}, None).await;
+        assert_eq!(recv_ipc(&mut stream).await.error.unwrap().code, ErrorCode::Unauthorized);
+    }
+
+    #[tokio::test]
+    async fn test_ipc_persistent_connections() {
+        use license_secret_agent::protocol::{IpcRequest, IpcResponse, PROTOCOL_VERSION};
+        use tokio::io::AsyncWriteExt;
+        use tokio::net::UnixStream;
+
+        let uid = unsafe { libc::getuid() };
+        let agent = TestAgent::start(|management| {
+            management.allowed_uids = vec![uid];
+            management.ipc_idle_timeout_seconds = Some(1);
+            management.ipc_frame_timeout_seconds = Some(2);
+            management.ipc_max_requests_per_connection = Some(3);
+            management.ipc_max_connections = Some(1);
+        })
+        .await;
+        let validate = || {
+            IpcRequest::Validate(ValidateLicenseRequest {
+                license_token: b"garbage".to_vec(),
+                nonce: *uuid::Uuid::new_v4().as_bytes(),
+                app_id: None,
+            })
+        };
+
+        // Requêtes pipelinées : une réponse par id, sur la même connexion
+        let mut stream = UnixStream::connect(agent.socket()).await.unwrap();
+        send_ipc(&mut stream, Some(1), validate(), None).await;
+        send_ipc(&mut stream, Some(2), validate(), None).await;
+        let mut ids = Vec::new();
+        for _ in 0..2 {
+            let response = recv_ipc(&mut stream).await;
+            assert!(matches!(response.response, Some(IpcResponse::Validate(_))));
+            ids.push(response.id);
+        }
+        ids.sort();
+        assert_eq!(ids, vec![Some(1), Some(2)]);
+
+        // Corps envoyé lentement : le délai d'inactivité ne porte que sur l'attente de la requête
+        let frame = serde_json::to_vec(&serde_json::json!({
+            "version": PROTOCOL_VERSION,
+            "command": "validate",
+            "data": { "license_token": [1, 2, 3], "nonce": vec![9u8; 16] },
+        }))
+        .unwrap();
+        stream.write_all(&(frame.len() as u32).to_be_bytes()).await.unwrap();
+        stream.write_all(&frame[..10]).await.unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(1500)).await;
+        stream.write_all(&frame[10..]).await.unwrap();
+        assert!(matches!(recv_ipc(&mut stream).await.response, Some(IpcResponse::Validate(_))));
+
+        // Nombre maximal de requêtes atteint : connexion fermée par l'agent
+        assert_closed_by_agent(&mut stream).await;
+
+        // Connexion inactive fermée après le délai
+        let mut idle = UnixStream::connect(agent.socket()).await.unwrap();
+        assert_closed_by_agent(&mut idle).await;
+
+        // Préfixe de longueur sans corps : connexion fermée après le délai de trame
+        let mut stalled = UnixStream::connect(agent.socket()).await.unwrap();
+        stalled.write_all(&(1024u32 * 1024).to_be_bytes()).await.unwrap();
+        let started = std::time::Instant::now();
+        assert_closed_by_agent(&mut stalled).await;
+        assert!(started.elapsed() >= std::time::Duration::from_millis(1500));
+
+        // Plafond d'une connexion : la suivante n'est servie qu'après la fermeture de la première
+        let mut first = UnixStream::connect(agent.socket()).await.unwrap();
+        send_ipc(&mut first, None, validate(), None).await;
+        assert!(matches!(recv_ipc(&mut first).await.response, Some(IpcResponse::Validate(_))));
+        let started = std::time::Instant::now();
+        let mut second = UnixStream::connect(agent.socket()).await.unwrap();
+        send_ipc(&mut second, None, validate(), None).await;
+        assert!(matches!(recv_ipc(&mut second).await.response, Some(IpcResponse::Validate(_))));
+        assert!(started.elapsed() >= std::time::Duration::from_millis(700));
+        assert_closed_by_agent(&mut first).await;
+    }
+
+    #[tokio::test]
+    async fn test_config_paths() {
+        use license_secret_agent::config::{Config, PathsConfig};
+        use license_secret_agent::core::CoreEngine;
+        use std::ffi::OsString;
+
+        let dir = temp_dir("paths");
+        let config_path = dir.join("config.toml");
+        let write_config = |paths: &str| write_agent_config(&config_path, paths);
+
+        // Section [paths] : tous les fichiers sous des répertoires propres à l'instance
+        write_config(&format!(
+            "[paths]\nstate_dir = \"{dir}/state\"\naudit_log = \"{dir}/log/audit.log\"\nruntime_dir = \"{dir}/run\"",
+            dir = dir.display()
+        ));
+        let config = Config::load_from_path(&config_path).unwrap();
+        assert_eq!(config.state_path(), dir.join("state/state.json"));
+        assert_eq!(config.secret_store_path(), dir.join("state/secrets"));
+        assert_eq!(config.kek_path(), dir.join("state/kek.json"));
+        assert_eq!(config.app_keys_path(), dir.join("state/apps"));
+        assert_eq!(config.audit_log_path(), dir.join("log/audit.log"));
+        assert_eq!(config.ipc_socket_path(), dir.join("run/license-agent.sock"));
+
+        let engine = CoreEngine::new(config).await.unwrap();
+        assert!(engine.get_status().await.unwrap().active_secret.is_none());
+        assert!(dir.join("log/audit.log").exists());
+        assert!(dir.join("state/kek.json").exists());
+
+        // Sans [paths] ni répertoire systemd : socket par défaut des clients
+        if std::env::var_os("RUNTIME_DIRECTORY").is_none() {
+            write_config("");
+            assert_eq!(
+                Config::load_from_path(&config_path).unwrap().ipc_socket_path(),
+                std::path::Path::new(license_secret_agent::config::DEFAULT_SOCKET_PATH)
+            );
+        }
+
+        // Répertoires de systemd : utilisés en l'absence de valeur explicite
+        let mut paths = PathsConfig {
+            runtime_dir: Some(dir.join("explicit")),
+            ..Default::default()
+        };
+        paths.apply_systemd_directories(|name| match name {
+            "STATE_DIRECTORY" => Some(OsString::from("/var/lib/agent-a:/var/lib/agent-a-extra")),
+            "LOGS_DIRECTORY" => Some(OsString::from("/var/log/agent-a")),
+            "RUNTIME_DIRECTORY" => Some(OsString::from("/run/agent-a")),
+            _ => None,
+        });
+        assert_eq!(paths.state_dir.unwrap(), std::path::Path::new("/var/lib/agent-a"));
+        assert_eq!(paths.audit_log.unwrap(), std::path::Path::new("/var/log/agent-a/audit.log"));
+        assert_eq!(paths.runtime_dir.unwrap(), dir.join("explicit"));
+
+        // Ancien emplacement et [paths] en conflit
+        write_config("[paths]\nsocket = \"/tmp/a.sock\"");
+        let conflicting = std::fs::read_to_string(&config_path)
+            .unwrap()
+            .replace("allowed_uids = [1000]", "allowed_uids = [1000]\nipc_socket_path = \"/tmp/b.sock\"");
+        std::fs::write(&config_path, conflicting).unwrap();
+        let error = Config::load_from_path(&config_path).unwrap_err();
+        assert!(error.to_string().contains("mutually exclusive"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_daemon_cli() {
+        use std::process::Command;
+
+        let agent = env!("CARGO_BIN_EXE_license-agent");
+        let dir = temp_dir("daemon");
+        let config_path = dir.join("config.toml");
+        write_agent_config(
+            &config_path,
+            &format!("[paths]\nstate_dir = \"{dir}/state\"\naudit_log = \"{dir}/audit.log\"", dir = dir.display()),
+        );
+
+        let version = Command::new(agent).arg("--version").output().unwrap();
+        assert!(version.status.success());
+        let version = String::from_utf8(version.stdout).unwrap();
+        assert!(version.starts_with(&format!("license-agent {}", env!("CARGO_PKG_VERSION"))));
+        assert_eq!(version.contains("tpm"), cfg!(feature = "tpm"));
+
+        // Configuration d'exemple : TOML valide, avec toutes les sections obligatoires
+        let default_config = Command::new(agent).arg("--print-default-config").output().unwrap();
+        assert!(default_config.status.success());
+        let default_config: toml::Value = toml::from_str(std::str::from_utf8(&default_config.stdout).unwrap()).unwrap();
+        for section in ["server", "agent", "tpm", "management", "degraded_mode"] {
+            assert!(default_config.get(section).is_some(), "missing [{}]", section);
+        }
+
+        // Diagnostic : chemins résolus, sans démarrer l'agent
+        let check = Command::new(agent).arg("--check-config").arg("--config").arg(&config_path).output().unwrap();
+        assert!(check.status.success());
+        let diagnostic = String::from_utf8(check.stdout).unwrap();
+        assert!(diagnostic.contains("Configuration valide"));
+        assert!(diagnostic.contains(&dir.join("state/state.json").display().to_string()));
+        assert!(!dir.join("state").exists());
+
+        let invalid = dir.join("invalid.toml");
+        write_agent_config(&invalid, "[paths]\nsecret_store = \"/tmp/a\"");
+        let invalid = std::fs::read_to_string(&invalid)
+            .unwrap()
+            .replace("enabled = false", "enabled = false\nfallback_encrypted_storage = \"/tmp/b\"");
+        std::fs::write(dir.join("invalid.toml"), invalid).unwrap();
+        let check = Command::new(agent)
+            .args(["--check-config", "--log-format", "json", "-c"])
+            .arg(dir.join("invalid.toml"))
+            .output()
+            .unwrap();
+        assert_eq!(check.status.code(), Some(1));
+        assert!(String::from_utf8(check.stderr).unwrap().contains("mutually exclusive"));
+
+        let missing = Command::new(agent).args(["--check-config", "-c"]).arg(dir.join("missing.toml")).output().unwrap();
+        assert_eq!(missing.status.code(), Some(1));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Nécessite un simulateur TPM (swtpm/mssim) sur localhost:2321 :
+    /// `cargo test --features tpm -- --ignored`
+    #[cfg(feature = "tpm")]
+    #[tokio::test]
+    #[ignore = "requires a TPM simulator on localhost:2321"]
+    async fn test_tpm_sealing_with_simulator() {
+        use license_secret_agent::kek::KeyHierarchy;
+        use license_secret_agent::secret::SecretManager;
+        use license_secret_agent::tpm::{TpmManager, NV_SLOT_MAX, NV_STATE_SLOT};
+        use std::sync::Arc;
+
+        let dir = temp_dir("tpm");
+        let kek = KeyHierarchy::open(dir.join("kek.json"), b"test-seed", b"machine-a").unwrap();
+        let tpm = Arc::new(TpmManager::new(true, Arc::new(kek), None).unwrap());
+        assert!(tpm.is_available(), "TPM simulator not reachable");
+
+        // Scellement direct : déchiffrable uniquement pour la même version
+        let sealed = tpm.encrypt(1, b"tpm-secret").unwrap();
+        assert_eq!(tpm.decrypt(1, &sealed).unwrap(), b"tpm-secret");
+        assert!(tpm.decrypt(2, &sealed).is_err());
+
+        let manager = SecretManager::new(Arc::clone(&tpm), dir.join("state.json"), dir.join("secrets"));
+        let usage_before = tpm.nv_usage().unwrap();
+        manager.store_secret(test_secret(1, 9), 1).await.unwrap();
+        assert_eq!(manager.get_secret(1).await.unwrap().data, vec![9u8; 32]);
+        assert!(!dir.join("secrets").exists());
+
+        // Clé de l'état dans son emplacement réservé, versions hors plage refusées
+        assert!(tpm.nv_read(NV_STATE_SLOT).unwrap().is_some());
+        assert!(!dir.join("state.json.key").exists());
+        assert!(manager.store_secret(test_secret(NV_SLOT_MAX as u64 + 1, 9), NV_SLOT_MAX as u64 + 1).await.is_err());
+        assert!(manager.store_secret(test_secret(u64::MAX, 9), u64::MAX).await.is_err());
+
+        let usage = tpm.nv_usage().unwrap();
+        assert!(usage.indices >= usage_before.indices);
+        assert!(usage.bytes > 0);
+        let status = tpm.get_status();
+        assert!(status.nv_space_used.is_some());
+        assert!(status.manufacturer.is_some());
+        assert!(status.version.as_deref().is_some_and(|v| v.starts_with("2.0")));
+        assert!(status.lockout.is_some_and(|l| l.max_tries > 0));
+
+        // La réinitialisation libère les NV Index
+        manager.reset().await.unwrap();
+        assert!(tpm.nv_read(1).unwrap().is_none());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(feature = "tpm")]
+    #[tokio::test]
+    #[ignore = "requires a TPM simulator on localhost:2321"]
+    async fn test_tpm_pcr_policy_reseal_with_simulator() {
+        use license_secret_agent::config::PcrPolicyConfig;
+        use license_secret_agent::kek::KeyHierarchy;
+        use license_secret_agent::secret::SecretManager;
+        use license_secret_agent::tpm::TpmManager;
+        use std::sync::Arc;
+
+        let dir = temp_dir("pcr");
+        let kek = KeyHierarchy::open(dir.join("kek.json"), b"test-seed", b"machine-a").unwrap();
+        let policy = PcrPolicyConfig { bank: "sha256".to_string(), pcrs: vec![0, 7] };
+        let tpm = Arc::new(TpmManager::new(true, Arc::new(kek), Some(policy)).unwrap());
+        assert!(tpm.is_available(), "TPM simulator not reachable");
+
+        let manager = SecretManager::new(Arc::clone(&tpm), dir.join("state.json"), dir.join("secrets"));
+        manager.store_secret(test_secret(1, 5), 1).await.unwrap();
+        assert_eq!(manager.get_secret(1).await.unwrap().data, vec![5u8; 32]);
+
+        // Fenêtre de mise à jour : scellement sans PCR, puis retour à la liaison PCR
+        assert_eq!(manager.reseal(true).await.unwrap(), 1);
+        assert!(tpm.get_status().pcr_binding_suspended);
+        assert_eq!(manager.get_secret(1).await.unwrap().data, vec![5u8; 32]);
+
+        assert_eq!(manager.reseal(false).await.unwrap(), 1);
+        let status = tpm.get_status();
+        assert_eq!(status.pcr_policy.as_deref(), Some("sha256:0,7"));
+        assert!(!status.pcr_binding_suspended);
+        assert!(status.pcr_policy_failure.is_none());
+        assert_eq!(manager.get_secret(1).await.unwrap().data, vec![5u8; 32]);
+
+        manager.reset().await.unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 }